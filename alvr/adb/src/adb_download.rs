@@ -0,0 +1,278 @@
+use crate::commands::{self, AdbSource};
+use crate::server_lease::ServerLease;
+use crate::{
+    AdbError, AdbResult, WiredCancelHandle, WiredConnection, WiredConnectionStatus, WiredMessage,
+    WiredStatusKind, fail,
+};
+use alvr_common::anyhow::anyhow;
+use alvr_common::{info, warn};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+pub(crate) type DownloadProgressCallback = Box<dyn Fn(usize, Option<usize>) + Send>;
+
+// Where ADB is installed, kept to install it again if its executable disappears
+#[derive(Clone)]
+pub(crate) struct AdbLocation {
+    pub(crate) layout: alvr_filesystem::Layout,
+    pub(crate) adb_dir: Option<PathBuf>,
+    pub(crate) adb_env: HashMap<String, String>,
+}
+
+// The callback is given back to pass it to the next attempt
+type DownloadResult = (AdbResult<(String, AdbSource)>, DownloadProgressCallback);
+
+/// ADB being installed on a background thread, see `WiredConnectionBuilder::build_in_background`
+pub(crate) struct AdbDownload {
+    location: AdbLocation,
+    cancel_handle: WiredCancelHandle,
+    // Downloaded and total size of the current attempt
+    progress: Arc<Mutex<(usize, Option<usize>)>>,
+    thread: Option<JoinHandle<DownloadResult>>,
+    // Set between attempts, after a failure
+    progress_callback: Option<DownloadProgressCallback>,
+}
+
+impl AdbDownload {
+    pub(crate) fn start(
+        location: AdbLocation,
+        cancel_handle: WiredCancelHandle,
+        progress_callback: DownloadProgressCallback,
+    ) -> Self {
+        let mut download = Self {
+            location,
+            cancel_handle,
+            progress: Arc::new(Mutex::new((0, None))),
+            thread: None,
+            progress_callback: Some(progress_callback),
+        };
+        download.spawn();
+
+        download
+    }
+
+    fn spawn(&mut self) {
+        let Some(progress_callback) = self.progress_callback.take() else {
+            return;
+        };
+        *self.progress.lock().unwrap() = (0, None);
+
+        let location = self.location.clone();
+        let cancel_handle = self.cancel_handle.clone();
+        let progress = Arc::clone(&self.progress);
+        self.thread = Some(thread::spawn(move || {
+            let res = require_adb_in(&location, &cancel_handle, |downloaded, total| {
+                *progress.lock().unwrap() = (downloaded, total);
+                progress_callback(downloaded, total);
+            });
+
+            (res, progress_callback)
+        }));
+    }
+}
+
+// Installs ADB in `adb_dir` instead of the executables directory of the layout if set. `adb_env`
+// applies to the commands run to check the installed ADB too.
+pub(crate) fn require_adb_in(
+    location: &AdbLocation,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<(String, AdbSource)> {
+    commands::set_adb_env(&location.adb_env)?;
+
+    let layout = match &location.adb_dir {
+        Some(adb_dir) => {
+            commands::ensure_writable_dir(adb_dir)?;
+
+            alvr_filesystem::Layout {
+                executables_dir: adb_dir.clone(),
+                ..location.layout.clone()
+            }
+        }
+        None => location.layout.clone(),
+    };
+
+    commands::require_pinned_adb(&layout, None, cancel_handle, progress_callback)
+}
+
+impl WiredConnection {
+    /// Whether ADB is still being installed in the background, see
+    /// `WiredConnectionBuilder::build_in_background`
+    pub fn is_downloading_adb(&self) -> bool {
+        self.adb_download.is_some()
+    }
+
+    // Returns the status of the setup until ADB is installed. A failed attempt is returned once,
+    // the next call starts another one.
+    pub(crate) fn poll_adb_download(&mut self) -> AdbResult<Option<WiredConnectionStatus>> {
+        let Some(download) = &mut self.adb_download else {
+            return Ok(None);
+        };
+
+        if download.thread.is_none() {
+            download.spawn();
+        }
+        let Some(thread) = download.thread.take_if(|thread| thread.is_finished()) else {
+            let (downloaded, total) = *download.progress.lock().unwrap();
+
+            return Ok(Some(downloading_adb_status(downloaded, total)));
+        };
+        let (res, progress_callback) = thread.join().unwrap_or_else(|_| {
+            (
+                fail(AdbError::Other(anyhow!("ADB download thread panicked"))),
+                Box::new(|_, _| ()),
+            )
+        });
+        download.progress_callback = Some(progress_callback);
+        let (adb_path, adb_source) = res?;
+
+        info!("wired_connection: Using {adb_source:?} ADB");
+        self.adb_download = None;
+        self.server_lease = Some(ServerLease::acquire(adb_path.clone()));
+        self.use_adb(adb_path, adb_source);
+
+        Ok(None)
+    }
+
+    // Installs ADB again after its executable disappeared, e.g. quarantined by an antivirus, and
+    // uses it from then on. The connections given an adb path can't install it.
+    pub(crate) fn reinstall_adb(&mut self) -> AdbResult<()> {
+        let Some(location) = &self.adb_location else {
+            return fail(AdbError::Other(anyhow!(
+                "ADB not found at {}",
+                self.adb_path
+            )));
+        };
+        warn!(
+            "wired_connection: ADB not found at {}, installing it again",
+            self.adb_path
+        );
+        let (adb_path, adb_source) = require_adb_in(location, &self.cancel_handle, |_, _| ())?;
+
+        info!("wired_connection: Using {adb_source:?} ADB at {adb_path}");
+        if self.server_lease.is_some() {
+            // Acquired before the old one is released, which would kill the server
+            self.server_lease = Some(ServerLease::acquire(adb_path.clone()));
+        }
+        self.use_adb(adb_path, adb_source);
+
+        Ok(())
+    }
+
+    fn use_adb(&mut self, adb_path: String, adb_source: AdbSource) {
+        self.adb_version = commands::get_adb_version(&adb_path).ok();
+        self.device_watcher.adb_path = adb_path.clone();
+        for connection in self.other_devices.values_mut() {
+            connection.use_adb(adb_path.clone(), adb_source);
+        }
+        self.adb_path = adb_path;
+        self.adb_source = adb_source;
+    }
+}
+
+pub(crate) fn adb_missing_status(error: String) -> WiredConnectionStatus {
+    WiredConnectionStatus::not_ready(
+        WiredStatusKind::AdbMissing,
+        WiredMessage::AdbMissing { error },
+    )
+}
+
+fn downloading_adb_status(downloaded: usize, total: Option<usize>) -> WiredConnectionStatus {
+    let message = match total {
+        Some(total) if total > 0 => WiredMessage::DownloadingAdb {
+            percent: downloaded * 100 / total,
+        },
+        _ => WiredMessage::DownloadingAdbSize {
+            kilobytes: downloaded / 1024,
+        },
+    };
+
+    WiredConnectionStatus::not_ready(
+        WiredStatusKind::DownloadingAdb { downloaded, total },
+        message,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_poll_adb_download() {
+        let mut connection =
+            WiredConnection::with_adb_path(String::new(), AdbSource::Downloaded, None);
+        let (finish_sender, finish_receiver) = mpsc::channel::<()>();
+        let progress = Arc::new(Mutex::new((0, None)));
+        connection.adb_download = Some(AdbDownload {
+            location: AdbLocation {
+                layout: alvr_filesystem::Layout::default(),
+                adb_dir: None,
+                adb_env: HashMap::new(),
+            },
+            cancel_handle: WiredCancelHandle::default(),
+            progress: Arc::clone(&progress),
+            thread: Some(thread::spawn(move || {
+                finish_receiver.recv().ok();
+                let callback: DownloadProgressCallback = Box::new(|_, _| ());
+
+                (fail(AdbError::Other(anyhow!("No internet"))), callback)
+            })),
+            progress_callback: None,
+        });
+
+        *progress.lock().unwrap() = (1024, Some(4096));
+        assert_eq!(
+            connection.poll_adb_download().unwrap(),
+            Some(WiredConnectionStatus::not_ready(
+                WiredStatusKind::DownloadingAdb {
+                    downloaded: 1024,
+                    total: Some(4096)
+                },
+                WiredMessage::DownloadingAdb { percent: 25 }
+            ))
+        );
+
+        // The failure is returned once, with its detail
+        finish_sender.send(()).unwrap();
+        while !connection
+            .adb_download
+            .as_ref()
+            .and_then(|download| download.thread.as_ref())
+            .is_some_and(JoinHandle::is_finished)
+        {
+            thread::yield_now();
+        }
+        let e = connection.poll_adb_download().unwrap_err();
+        assert!(format!("{e:#}").contains("No internet"));
+        assert!(connection.is_downloading_adb());
+        assert!(
+            connection
+                .adb_download
+                .as_ref()
+                .is_some_and(|download| download.progress_callback.is_some())
+        );
+    }
+
+    #[test]
+    fn test_missing_adb() {
+        let mut connection = WiredConnection::with_adb_path(
+            "/nonexistent/adb".to_owned(),
+            AdbSource::UserProvided,
+            Some("1WMHH000000000".to_owned()),
+        );
+
+        // Without a location to install ADB again, the failure is a status
+        let status = connection.tick().unwrap();
+        assert!(matches!(
+            status,
+            WiredConnectionStatus::NotReady {
+                kind: WiredStatusKind::AdbMissing,
+                ..
+            }
+        ));
+        assert!(connection.last_error.is_none());
+    }
+}
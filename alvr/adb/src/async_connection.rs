@@ -0,0 +1,195 @@
+use crate::{
+    AdbResult, WiredCancelHandle, WiredConnection, WiredConnectionStatus, WiredSetupParams,
+};
+use alvr_common::anyhow::Context;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::task;
+
+/// `WiredConnection` for callers running on a tokio runtime. The adb commands block for seconds
+/// (downloads, installs), they run on the blocking thread pool instead of the async workers.
+///
+/// Dropping a future returned by this type cancels its call: the setup stops before its next
+/// phase, and the downloads, installs and file transfers kill their adb process. An adb command
+/// already running is still waited for, so none is left behind. The next call can start once the
+/// cancelled one returned.
+pub struct AsyncWiredConnection {
+    inner: Arc<Mutex<WiredConnection>>,
+    cancel_handle: WiredCancelHandle,
+}
+
+impl AsyncWiredConnection {
+    pub async fn new(
+        layout: alvr_filesystem::Layout,
+        adb_dir: Option<PathBuf>,
+        adb_env: HashMap<String, String>,
+        download_progress_callback: impl Fn(usize, Option<usize>) + Send + 'static,
+    ) -> AdbResult<Self> {
+        let connection = task::spawn_blocking(move || {
+            WiredConnection::new(
+                &layout,
+                adb_dir.as_deref(),
+                &adb_env,
+                download_progress_callback,
+            )
+        })
+        .await
+        .context("ADB setup task failed")??;
+
+        Ok(Self::from(connection))
+    }
+
+    pub async fn setup(&self, params: WiredSetupParams) -> AdbResult<WiredConnectionStatus> {
+        self.run(move |connection| connection.setup_with_params(&params))
+            .await?
+    }
+
+    /// Handle to shut the connection down, it cancels the call in progress and every following
+    /// one. Within `run`, `WiredConnection::cancel_handle` only cancels the current call.
+    pub fn cancel_handle(&self) -> WiredCancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    /// Runs `f` on the blocking thread pool, for the methods of `WiredConnection` that have no
+    /// async version.
+    pub async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut WiredConnection) -> T + Send + 'static,
+    ) -> AdbResult<T> {
+        let inner = Arc::clone(&self.inner);
+        // Each call has its own handle, so that dropping one doesn't cancel the following ones
+        let call_cancel_handle = self.cancel_handle.child();
+        let cancel_on_drop = CancelOnDrop(Some(call_cancel_handle.clone()));
+
+        let value = task::spawn_blocking(move || {
+            // A panic in a previous call leaves the connection usable, like the sync version
+            let mut connection = inner.lock().unwrap_or_else(PoisonError::into_inner);
+            connection.cancel_handle = call_cancel_handle;
+
+            f(&mut connection)
+        })
+        .await
+        .context("ADB task failed")?;
+        cancel_on_drop.disarm();
+
+        Ok(value)
+    }
+}
+
+impl From<WiredConnection> for AsyncWiredConnection {
+    fn from(connection: WiredConnection) -> Self {
+        Self {
+            cancel_handle: connection.cancel_handle(),
+            inner: Arc::new(Mutex::new(connection)),
+        }
+    }
+}
+
+// Cancels the call of a future dropped before it completed
+struct CancelOnDrop(Option<WiredCancelHandle>);
+
+impl CancelOnDrop {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(cancel_handle) = &self.0 {
+            cancel_handle.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AdbSource, AdbTransport, ClientFlavor, MockTransport};
+    use std::future;
+    use std::io;
+    use std::process::Output;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::task::Poll;
+
+    const MOCK_SERIAL: &str = "1WMHH000000000";
+
+    // Blocks the first command until it is released
+    struct BlockingTransport {
+        mock: MockTransport,
+        blocked: AtomicBool,
+        started: Mutex<Sender<()>>,
+        release: Mutex<Receiver<()>>,
+    }
+
+    impl AdbTransport for BlockingTransport {
+        fn run_command(&self, adb_path: &str, args: &[String]) -> io::Result<Output> {
+            if !self.blocked.swap(true, Ordering::Relaxed) {
+                self.started.lock().unwrap().send(()).ok();
+                self.release.lock().unwrap().recv().ok();
+            }
+
+            self.mock.run_command(adb_path, args)
+        }
+    }
+
+    #[test]
+    fn test_drop_pending_setup() {
+        let (started_sender, started) = mpsc::channel();
+        let (release, release_receiver) = mpsc::channel();
+        let transport = Arc::new(BlockingTransport {
+            mock: MockTransport::new(),
+            blocked: AtomicBool::new(false),
+            started: Mutex::new(started_sender),
+            release: Mutex::new(release_receiver),
+        });
+        transport.mock.respond(
+            &["devices"],
+            &format!("List of devices attached\n{MOCK_SERIAL} device usb:1-1\n"),
+        );
+        transport.mock.respond(&["get-state"], "device\n");
+        transport
+            .mock
+            .respond(&["getprop", "sys.boot_completed"], "1\n");
+        let mut connection = WiredConnection::with_adb_path(
+            "adb".to_owned(),
+            AdbSource::UserProvided,
+            Some(MOCK_SERIAL.to_owned()),
+        );
+        connection.set_transport(Arc::clone(&transport) as Arc<dyn AdbTransport>);
+        let connection = AsyncWiredConnection::from(connection);
+        let params = WiredSetupParams::new(9943, 9944)
+            .client_type(ClientFlavor::Custom("com.example.client".to_owned()));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let mut setup = Box::pin(connection.setup(params.clone()));
+            // Polled once, so that the setup starts and blocks on its first command
+            future::poll_fn(|cx| {
+                assert!(setup.as_mut().poll(cx).is_pending());
+
+                Poll::Ready(())
+            })
+            .await;
+            started.recv().unwrap();
+            drop(setup);
+            release.send(()).unwrap();
+
+            // Waits for the dropped setup to return, it holds the connection until then
+            connection.run(|_| ()).await.unwrap();
+            assert!(!transport.mock.was_called(&["forward"]));
+
+            // Only the dropped call was cancelled
+            connection.setup(params).await.unwrap();
+            assert!(
+                transport
+                    .mock
+                    .was_called(&["forward", "tcp:9943", "tcp:9943"])
+            );
+        });
+    }
+}
@@ -0,0 +1,233 @@
+use crate::adb_download::{AdbDownload, AdbLocation};
+use crate::commands::AdbSource;
+use crate::{
+    AdbResult, AdbTransport, DEFAULT_DEVICE_LOST_GRACE, DEFAULT_MIN_TICK_INTERVAL,
+    ProcessNameResolver, WiredCancelHandle, WiredConnection, WiredEvent, WiredEventHandler,
+    WiredSetupParams, WiredTimingState,
+};
+use alvr_session::{WiredClientAutoLaunchConfig, WiredDeviceSetting};
+use alvr_system_info::ClientFlavor;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configures a `WiredConnection` once, so that `WiredConnection::tick` can run the setup without
+/// arguments. The setup parameters default to the ones of the default settings.
+pub struct WiredConnectionBuilder {
+    adb_dir: Option<PathBuf>,
+    adb_env: HashMap<String, String>,
+    cancel_handle: WiredCancelHandle,
+    device_serial: Option<String>,
+    params: WiredSetupParams,
+    event_handler: Option<WiredEventHandler>,
+    process_name_resolver: Option<ProcessNameResolver>,
+    cleanup_stray_servers: bool,
+    transport: Option<Arc<dyn AdbTransport>>,
+    device_lost_grace: Duration,
+    min_tick_interval: Duration,
+    timing_state: Option<WiredTimingState>,
+}
+
+impl WiredConnectionBuilder {
+    pub fn new() -> Self {
+        Self {
+            adb_dir: None,
+            adb_env: HashMap::new(),
+            cancel_handle: WiredCancelHandle::default(),
+            device_serial: None,
+            params: WiredSetupParams::default(),
+            event_handler: None,
+            process_name_resolver: None,
+            cleanup_stray_servers: false,
+            transport: None,
+            device_lost_grace: DEFAULT_DEVICE_LOST_GRACE,
+            min_tick_interval: DEFAULT_MIN_TICK_INTERVAL,
+            timing_state: None,
+        }
+    }
+
+    /// See `WiredConnection::new`
+    pub fn adb_dir(mut self, adb_dir: impl AsRef<Path>) -> Self {
+        self.adb_dir = Some(adb_dir.as_ref().to_owned());
+        self
+    }
+
+    /// See `WiredConnection::new`. With `build_in_background`, an invalid variable is returned by
+    /// the setup like a failed download.
+    pub fn adb_env(mut self, adb_env: HashMap<String, String>) -> Self {
+        self.adb_env = adb_env;
+        self
+    }
+
+    /// Aborts the download of ADB and becomes the handle of the connection
+    pub fn cancel_handle(mut self, cancel_handle: WiredCancelHandle) -> Self {
+        self.cancel_handle = cancel_handle;
+        self
+    }
+
+    /// Only considers the device with this serial, instead of the first wired device
+    pub fn device_filter(mut self, device_serial: impl Into<String>) -> Self {
+        self.device_serial = Some(device_serial.into());
+        self
+    }
+
+    pub fn client_flavor(mut self, client_flavor: ClientFlavor) -> Self {
+        self.params.client_type = client_flavor;
+        self
+    }
+
+    pub fn ports(mut self, control_port: u16, stream_port: u16) -> Self {
+        self.params.control_port = control_port;
+        self.params.stream_port = stream_port;
+        self
+    }
+
+    pub fn allow_background_client(mut self, allow: bool) -> Self {
+        self.params.allow_background_client = allow;
+        self
+    }
+
+    pub fn keep_display_awake(mut self, keep_awake: bool) -> Self {
+        self.params.keep_display_awake = keep_awake;
+        self
+    }
+
+    /// None disables auto-launch. The config includes the boot and launch delays.
+    pub fn autolaunch(mut self, config: Option<WiredClientAutoLaunchConfig>) -> Self {
+        self.params.client_autolaunch = config;
+        self
+    }
+
+    /// See `WiredConnection::set_device_settings`
+    pub fn device_settings(mut self, settings: Vec<WiredDeviceSetting>) -> Self {
+        self.params.device_settings = settings;
+        self
+    }
+
+    /// Shell command lines to run before each launch of the client. Each one is logged, and a
+    /// failure is reported as a failed launch.
+    pub fn pre_launch_shell(mut self, command_lines: Vec<String>) -> Self {
+        self.params.pre_launch_shell = command_lines;
+        self
+    }
+
+    /// Runtime permissions to grant to the client when it is found, see
+    /// `WiredConnection::ensure_permissions`
+    pub fn client_permissions(mut self, permissions: Vec<String>) -> Self {
+        self.params.client_permissions = permissions;
+        self
+    }
+
+    /// See `WiredConnection::set_event_handler`
+    pub fn on_event(mut self, handler: impl Fn(WiredEvent) + Send + 'static) -> Self {
+        self.event_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// See `WiredConnection::set_process_name_resolver`
+    pub fn process_name_resolver(
+        mut self,
+        resolver: impl Fn(&[&str]) -> Option<String> + Send + 'static,
+    ) -> Self {
+        self.process_name_resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// See `WiredConnection::set_cleanup_stray_servers`
+    pub fn cleanup_stray_servers(mut self, enabled: bool) -> Self {
+        self.cleanup_stray_servers = enabled;
+        self
+    }
+
+    /// See `WiredConnection::set_device_lost_grace`
+    pub fn device_lost_grace(mut self, grace: Duration) -> Self {
+        self.device_lost_grace = grace;
+        self
+    }
+
+    /// See `WiredConnection::set_min_tick_interval`
+    pub fn min_tick_interval(mut self, interval: Duration) -> Self {
+        self.min_tick_interval = interval;
+        self
+    }
+
+    /// See `WiredConnection::restore_timing_state`
+    pub fn timing_state(mut self, state: WiredTimingState) -> Self {
+        self.timing_state = Some(state);
+        self
+    }
+
+    /// See `WiredConnection::set_transport`
+    pub fn transport(mut self, transport: Arc<dyn AdbTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Downloads ADB if needed, like `WiredConnection::new`
+    pub fn build(
+        self,
+        layout: &alvr_filesystem::Layout,
+        download_progress_callback: impl Fn(usize, Option<usize>),
+    ) -> AdbResult<WiredConnection> {
+        let connection = WiredConnection::new_cancellable(
+            layout,
+            self.adb_dir.as_deref(),
+            &self.adb_env,
+            self.cancel_handle.clone(),
+            download_progress_callback,
+        )?;
+
+        Ok(self.configure(connection))
+    }
+
+    /// Like `build`, but returns right away and installs ADB on a background thread. Until it is
+    /// installed, the setup returns a `WiredStatusKind::DownloadingAdb` status with the progress.
+    /// A failure is returned by the next setup, and the setup after it tries again.
+    pub fn build_in_background(
+        self,
+        layout: &alvr_filesystem::Layout,
+        download_progress_callback: impl Fn(usize, Option<usize>) + Send + 'static,
+    ) -> WiredConnection {
+        let location = AdbLocation {
+            layout: layout.clone(),
+            adb_dir: self.adb_dir.clone(),
+            adb_env: self.adb_env.clone(),
+        };
+        let mut connection =
+            WiredConnection::with_adb_path(String::new(), AdbSource::Downloaded, None);
+        connection.adb_download = Some(AdbDownload::start(
+            location.clone(),
+            self.cancel_handle.clone(),
+            Box::new(download_progress_callback),
+        ));
+        connection.adb_location = Some(location);
+        connection.cancel_handle = self.cancel_handle.clone();
+        connection.lock_dir = Some(layout.config_dir.clone());
+
+        self.configure(connection)
+    }
+
+    fn configure(self, mut connection: WiredConnection) -> WiredConnection {
+        // Unlike the connections of a fleet, this one still owns the server
+        connection.device_watcher.device_filter = self.device_serial;
+        connection.event_handler = self.event_handler;
+        connection.process_name_resolver = self.process_name_resolver;
+        connection.cleanup_stray_servers = self.cleanup_stray_servers;
+        connection.setup_params = self.params;
+        connection.transport = self.transport;
+        connection.device_lost_grace = self.device_lost_grace;
+        connection.min_tick_interval = self.min_tick_interval;
+        if let Some(state) = self.timing_state {
+            connection.restore_timing_state(state);
+        }
+
+        connection
+    }
+}
+
+impl Default for WiredConnectionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,13 +1,31 @@
 // https://android.googlesource.com/platform/packages/modules/adb/+/refs/heads/main/docs/user/adb.1.md
 
-use crate::parse::{self, Device, ForwardedPorts};
+use crate::parse::{
+    self, ActivityState, BatteryState, ConnectionState, CrashReport, Device, DeviceClock, Display,
+    ForwardedPorts, GraphicsInfo, KeyguardState, LaunchResult, MemoryInfo, ProcessImportance,
+    Transport,
+};
+use crate::{AdbError, AdbResult, ParseContext, WiredCancelHandle, as_adb_error, fail, transport};
+use alvr_common::dbg_connection;
 use alvr_filesystem as afs;
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
 use std::{
-    collections::HashSet,
-    io::{Cursor, Read},
-    process::Command,
+    collections::{HashMap, HashSet},
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Cursor, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
     str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, TrySendError},
+    },
+    thread::{self, JoinHandle},
     time::Duration,
 };
 use zip::ZipArchive;
@@ -18,8 +36,15 @@ use std::os::windows::process::CommandExt;
 // https://developer.android.com/tools/releases/platform-tools#revisions
 // NOTE: At the time of writing this comment, the revisions section above
 // shows the latest version as 35.0.2, but the latest that can be downloaded
-// by specifying a version is 35.0.0
-const PLATFORM_TOOLS_VERSION: &str = "-latest"; // E.g. "_r35.0.0"
+// by specifying a version is 35.0.0. A fixed release is used so that a new one can't break
+// existing installs before it is tested.
+const PLATFORM_TOOLS_VERSION: &str = "_r35.0.0";
+
+// Allow downloading platform-tools from a mirror, for networks where dl.google.com is blocked
+const PLATFORM_TOOLS_URL_ENV_VAR: &str = "ALVR_PLATFORM_TOOLS_URL";
+const PLATFORM_TOOLS_PROXY_ENV_VAR: &str = "ALVR_PLATFORM_TOOLS_PROXY";
+// SHA-1 of the archive served by the mirror, if it is not the pinned or built-in release
+const PLATFORM_TOOLS_SHA1_ENV_VAR: &str = "ALVR_PLATFORM_TOOLS_SHA1";
 
 #[cfg(target_os = "linux")]
 const PLATFORM_TOOLS_OS: &str = "linux";
@@ -28,11 +53,109 @@ const PLATFORM_TOOLS_OS: &str = "darwin";
 #[cfg(windows)]
 const PLATFORM_TOOLS_OS: &str = "windows";
 
+// SHA-1 of the archive of PLATFORM_TOOLS_VERSION for this OS, as published in the Android SDK
+// repository manifest when the version was pinned. Downloads of the built-in version fail while
+// it is None.
+#[cfg(target_os = "linux")]
+const PLATFORM_TOOLS_SHA1: Option<&str> = None;
+#[cfg(target_os = "macos")]
+const PLATFORM_TOOLS_SHA1: Option<&str> = None;
+#[cfg(windows)]
+const PLATFORM_TOOLS_SHA1: Option<&str> = None;
+
+// Port of the ADB server unless ANDROID_ADB_SERVER_PORT is set
+const DEFAULT_SERVER_PORT: u16 = 5037;
+const SERVER_PORT_ENV_VAR: &str = "ANDROID_ADB_SERVER_PORT";
+// Keys adb authenticates with besides ~/.android/adbkey, files or directories separated like PATH
+const VENDOR_KEYS_ENV_VAR: &str = "ADB_VENDOR_KEYS";
+
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const SERVER_CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Followed by "/<abi>/runtimes/active/0", for the runtimes of OpenXR 1.x
+const OPENXR_RUNTIME_BROKER_URI: &str = "content://org.khronos.openxr.runtime_broker/openxr/1/abi";
+
+// Lines received while the consumer has this many lines pending are dropped
+const LOGCAT_BUFFER_SIZE: usize = 1024;
+const LOGCAT_CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Clock ticks per second used by /proc (USER_HZ), which is 100 on all Android architectures
+const USER_HZ: u64 = 100;
+
+#[cfg(feature = "root")]
+const ADBD_RESTART_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(feature = "root")]
+const ADBD_RESTART_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Variables set by `set_adb_env`
+static ADB_ENV: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
+
+// Numbers the scratch directories of `install_package_file`, so that concurrent installs to several
+// devices don't share one
+static SPLITS_DIR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+pub use crate::parse::{LogcatLine, LogcatPriority};
+
+// Failures of adb itself are returned as errors, the exit status of the command is not checked
+fn run_adb(command: &mut Command) -> AdbResult<Output> {
+    let output = transport::run_command(command).map_err(AdbError::SpawnFailed)?;
+    if let Some(e) = parse::parse_adb_error(&String::from_utf8_lossy(&output.stderr)) {
+        return fail(e);
+    }
 
-fn get_command(adb_path: &str, args: &[&str]) -> Command {
+    Ok(output)
+}
+
+// Error with the output of a command that exited with an error
+fn command_failed(output: &Output) -> AdbError {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    AdbError::CommandFailed {
+        stderr: if stderr.trim().is_empty() {
+            stdout.trim().to_owned()
+        } else {
+            stderr.trim().to_owned()
+        },
+        exit_code: output.status.code(),
+    }
+}
+
+/// Sets environment variables of every adb command spawned afterwards, e.g. `ADB_VENDOR_KEYS` for
+/// devices authorized beforehand or `ANDROID_ADB_SERVER_PORT` for a server on another port. Like
+/// the ADB server, it applies to the whole process and replaces the variables set before. Fails if
+/// a file of `ADB_VENDOR_KEYS` doesn't exist, as adb would silently ignore it.
+pub fn set_adb_env(adb_env: &HashMap<String, String>) -> AdbResult<()> {
+    if let Some(keys) = adb_env.get(VENDOR_KEYS_ENV_VAR) {
+        for path in env::split_paths(keys).filter(|path| !path.as_os_str().is_empty()) {
+            if !path.exists() {
+                return fail(AdbError::Other(anyhow!(
+                    "ADB vendor key {} not found",
+                    path.display()
+                )));
+            }
+        }
+    }
+    if let Some(port) = adb_env.get(SERVER_PORT_ENV_VAR)
+        && port.parse::<u16>().is_err()
+    {
+        return fail(AdbError::Other(anyhow!("Invalid ADB server port {port:?}")));
+    }
+
+    let mut vars = adb_env
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<Vec<_>>();
+    vars.sort();
+    *ADB_ENV.lock().unwrap() = vars;
+
+    Ok(())
+}
+
+pub(crate) fn get_command(adb_path: &str, args: &[&str]) -> Command {
     let mut command = Command::new(adb_path);
     command.args(args);
+    command.envs(ADB_ENV.lock().unwrap().iter().map(|(k, v)| (k, v)));
 
     #[cfg(windows)]
     command.creation_flags(0x08000000); // CREATE_NO_WINDOW
@@ -40,310 +163,2388 @@ fn get_command(adb_path: &str, args: &[&str]) -> Command {
     command
 }
 
-pub fn download(url: &str, progress_callback: impl Fn(usize, Option<usize>)) -> Result<Vec<u8>> {
-    let agent: ureq::Agent = ureq::Agent::config_builder()
-        .timeout_global(Some(REQUEST_TIMEOUT))
-        .build()
-        .into();
-    let response = agent.get(url).call()?;
-    let maybe_expected_size = response
-        .headers()
-        .get("Content-Length")
-        .and_then(|v| v.to_str().ok()?.parse::<usize>().ok());
+/// `progress_callback` receives the downloaded size and the total size. It is called with a size of
+/// 0 once the response is received, then after each chunk with an increasing size. The total is
+/// None if the server does not tell the size of the content, e.g. for chunked or compressed
+/// responses, which a progress bar can render as indeterminate.
+pub fn download(url: &str, progress_callback: impl Fn(usize, Option<usize>)) -> AdbResult<Vec<u8>> {
+    download_via_proxy(url, None, &WiredCancelHandle::default(), progress_callback)
+}
+
+pub fn download_via_proxy(
+    url: &str,
+    proxy: Option<&str>,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<Vec<u8>> {
+    let mut config = ureq::Agent::config_builder().timeout_global(Some(REQUEST_TIMEOUT));
+    if let Some(proxy) = proxy {
+        config = config.proxy(Some(
+            ureq::Proxy::new(proxy).context(format!("Invalid proxy {proxy}"))?,
+        ));
+    }
+    let agent: ureq::Agent = config.build().into();
+    let response = agent
+        .get(url)
+        .call()
+        .context(format!("Failed to download {url}"))?;
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let maybe_expected_size =
+        parse_content_length(header("Content-Length"), header("Content-Encoding"));
     let mut result = maybe_expected_size
         .map(Vec::with_capacity)
         .unwrap_or_default();
-    let mut reader = response.into_body().into_reader();
-    let mut buffer = vec![0; 65535];
-    loop {
-        let read_count: usize = reader.read(&mut buffer)?;
-        if read_count == 0 {
-            break;
-        }
-        result.extend_from_slice(&buffer[..read_count]);
-        let current_size = result.len();
-        (progress_callback)(current_size, maybe_expected_size);
-    }
+    (progress_callback)(0, maybe_expected_size);
+    copy_with_progress(
+        &mut response.into_body().into_reader(),
+        &mut result,
+        maybe_expected_size,
+        cancel_handle,
+        progress_callback,
+    )?;
 
     Ok(result)
 }
 
-///////////
-// Activity
+////////
+// Shell
 
-pub fn get_process_id(
-    adb_path: &str,
-    device_serial: &str,
-    process_name: &str,
-) -> Result<Option<usize>> {
-    let output = get_command(
-        adb_path,
-        &["-s", device_serial, "shell", "pidof", process_name],
-    )
-    .output()
-    .context(format!("Failed to get ID of process {process_name}"))?;
-    let text = String::from_utf8_lossy(&output.stdout).trim().to_owned();
-    if text.is_empty() {
-        return Ok(None);
-    }
-    let process_id = text
-        .parse::<usize>()
-        .context("Failed to parse process ID")?;
+/// Runs a command in the device shell and returns its standard output. Each argument is escaped so
+/// that it reaches the device as a single word. The exit status of the command is not checked.
+pub fn shell(adb_path: &str, device_serial: &str, args: &[&str]) -> AdbResult<String> {
+    let output = shell_output(adb_path, device_serial, args)?;
 
-    Ok(Some(process_id))
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn shell_output(adb_path: &str, device_serial: &str, args: &[&str]) -> AdbResult<Output> {
+    run_adb(
+        get_command(adb_path, &["-s", device_serial, "shell"])
+            .args(args.iter().map(|arg| escape_shell_arg(arg))),
+    )
 }
 
-pub fn is_activity_resumed(
+/// Runs a command line with `sh -c` on the device, so that it can use pipes or several commands.
+/// Fails if it exits with an error.
+pub fn shell_command_line(
     adb_path: &str,
     device_serial: &str,
-    activity_name: &str,
-) -> Result<bool> {
-    let output = get_command(
-        adb_path,
-        &[
-            "-s",
-            device_serial,
-            "shell",
-            "dumpsys",
-            "activity",
-            activity_name,
-        ],
-    )
-    .output()
-    .context(format!("Failed to get state of activity {activity_name}"))?;
-    let text = String::from_utf8_lossy(&output.stdout);
-    if let Some(line) = text
-        .lines()
-        .map(|l| l.trim())
-        .find(|l| l.contains("mResumed"))
-    {
-        let (entry, _) = line
-            .split_once(' ')
-            .ok_or(anyhow!("Failed to split resumed state line"))?;
-        let (_, value) = entry
-            .split_once('=')
-            .ok_or(anyhow!("Failed to split resumed state entry"))?;
-        match value {
-            "true" => Ok(true),
-            "false" => Ok(false),
-            _ => Err(anyhow!("Failed to parse resumed state value"))?,
-        }
-    } else {
-        Err(anyhow!("Failed to find resumed state line"))
+    command_line: &str,
+) -> AdbResult<String> {
+    checked_shell(adb_path, device_serial, &["sh", "-c", command_line])
+}
+
+// Like `shell`, but fails if the command exits with an error
+fn checked_shell(adb_path: &str, device_serial: &str, args: &[&str]) -> AdbResult<String> {
+    let output = shell_output(adb_path, device_serial, args)?;
+    if !output.status.success() {
+        return fail(command_failed(&output));
     }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
-///////////////////
-// ADB Installation
+// Fails with `AdbError::DumpsysRestricted` if the device doesn't let the shell user dump the service
+fn dumpsys(adb_path: &str, device_serial: &str, args: &[&str]) -> AdbResult<String> {
+    let text = shell(adb_path, device_serial, &[&["dumpsys"], args].concat())?;
+    if parse::is_dumpsys_restricted(&text) {
+        return fail(AdbError::DumpsysRestricted {
+            service: args.first().copied().unwrap_or_default().to_owned(),
+        });
+    }
 
-pub fn require_adb(
-    layout: &afs::Layout,
-    progress_callback: impl Fn(usize, Option<usize>),
-) -> Result<String> {
-    if let Some(path) = get_adb_path(layout) {
-        Ok(path)
+    Ok(text)
+}
+
+fn escape_shell_arg(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c))
+    {
+        arg.to_owned()
     } else {
-        install_adb(layout, progress_callback).context("Failed to install ADB")?;
-        Ok(get_adb_path(layout).context("Failed to get ADB path after installation")?)
+        format!("'{}'", arg.replace('\'', r"'\''"))
     }
 }
 
-fn install_adb(
-    layout: &afs::Layout,
-    progress_callback: impl Fn(usize, Option<usize>),
-) -> Result<()> {
-    let mut reader = Cursor::new(download_adb(progress_callback)?);
-    ZipArchive::new(&mut reader)?.extract(layout.executables_dir.clone())?;
+/////////
+// Logcat
 
-    Ok(())
+pub struct LogcatFilter {
+    // Minimum priority of each listed tag
+    pub tags: Vec<(String, LogcatPriority)>,
+    // Minimum priority of the tags not listed. Use `LogcatPriority::Silent` to show only the
+    // listed tags
+    pub default_priority: LogcatPriority,
 }
 
-fn download_adb(progress_callback: impl Fn(usize, Option<usize>)) -> Result<Vec<u8>> {
-    let url = get_platform_tools_url();
-
-    download(&url, progress_callback).context(format!("Failed to download ADB from {url}"))
+impl Default for LogcatFilter {
+    fn default() -> Self {
+        Self {
+            tags: vec![],
+            default_priority: LogcatPriority::Verbose,
+        }
+    }
 }
 
-fn get_platform_tools_url() -> String {
-    format!(
-        "https://dl.google.com/android/repository/platform-tools{PLATFORM_TOOLS_VERSION}-{PLATFORM_TOOLS_OS}.zip"
-    )
+/// Live tail of the device log. Iterating blocks until a new line is available and ends when
+/// logcat exits. Dropping the stream kills the underlying logcat process.
+pub struct LogcatStream {
+    child: Child,
+    receiver: Receiver<LogcatLine>,
+    dropped_lines: Arc<AtomicUsize>,
+    reader_thread: Option<JoinHandle<()>>,
+    cancel_handle: WiredCancelHandle,
 }
 
-///////////////
-// Applications
+impl LogcatStream {
+    pub fn try_next(&self) -> Option<LogcatLine> {
+        if self.cancel_handle.is_cancelled() {
+            return None;
+        }
 
-pub fn start_application(adb_path: &str, device_serial: &str, application_id: &str) -> Result<()> {
-    get_command(
-        adb_path,
-        &[
-            "-s",
-            device_serial,
-            "shell",
-            "monkey",
-            "-p",
-            application_id,
-            "1",
-        ],
-    )
-    .output()
-    .context(format!("Failed to start {application_id}"))?;
+        self.receiver.try_recv().ok()
+    }
 
-    Ok(())
+    // Number of lines discarded so far because the consumer was not keeping up
+    pub fn dropped_lines(&self) -> usize {
+        self.dropped_lines.load(Ordering::Relaxed)
+    }
 }
 
-//////////
-// Devices
+impl Iterator for LogcatStream {
+    type Item = LogcatLine;
 
-pub fn list_devices(adb_path: &str) -> Result<Vec<Device>> {
-    let output = get_command(adb_path, &["devices", "-l"])
-        .output()
-        .context("Failed to list ADB devices")?;
-    let text = String::from_utf8_lossy(&output.stdout);
-    let devices = text
-        .lines()
-        .skip(1)
-        .filter_map(parse::parse_device)
-        .collect();
+    fn next(&mut self) -> Option<LogcatLine> {
+        loop {
+            if self.cancel_handle.is_cancelled() {
+                self.child.kill().ok();
+
+                return None;
+            }
 
-    Ok(devices)
+            match self.receiver.recv_timeout(LOGCAT_CANCEL_POLL_INTERVAL) {
+                Ok(line) => return Some(line),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
 }
 
-///////////
-// Packages
+impl Drop for LogcatStream {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+        if let Some(thread) = self.reader_thread.take() {
+            thread.join().ok();
+        }
+    }
+}
 
-pub fn install_package(adb_path: &str, device_serial: &str, apk_path: &str) -> Result<()> {
-    get_command(adb_path, &["-s", device_serial, "install", "-r", apk_path])
-        .output()
-        .context(format!("Failed to install {apk_path}"))?;
+/// The stream ends when `cancel_handle` is cancelled.
+pub fn logcat_stream(
+    adb_path: &str,
+    device_serial: &str,
+    filter: &LogcatFilter,
+    cancel_handle: &WiredCancelHandle,
+) -> AdbResult<LogcatStream> {
+    let filter_specs = filter
+        .tags
+        .iter()
+        .map(|(tag, priority)| format!("{tag}:{}", priority.as_char()))
+        .chain([format!("*:{}", filter.default_priority.as_char())]);
+    let mut child = get_command(
+        adb_path,
+        &["-s", device_serial, "logcat", "-v", "threadtime"],
+    )
+    .args(filter_specs)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .context(format!(
+        "Failed to start logcat on device {device_serial:?}"
+    ))?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to capture logcat output")?;
 
-    Ok(())
+    let (sender, receiver) = mpsc::sync_channel(LOGCAT_BUFFER_SIZE);
+    let dropped_lines = Arc::new(AtomicUsize::new(0));
+    let reader_thread = thread::spawn({
+        let dropped_lines = Arc::clone(&dropped_lines);
+        move || {
+            let lines = BufReader::new(stdout)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|l| parse::parse_logcat_line(&l));
+            for line in lines {
+                match sender.try_send(line) {
+                    Ok(()) => (),
+                    Err(TrySendError::Full(_)) => {
+                        dropped_lines.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(TrySendError::Disconnected(_)) => break,
+                }
+            }
+        }
+    });
+
+    Ok(LogcatStream {
+        child,
+        receiver,
+        dropped_lines,
+        reader_thread: Some(reader_thread),
+        cancel_handle: cancel_handle.clone(),
+    })
 }
 
-pub fn is_package_installed(
+///////////
+// Activity
+
+pub fn get_process_id(
     adb_path: &str,
     device_serial: &str,
-    application_id: &str,
-) -> Result<bool> {
-    let found = list_installed_packages(adb_path, device_serial)
-        .context(format!(
-            "Failed to check if package {application_id} is installed"
-        ))?
-        .contains(application_id);
+    process_name: &str,
+) -> AdbResult<Option<usize>> {
+    let output = shell(adb_path, device_serial, &["pidof", process_name])
+        .context(format!("Failed to get ID of process {process_name}"))?;
+    let text = output.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let process_id = text
+        .parse::<usize>()
+        .parse_context("Failed to parse process ID")?;
 
-    Ok(found)
+    Ok(Some(process_id))
 }
 
-pub fn uninstall_package(adb_path: &str, device_serial: &str, application_id: &str) -> Result<()> {
-    get_command(
+// Returns None if the process is not running
+pub fn get_process_importance(
+    adb_path: &str,
+    device_serial: &str,
+    process_name: &str,
+) -> AdbResult<Option<ProcessImportance>> {
+    let text = dumpsys(
         adb_path,
-        &["-s", device_serial, "uninstall", application_id],
+        device_serial,
+        &["activity", "processes", process_name],
     )
-    .output()
-    .context(format!("Failed to uninstall {application_id}"))?;
+    .context(format!(
+        "Failed to get importance of process {process_name}"
+    ))?;
 
-    Ok(())
+    Ok(parse::parse_process_importance(&text, process_name))
 }
 
-pub fn list_installed_packages(adb_path: &str, device_serial: &str) -> Result<HashSet<String>> {
-    let output = get_command(
-        adb_path,
-        &["-s", device_serial, "shell", "pm", "list", "package"],
-    )
-    .output()
-    .context("Failed to list installed packages")?;
-    let text = String::from_utf8_lossy(&output.stdout);
-    let packages = text.lines().map(|l| l.replace("package:", "")).collect();
+pub fn get_memory_info(
+    adb_path: &str,
+    device_serial: &str,
+    package: &str,
+) -> AdbResult<Option<MemoryInfo>> {
+    let text = dumpsys(adb_path, device_serial, &["meminfo", package])
+        .context(format!("Failed to get memory usage of {package}"))?;
 
-    Ok(packages)
+    Ok(parse::parse_memory_info(&text))
 }
 
-////////
-// Paths
+pub fn get_graphics_info(
+    adb_path: &str,
+    device_serial: &str,
+    package: &str,
+) -> AdbResult<Option<GraphicsInfo>> {
+    let text = dumpsys(adb_path, device_serial, &["gfxinfo", package])
+        .context(format!("Failed to get frame stats of {package}"))?;
 
-/// Returns the path of a local (i.e. installed by ALVR) or OS version of `adb` if found, `None` otherwise.
-pub fn get_adb_path(layout: &afs::Layout) -> Option<String> {
-    let exe_name = afs::exec_fname("adb").to_owned();
-    let adb_path = get_command(&exe_name, &[])
-        .output()
-        .is_ok()
-        .then_some(exe_name);
+    Ok(parse::parse_graphics_info(&text))
+}
 
-    adb_path.or_else(|| {
-        let path = layout.local_adb_exe();
+// Returns the most recent crash, native crash or ANR of the application recorded by the dropbox
+pub fn last_crash(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<Option<CrashReport>> {
+    let mut last_crash = None::<CrashReport>;
+    // Entries only match if they match every search argument, so query one tag at a time
+    for tag in ["data_app_crash", "data_app_native_crash", "data_app_anr"] {
+        let text = dumpsys(adb_path, device_serial, &["dropbox", "--print", tag])
+            .context(format!("Failed to get crashes of {application_id}"))?;
 
-        path.try_exists()
-            .unwrap_or(false)
-            .then(|| path.to_string_lossy().to_string())
-    })
+        if let Some(crash) = parse::parse_last_crash(&text, application_id)
+            && last_crash
+                .as_ref()
+                .is_none_or(|last| crash.time > last.time)
+        {
+            last_crash = Some(crash);
+        }
+    }
+
+    Ok(last_crash)
 }
 
-////////
-// Utility
-pub fn get_uptime(adb_path: &str, device_serial: &str) -> Result<Duration> {
-    let output = get_command(
+// Returns the time elapsed between the device boot and the start of the process
+pub fn get_process_start_time(
+    adb_path: &str,
+    device_serial: &str,
+    process_id: usize,
+) -> AdbResult<Duration> {
+    let stat = shell(
         adb_path,
-        &["-s", device_serial, "shell", "cat", "/proc/uptime"],
+        device_serial,
+        &["cat", &format!("/proc/{process_id}/stat")],
     )
-    .output()
-    .context("Failed to get system uptime")?;
+    .context(format!("Failed to get start time of process {process_id}"))?;
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
+    let ticks = parse::parse_process_start_ticks(&stat).parse_context(format!(
+        "Failed to parse start time of process {process_id}"
+    ))?;
 
-    let uptime_string = output_str
-        .split_ascii_whitespace()
-        .next()
-        .context("Empty result from /proc/uptime")?;
+    Ok(Duration::from_millis(ticks * 1000 / USER_HZ))
+}
 
-    let uptime = f64::from_str(uptime_string).context("Cannot parse uptime into an f64")?;
+pub fn get_activity_state(
+    adb_path: &str,
+    device_serial: &str,
+    package: &str,
+) -> AdbResult<ActivityState> {
+    let text = dumpsys(adb_path, device_serial, &["activity", "activities"])
+        .context(format!("Failed to get state of activity {package}"))?;
+    let state = parse::parse_activity_state(&text, package);
+    if state != ActivityState::Unknown {
+        return Ok(state);
+    }
+
+    // Fall back to the state printed by the activity itself
+    let text = dumpsys(adb_path, device_serial, &["activity", package])
+        .context(format!("Failed to get state of activity {package}"))?;
+    let state = match parse::parse_activity_resumed(&text) {
+        Some(true) => ActivityState::Resumed,
+        Some(false) => ActivityState::Paused,
+        None => ActivityState::Unknown,
+    };
 
-    Duration::try_from_secs_f64(uptime).context("Invalid f64 value for a duration ")
+    Ok(state)
 }
 
-//////////////////
-// Port forwarding
+pub fn is_process_not_responding(
+    adb_path: &str,
+    device_serial: &str,
+    process_name: &str,
+) -> AdbResult<bool> {
+    let text = dumpsys(
+        adb_path,
+        device_serial,
+        &["activity", "processes", process_name],
+    )
+    .context(format!(
+        "Failed to get responding state of process {process_name}"
+    ))?;
 
-pub fn list_forwarded_ports(adb_path: &str, device_serial: &str) -> Result<Vec<ForwardedPorts>> {
-    let output = get_command(adb_path, &["-s", device_serial, "forward", "--list"])
-        .output()
-        .context(format!(
-            "Failed to list forwarded ports of device {device_serial:?}"
-        ))?;
-    let text = String::from_utf8_lossy(&output.stdout);
-    let forwarded_ports = text
-        .lines()
-        .filter_map(parse::parse_forwarded_ports)
-        .collect();
+    Ok(parse::parse_process_not_responding(&text, process_name))
+}
 
-    Ok(forwarded_ports)
+////////
+// Files
+
+/// Copies a local file to the device. The data is streamed through the device shell instead of
+/// using `adb push`, which prints its progress only when attached to a terminal.
+pub fn push_file(
+    adb_path: &str,
+    device_serial: &str,
+    local_path: &str,
+    remote_path: &str,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<()> {
+    let mut file = File::open(local_path).context(format!("Failed to open {local_path}"))?;
+    let maybe_total_size = file
+        .metadata()
+        .ok()
+        .and_then(|m| usize::try_from(m.len()).ok());
+
+    let output = exec_in(
+        adb_path,
+        device_serial,
+        &format!("cat > {}", escape_shell_arg(remote_path)),
+        &mut file,
+        maybe_total_size,
+        cancel_handle,
+        progress_callback,
+    )
+    .context(format!("Failed to push {local_path} to {remote_path}"))?;
+    if !output.status.success() {
+        return fail(command_failed(&output));
+    }
+
+    Ok(())
 }
 
-pub fn forward_port(adb_path: &str, device_serial: &str, port: u16) -> Result<()> {
-    get_command(
+pub fn pull_file(adb_path: &str, device_serial: &str, remote_path: &str) -> AdbResult<Vec<u8>> {
+    let output = run_adb(&mut get_command(
         adb_path,
         &[
             "-s",
             device_serial,
-            "forward",
-            &format!("tcp:{port}"),
-            &format!("tcp:{port}"),
+            "exec-out",
+            &format!("cat {}", escape_shell_arg(remote_path)),
         ],
-    )
-    .output()
-    .context(format!(
-        "Failed to forward port {port:?} of device {device_serial:?}"
-    ))?;
+    ))
+    .context(format!("Failed to pull {remote_path}"))?;
+    if !output.status.success() {
+        return fail(command_failed(&output));
+    }
 
-    Ok(())
+    Ok(output.stdout)
 }
 
-/////////
-// Server
-
-pub fn kill_server(adb_path: &str) -> Result<()> {
-    get_command(adb_path, &["kill-server"])
-        .output()
-        .context("Failed to kill ADB server")?;
+pub fn remove_file(adb_path: &str, device_serial: &str, remote_path: &str) -> AdbResult<()> {
+    shell(adb_path, device_serial, &["rm", "-f", remote_path])
+        .context(format!("Failed to remove {remote_path}"))?;
 
     Ok(())
 }
+
+// Runs a command on the device with the data of `reader` as its stdin. adb is killed if the copy is
+// cancelled.
+fn exec_in(
+    adb_path: &str,
+    device_serial: &str,
+    command: &str,
+    reader: &mut impl Read,
+    maybe_total_size: Option<usize>,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<Output> {
+    let mut child = get_command(adb_path, &["-s", device_serial, "exec-in", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(AdbError::SpawnFailed)?;
+
+    // stdin is dropped once copied, which lets the device side finish reading
+    let copy_result = match child.stdin.take() {
+        Some(stdin) => copy_with_progress(
+            reader,
+            stdin,
+            maybe_total_size,
+            cancel_handle,
+            progress_callback,
+        ),
+        None => fail(AdbError::Other(anyhow!("Failed to open stream"))),
+    };
+    if copy_result.is_err() {
+        // Otherwise adb waits for the rest of the data
+        child.kill().ok();
+    }
+
+    let output = child.wait_with_output()?;
+    cancel_handle.check()?;
+    if let Some(e) = parse::parse_adb_error(&String::from_utf8_lossy(&output.stderr)) {
+        return fail(e);
+    }
+    copy_result?;
+
+    Ok(output)
+}
+
+// The length of a compressed response is the one before decompression
+fn parse_content_length(
+    content_length: Option<&str>,
+    content_encoding: Option<&str>,
+) -> Option<usize> {
+    if content_encoding.is_some_and(|encoding| !encoding.trim().eq_ignore_ascii_case("identity")) {
+        return None;
+    }
+
+    content_length?.trim().parse().ok()
+}
+
+// A total size smaller than the copied size was wrong, the progress is then reported without it
+fn copy_with_progress(
+    reader: &mut impl Read,
+    mut writer: impl Write,
+    maybe_total_size: Option<usize>,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<()> {
+    let mut buffer = vec![0; 65535];
+    let mut copied_size = 0;
+    loop {
+        cancel_handle.check()?;
+        let read_count = reader.read(&mut buffer)?;
+        if read_count == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read_count])?;
+        copied_size += read_count;
+        (progress_callback)(
+            copied_size,
+            maybe_total_size.filter(|total_size| copied_size <= *total_size),
+        );
+    }
+
+    Ok(())
+}
+
+///////////////////
+// ADB Installation
+
+/// A platform-tools release to install instead of the default one. `sha1` is the checksum of the
+/// archive for this OS published in the Android SDK repository manifest. It is required so that a
+/// pinned archive is always verified.
+#[derive(Clone, Debug)]
+pub struct PlatformToolsPin {
+    pub version: String, // E.g. "35.0.0"
+    pub sha1: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdbSource {
+    // The copy downloaded by a previous run
+    Bundled,
+    Downloaded,
+    // Extracted from the archive shipped with the installer, see `get_offline_bundle_path`
+    OfflineBundle,
+    // Found in PATH
+    UserProvided,
+}
+
+/// Downloads the platform-tools release in `PLATFORM_TOOLS_VERSION` if no adb is found. See
+/// `download` for the arguments of `progress_callback`, which is not called if ADB is already
+/// installed
+pub fn require_adb(
+    layout: &afs::Layout,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<(String, AdbSource)> {
+    require_pinned_adb(
+        layout,
+        None,
+        &WiredCancelHandle::default(),
+        progress_callback,
+    )
+}
+
+/// Like `require_adb`, but a local adb of another version than `pin` is replaced. The adb of the
+/// system is used as is.
+pub fn require_pinned_adb(
+    layout: &afs::Layout,
+    pin: Option<&PlatformToolsPin>,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<(String, AdbSource)> {
+    if let Some(path) = get_adb_path(layout) {
+        if Path::new(&path) != layout.local_adb_exe() {
+            return Ok((path, AdbSource::UserProvided));
+        }
+
+        ensure_executable(Path::new(&path))?;
+
+        let version = get_adb_version(&path);
+        match pin {
+            Some(pin) if version.as_ref().ok() != Some(&pin.version) => {
+                dbg_connection!(
+                    "require_adb: Replacing ADB {version:?} with version {}",
+                    pin.version
+                );
+                kill_server(&path).ok();
+            }
+            _ => return Ok((path, AdbSource::Bundled)),
+        }
+    }
+
+    let bundle_path = get_offline_bundle_path(layout);
+    let (data, source) = match read_offline_bundle(&bundle_path, pin)? {
+        Some(data) => (data, AdbSource::OfflineBundle),
+        None => match download_adb(pin, cancel_handle, progress_callback) {
+            Ok(data) => (data, AdbSource::Downloaded),
+            Err(e) if matches!(as_adb_error(&e), Some(AdbError::Cancelled)) => return Err(e),
+            Err(e) => {
+                return fail(AdbError::Other(anyhow!(
+                    "{e:#}. Without internet access, place the platform tools archive and its \
+                    SHA-1 checksum at {} and {}.sha1",
+                    bundle_path.display(),
+                    bundle_path.display()
+                )));
+            }
+        },
+    };
+    install_adb(layout, data).context("Failed to install ADB")?;
+    let path = get_adb_path(layout).context("Failed to get ADB path after installation")?;
+
+    Ok((path, source))
+}
+
+// `adb version` prints "Version 35.0.0-11411520" on the second line
+pub fn get_adb_version(adb_path: &str) -> AdbResult<String> {
+    let output =
+        run_adb(&mut get_command(adb_path, &["version"])).context("Failed to get ADB version")?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    parse::parse_adb_version(&text).parse_context("Failed to parse ADB version")
+}
+
+// Creates `dir` if needed and checks that ADB can be extracted into it, so that a read-only
+// location is reported before downloading
+pub fn ensure_writable_dir(dir: &Path) -> AdbResult<()> {
+    let context = || format!("ADB directory {} is not writable", dir.display());
+    fs::create_dir_all(dir).with_context(context)?;
+
+    let probe_path = dir.join(".alvr_write_test");
+    fs::write(&probe_path, []).with_context(context)?;
+    fs::remove_file(&probe_path).ok();
+
+    Ok(())
+}
+
+fn install_adb(layout: &afs::Layout, archive: Vec<u8>) -> AdbResult<()> {
+    let mut reader = Cursor::new(archive);
+    ZipArchive::new(&mut reader)
+        .context("Failed to open the platform tools archive")?
+        .extract(layout.executables_dir.clone())
+        .context("Failed to extract the platform tools")?;
+
+    // Archives from mirrors can lack the unix permissions
+    ensure_executable(&layout.local_adb_exe())
+}
+
+// Otherwise adb would only fail with "permission denied" once it is first run
+#[cfg(unix)]
+fn ensure_executable(path: &Path) -> AdbResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)
+        .context(format!("Failed to read permissions of {}", path.display()))?
+        .permissions();
+    if permissions.mode() & 0o111 == 0 {
+        permissions.set_mode(permissions.mode() | 0o755);
+        fs::set_permissions(path, permissions).context(format!(
+            "ADB at {} is not executable, run `chmod +x` on it",
+            path.display()
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn ensure_executable(_: &Path) -> AdbResult<()> {
+    Ok(())
+}
+
+fn download_adb(
+    pin: Option<&PlatformToolsPin>,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<Vec<u8>> {
+    let url = get_platform_tools_url(pin);
+    let sha1 = get_platform_tools_sha1(
+        pin,
+        env::var(PLATFORM_TOOLS_SHA1_ENV_VAR).ok(),
+        env::var(PLATFORM_TOOLS_URL_ENV_VAR).is_ok(),
+    )?;
+    let proxy = env::var(PLATFORM_TOOLS_PROXY_ENV_VAR).ok();
+
+    let data = download_via_proxy(&url, proxy.as_deref(), cancel_handle, progress_callback)
+        .context(format!("Failed to download ADB from {url}"))?;
+
+    verify_sha1(&data, &sha1).context(format!("Invalid ADB archive downloaded from {url}"))?;
+
+    Ok(data)
+}
+
+/// Archive of the platform tools shipped with the installer, for machines without internet access.
+/// It is used instead of downloading ADB if present. Its SHA-1 checksum is read from the file of
+/// the same name with a ".sha1" extension, e.g. the output of `sha1sum`.
+pub fn get_offline_bundle_path(layout: &afs::Layout) -> PathBuf {
+    layout
+        .static_resources_dir
+        .join(format!("platform-tools-{PLATFORM_TOOLS_OS}.zip"))
+}
+
+// Returns None if there is no bundle, or if a release is pinned that the bundle is not known to be
+fn read_offline_bundle(
+    bundle_path: &Path,
+    pin: Option<&PlatformToolsPin>,
+) -> AdbResult<Option<Vec<u8>>> {
+    if !bundle_path.exists() {
+        return Ok(None);
+    }
+
+    let checksum_path = PathBuf::from(format!("{}.sha1", bundle_path.display()));
+    let checksum = fs::read_to_string(&checksum_path).context(format!(
+        "Failed to read the checksum of the offline ADB bundle at {}",
+        checksum_path.display()
+    ))?;
+    // `sha1sum` prints the file name after the checksum
+    let sha1 = checksum.split_whitespace().next().unwrap_or_default();
+
+    if let Some(pin) = pin
+        && !pin.sha1.trim().eq_ignore_ascii_case(sha1)
+    {
+        dbg_connection!(
+            "require_adb: Offline ADB bundle is not version {}, downloading it",
+            pin.version
+        );
+
+        return Ok(None);
+    }
+
+    let data = fs::read(bundle_path)?;
+    if let Err(e) = verify_sha1(&data, sha1) {
+        return fail(AdbError::Other(anyhow!(
+            "Invalid offline ADB bundle at {}: {e:#}",
+            bundle_path.display()
+        )));
+    }
+
+    Ok(Some(data))
+}
+
+fn get_platform_tools_url(pin: Option<&PlatformToolsPin>) -> String {
+    env::var(PLATFORM_TOOLS_URL_ENV_VAR).unwrap_or_else(|_| {
+        let version = pin.map_or_else(
+            || PLATFORM_TOOLS_VERSION.to_owned(),
+            |pin| format!("_r{}", pin.version),
+        );
+
+        format!(
+            "https://dl.google.com/android/repository/platform-tools{version}-{PLATFORM_TOOLS_OS}.zip"
+        )
+    })
+}
+
+// Every downloaded archive is verified, also the ones of a mirror. The checksum set along with the
+// mirror takes precedence, as it can serve another release than the pinned or built-in one.
+fn get_platform_tools_sha1(
+    pin: Option<&PlatformToolsPin>,
+    sha1_override: Option<String>,
+    url_override: bool,
+) -> AdbResult<String> {
+    if let Some(sha1) = sha1_override {
+        return Ok(sha1);
+    }
+    if let Some(pin) = pin {
+        return Ok(pin.sha1.clone());
+    }
+
+    match PLATFORM_TOOLS_SHA1 {
+        Some(sha1) => Ok(sha1.to_owned()),
+        None if url_override => fail(AdbError::Other(anyhow!(
+            "The checksum of the archive at {PLATFORM_TOOLS_URL_ENV_VAR} is unknown, set it in \
+            {PLATFORM_TOOLS_SHA1_ENV_VAR}"
+        ))),
+        None => fail(AdbError::Other(anyhow!(
+            "No known SHA-1 checksum of platform-tools{PLATFORM_TOOLS_VERSION} for \
+            {PLATFORM_TOOLS_OS}, set it in {PLATFORM_TOOLS_SHA1_ENV_VAR}"
+        ))),
+    }
+}
+
+fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn verify_sha1(data: &[u8], expected: &str) -> AdbResult<()> {
+    let sha1 = sha1_hex(data);
+    if !sha1.eq_ignore_ascii_case(expected.trim()) {
+        return fail(AdbError::Other(anyhow!(
+            "SHA-1 checksum mismatch: expected {expected}, got {sha1}"
+        )));
+    }
+
+    Ok(())
+}
+
+///////////////
+// Applications
+
+/// SHA-1 of a local APK in lowercase hex, e.g. to show the hash of the client that would be
+/// installed. It doesn't run adb.
+pub fn hash_local_apk(apk_path: &Path) -> AdbResult<String> {
+    let data = fs::read(apk_path).context(format!("Failed to read {}", apk_path.display()))?;
+
+    Ok(sha1_hex(&data))
+}
+
+// Starts the launcher activity of the application without waiting for it
+pub fn start_application(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<LaunchResult> {
+    let output = shell_output(
+        adb_path,
+        device_serial,
+        &["monkey", "-p", application_id, "1"],
+    )
+    .context(format!("Failed to start {application_id}"))?;
+
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    match parse::parse_launch_error(&text) {
+        Some(error) => Ok(LaunchResult::Failed(error)),
+        None => Ok(LaunchResult::Started {
+            total_time_ms: None,
+        }),
+    }
+}
+
+pub enum BroadcastExtra {
+    String(String),
+    Int(i32),
+    Bool(bool),
+}
+
+// The broadcast is only delivered to receivers of the application
+pub fn send_broadcast(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+    action: &str,
+    extras: &[(&str, BroadcastExtra)],
+) -> AdbResult<()> {
+    let mut args = vec![
+        "am".to_owned(),
+        "broadcast".to_owned(),
+        "-p".to_owned(),
+        application_id.to_owned(),
+        "-a".to_owned(),
+        action.to_owned(),
+    ];
+    for (key, extra) in extras {
+        let (flag, value) = match extra {
+            BroadcastExtra::String(value) => ("--es", value.clone()),
+            BroadcastExtra::Int(value) => ("--ei", value.to_string()),
+            BroadcastExtra::Bool(value) => ("--ez", value.to_string()),
+        };
+        args.extend([flag.to_owned(), (*key).to_owned(), value]);
+    }
+
+    let text = shell(
+        adb_path,
+        device_serial,
+        &args.iter().map(String::as_str).collect::<Vec<_>>(),
+    )
+    .context(format!("Failed to send {action} to {application_id}"))?;
+    if !text.contains("Broadcast completed") {
+        return fail(AdbError::Other(anyhow!(
+            "Failed to send {action} to {application_id}: {}",
+            text.trim()
+        )));
+    }
+
+    Ok(())
+}
+
+// Starts an explicit component and waits until its first frame is drawn or `am` gives up
+// Launches on the default display if `display_id` is None. `--display` is available since Android 8.
+pub fn start_activity(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+    activity_name: &str,
+    display_id: Option<u32>,
+) -> AdbResult<LaunchResult> {
+    let component = format!("{application_id}/{activity_name}");
+    let display_id = display_id.map(|id| id.to_string());
+    let mut args = vec!["am", "start", "-W"];
+    if let Some(display_id) = &display_id {
+        args.extend(["--display", display_id]);
+    }
+    args.extend(["-n", &component]);
+    let output = shell_output(adb_path, device_serial, &args)
+        .context(format!("Failed to start {component}"))?;
+
+    // Errors are printed on stdout or stderr depending on the Android version
+    Ok(parse::parse_launch_result(&format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )))
+}
+
+pub fn force_stop_application(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<()> {
+    shell(
+        adb_path,
+        device_serial,
+        &["am", "force-stop", application_id],
+    )
+    .context(format!("Failed to stop {application_id}"))?;
+
+    Ok(())
+}
+
+//////////
+// Devices
+
+pub fn list_devices(adb_path: &str) -> AdbResult<Vec<Device>> {
+    let output = run_adb(&mut get_command(adb_path, &["devices", "-l"]))
+        .context("Failed to list ADB devices")?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    Ok(parse::parse_devices(&text))
+}
+
+// Lists every transport known to the server, including emulators and devices in any state
+pub fn list_all_transports(adb_path: &str) -> AdbResult<Vec<Transport>> {
+    Ok(list_devices(adb_path)?
+        .into_iter()
+        .map(|device| Transport {
+            transport_type: parse::parse_transport_type(&device),
+            device,
+        })
+        .collect())
+}
+
+pub fn connect(adb_path: &str, address: &str) -> AdbResult<()> {
+    let output = run_adb(&mut get_command(adb_path, &["connect", address]))
+        .context(format!("Failed to connect to {address}"))?;
+    // adb exits successfully even when the connection fails
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.contains("connected to") {
+        Ok(())
+    } else {
+        fail(AdbError::Other(anyhow!(
+            "Failed to connect to {address}: {}",
+            text.trim()
+        )))
+    }
+}
+
+pub fn disconnect(adb_path: &str, address: &str) -> AdbResult<()> {
+    run_adb(&mut get_command(adb_path, &["disconnect", address]))
+        .context(format!("Failed to disconnect from {address}"))?;
+
+    Ok(())
+}
+
+///////////
+// Packages
+
+pub fn install_package(adb_path: &str, device_serial: &str, apk_path: &str) -> AdbResult<()> {
+    install_package_with_progress(adb_path, device_serial, apk_path, |_, _| ())
+}
+
+/// Like `install_package`, `progress_callback` receives the installed size and the size of the APK,
+/// estimated from the progress printed by `adb install`. adb versions which don't print it only
+/// report the start and the end of the install.
+pub fn install_package_with_progress(
+    adb_path: &str,
+    device_serial: &str,
+    apk_path: &str,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<()> {
+    let size = fs::metadata(apk_path)
+        .context(format!("Failed to open {apk_path}"))?
+        .len();
+    let size = usize::try_from(size).context(format!("{apk_path} is too large"))?;
+
+    let mut child = get_command(adb_path, &["-s", device_serial, "install", "-r", apk_path])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(AdbError::SpawnFailed)?;
+
+    // Both pipes are read at the same time, otherwise adb blocks once the other one is full
+    let stderr = child.stderr.take();
+    let stderr_thread = thread::spawn(move || {
+        let mut data = vec![];
+        if let Some(mut stderr) = stderr {
+            stderr.read_to_end(&mut data).ok();
+        }
+
+        data
+    });
+
+    progress_callback(0, Some(size));
+    let mut stdout_data = vec![];
+    let mut line = vec![];
+    let mut report_line = |line: &mut Vec<u8>| {
+        if let Some(percent) = parse::parse_install_progress(&String::from_utf8_lossy(line)) {
+            progress_callback(size * percent / 100, Some(size));
+        }
+        stdout_data.append(line);
+        stdout_data.push(b'\n');
+    };
+    if let Some(stdout) = child.stdout.take() {
+        for byte in BufReader::new(stdout).bytes() {
+            match byte? {
+                b'\r' | b'\n' => report_line(&mut line),
+                byte => line.push(byte),
+            }
+        }
+    }
+    report_line(&mut line);
+
+    let output = Output {
+        status: child.wait()?,
+        stdout: stdout_data,
+        stderr: stderr_thread.join().unwrap_or_default(),
+    };
+    check_install_output(&output).context(format!("Failed to install {apk_path}"))?;
+    progress_callback(size, Some(size));
+
+    Ok(())
+}
+
+// `adb install` reports some failures like "Failure [INSTALL_FAILED_UPDATE_INCOMPATIBLE]" with a
+// successful exit status, only "Success" means that the APK was installed
+fn check_install_output(output: &Output) -> AdbResult<()> {
+    if let Some(e) = parse::parse_adb_error(&String::from_utf8_lossy(&output.stderr)) {
+        return fail(e);
+    }
+    if !output.status.success() || !String::from_utf8_lossy(&output.stdout).contains("Success") {
+        return fail(command_failed(output));
+    }
+
+    Ok(())
+}
+
+/// Installs an APK, or the base and split APKs of one application, through a package installer
+/// session. The APKs are streamed with progress, which avoids the size limits and timeouts of
+/// `adb install` with huge APKs on some hosts. Falls back to `adb install` if the device does not
+/// support sessions.
+pub fn install_package_session(
+    adb_path: &str,
+    device_serial: &str,
+    apk_paths: &[&str],
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<()> {
+    let sizes = apk_paths
+        .iter()
+        .map(|path| -> AdbResult<usize> {
+            let size = fs::metadata(path)
+                .context(format!("Failed to open {path}"))?
+                .len();
+            let size = usize::try_from(size).context(format!("{path} is too large"))?;
+
+            Ok(size)
+        })
+        .collect::<AdbResult<Vec<_>>>()?;
+    let total_size = sizes.iter().sum::<usize>();
+
+    let text = shell(
+        adb_path,
+        device_serial,
+        &["pm", "install-create", "-r", "-S", &total_size.to_string()],
+    )
+    .context("Failed to create install session")?;
+    let Some(session_id) = parse::parse_install_session_id(&text) else {
+        dbg_connection!(
+            "install_package_session: Sessions unavailable: {}",
+            text.trim()
+        );
+
+        return install_packages(adb_path, device_serial, apk_paths);
+    };
+
+    let res = apk_paths
+        .iter()
+        .zip(&sizes)
+        .enumerate()
+        .try_fold(
+            0,
+            |written_size, (index, (path, size))| -> AdbResult<usize> {
+                let mut file = File::open(path).context(format!("Failed to open {path}"))?;
+                let command = format!("pm install-write -S {size} {session_id} {index}.apk -");
+                let output = exec_in(
+                    adb_path,
+                    device_serial,
+                    &command,
+                    &mut file,
+                    Some(*size),
+                    cancel_handle,
+                    |written, _| progress_callback(written_size + written, Some(total_size)),
+                )
+                .context(format!("Failed to write {path} to install session"))?;
+                let text = String::from_utf8_lossy(&output.stdout);
+                if !text.starts_with("Success") {
+                    return fail(AdbError::Other(anyhow!(
+                        "Failed to write {path} to install session: {}",
+                        text.trim()
+                    )));
+                }
+
+                Ok(written_size + size)
+            },
+        )
+        .and_then(|_| {
+            cancel_handle.check()?;
+            let text = shell(
+                adb_path,
+                device_serial,
+                &["pm", "install-commit", &session_id.to_string()],
+            )?;
+            if !text.starts_with("Success") {
+                return fail(AdbError::Other(anyhow!(
+                    "Failed to install package: {}",
+                    text.trim()
+                )));
+            }
+
+            Ok(())
+        });
+    if res.is_err() {
+        // Otherwise the written APKs stay on the device until the next reboot
+        shell(
+            adb_path,
+            device_serial,
+            &["pm", "install-abandon", &session_id.to_string()],
+        )
+        .ok();
+    }
+
+    res
+}
+
+// ABIs of the split names, with the ones that end like another one first
+const SPLIT_ABIS: [&str; 7] = [
+    "arm64_v8a",
+    "armeabi_v7a",
+    "armeabi",
+    "x86_64",
+    "x86",
+    "mips64",
+    "mips",
+];
+const SPLIT_DENSITIES: [(&str, u32); 7] = [
+    ("ldpi", 120),
+    ("mdpi", 160),
+    ("tvdpi", 213),
+    ("hdpi", 240),
+    ("xhdpi", 320),
+    ("xxhdpi", 480),
+    ("xxxhdpi", 640),
+];
+
+/// Container of an application, detected from the entries of the archive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackageFormat {
+    Apk,
+    // Split APKs built by bundletool, in "splits/"
+    Apks,
+    // Split APKs from APKMirror, at the root of the archive
+    Apkm,
+}
+
+/// Installs an APK, or the splits of an APKS or APKM bundle that apply to the device: the splits
+/// for its preferred ABI and closest screen density, and all the others (base, languages,
+/// features). The APKs are installed like with `install_package_session`.
+pub fn install_package_file(
+    adb_path: &str,
+    device_serial: &str,
+    path: &Path,
+    cancel_handle: &WiredCancelHandle,
+    progress_callback: impl Fn(usize, Option<usize>),
+) -> AdbResult<()> {
+    let not_supported = || {
+        fail(AdbError::Other(anyhow!(
+            "{} is not an APK, APKS or APKM file",
+            path.display()
+        )))
+    };
+
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let Ok(mut archive) = ZipArchive::new(file) else {
+        return not_supported();
+    };
+    let entry_names = archive.file_names().map(str::to_owned).collect::<Vec<_>>();
+    let entry_names = entry_names.iter().map(String::as_str).collect::<Vec<_>>();
+    let Some(format) = detect_package_format(&entry_names) else {
+        return not_supported();
+    };
+    if format == PackageFormat::Apk {
+        return install_package_session(
+            adb_path,
+            device_serial,
+            &[&path.to_string_lossy()],
+            cancel_handle,
+            progress_callback,
+        );
+    }
+
+    let abis = get_prop(adb_path, device_serial, "ro.product.cpu.abilist")?
+        .split(',')
+        .map(|abi| abi.trim().replace('-', "_"))
+        .filter(|abi| !abi.is_empty())
+        .collect::<Vec<_>>();
+    let abis = abis.iter().map(String::as_str).collect::<Vec<_>>();
+    let density_dpi = get_prop(adb_path, device_serial, "ro.sf.lcd_density")
+        .ok()
+        .and_then(|density| density.parse().ok());
+    let splits = select_splits(&bundle_apk_names(format, &entry_names), &abis, density_dpi);
+    if splits.is_empty() {
+        return fail(AdbError::Other(anyhow!(
+            "{} contains no APK",
+            path.display()
+        )));
+    }
+    dbg_connection!("install_package_file: Installing {splits:?}");
+
+    let dir = env::temp_dir().join(format!(
+        "alvr_splits_{}_{}",
+        std::process::id(),
+        SPLITS_DIR_COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir)?;
+    let res = (|| {
+        let mut apk_paths = vec![];
+        for (index, name) in splits.iter().enumerate() {
+            let mut entry = archive.by_name(name).context(format!(
+                "Failed to read {name} from {}. Encrypted bundles are not supported",
+                path.display()
+            ))?;
+            let apk_path = dir.join(format!("{index}.apk"));
+            io::copy(&mut entry, &mut File::create(&apk_path)?)?;
+            apk_paths.push(apk_path.to_string_lossy().into_owned());
+        }
+        let apk_paths = apk_paths.iter().map(String::as_str).collect::<Vec<_>>();
+
+        install_package_session(
+            adb_path,
+            device_serial,
+            &apk_paths,
+            cancel_handle,
+            progress_callback,
+        )
+    })();
+    fs::remove_dir_all(&dir).ok();
+
+    res
+}
+
+fn detect_package_format(entry_names: &[&str]) -> Option<PackageFormat> {
+    if entry_names.contains(&"AndroidManifest.xml") {
+        Some(PackageFormat::Apk)
+    } else if entry_names
+        .iter()
+        .any(|name| name.starts_with("splits/") && name.ends_with(".apk"))
+    {
+        Some(PackageFormat::Apks)
+    } else if entry_names.contains(&"base.apk") {
+        Some(PackageFormat::Apkm)
+    } else {
+        None
+    }
+}
+
+fn bundle_apk_names<'a>(format: PackageFormat, entry_names: &[&'a str]) -> Vec<&'a str> {
+    entry_names
+        .iter()
+        .copied()
+        .filter(|name| match format {
+            PackageFormat::Apk => false,
+            PackageFormat::Apks => name.starts_with("splits/") && name.ends_with(".apk"),
+            PackageFormat::Apkm => !name.contains('/') && name.ends_with(".apk"),
+        })
+        .collect()
+}
+
+// Config splits are named like "base-arm64_v8a.apk" (bundletool) or
+// "split_config.xxhdpi.apk" (APKMirror)
+fn split_config<'a>(name: &str, configs: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let stem = name.strip_suffix(".apk").unwrap_or(name);
+
+    configs.into_iter().find(|config| {
+        stem.strip_suffix(config)
+            .is_some_and(|prefix| prefix.ends_with(['-', '.', '_']))
+    })
+}
+
+// `abis` are in the order of preference of the device. All the density splits are kept if the
+// density is unknown.
+fn select_splits<'a>(names: &[&'a str], abis: &[&str], density_dpi: Option<u32>) -> Vec<&'a str> {
+    let split_abi = |name| split_config(name, SPLIT_ABIS);
+    let split_density = |name| {
+        split_config(name, SPLIT_DENSITIES.map(|(config, _)| config)).and_then(|config| {
+            SPLIT_DENSITIES
+                .iter()
+                .find(|(density, _)| *density == config)
+                .map(|(_, dpi)| *dpi)
+        })
+    };
+
+    let abi = abis
+        .iter()
+        .find(|abi| names.iter().any(|name| split_abi(name) == Some(**abi)));
+    let densities = names
+        .iter()
+        .filter_map(|name| split_density(name))
+        .collect::<Vec<_>>();
+    // The smallest density at least as high as the device, otherwise the highest one
+    let density = density_dpi.and_then(|dpi| {
+        densities
+            .iter()
+            .filter(|density| **density >= dpi)
+            .min()
+            .or_else(|| densities.iter().max())
+            .copied()
+    });
+
+    names
+        .iter()
+        .copied()
+        .filter(|name| split_abi(name).is_none_or(|split_abi| Some(&split_abi) == abi))
+        .filter(|name| {
+            density.is_none() || split_density(name).is_none_or(|dpi| Some(dpi) == density)
+        })
+        .collect()
+}
+
+fn install_packages(adb_path: &str, device_serial: &str, apk_paths: &[&str]) -> AdbResult<()> {
+    let mut args = vec!["-s", device_serial, "install-multiple", "-r"];
+    args.extend(apk_paths);
+    let output = run_adb(&mut get_command(adb_path, &args)).context("Failed to install package")?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    if !text.contains("Success") {
+        return fail(AdbError::Other(anyhow!(
+            "Failed to install package: {}{}",
+            text.trim(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn is_package_installed(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<bool> {
+    let found = list_installed_packages(adb_path, device_serial)
+        .context(format!(
+            "Failed to check if package {application_id} is installed"
+        ))?
+        .contains(application_id);
+
+    Ok(found)
+}
+
+// Returns the seconds since 1970-01-01 00:00:00 in the device time zone
+pub fn get_package_update_time(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<i64> {
+    let text = dumpsys(adb_path, device_serial, &["package", application_id])
+        .context(format!("Failed to get update time of {application_id}"))?;
+
+    parse::parse_package_update_time(&text)
+        .parse_context(format!("Failed to parse update time of {application_id}"))
+}
+
+pub fn get_package_version(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<Option<String>> {
+    let text = dumpsys(adb_path, device_serial, &["package", application_id])
+        .context(format!("Failed to get version of {application_id}"))?;
+
+    Ok(parse::parse_package_version(&text))
+}
+
+pub fn list_granted_permissions(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<HashSet<String>> {
+    let text = dumpsys(adb_path, device_serial, &["package", application_id])
+        .context(format!("Failed to get permissions of {application_id}"))?;
+
+    Ok(parse::parse_granted_permissions(&text))
+}
+
+// Only runtime permissions requested by the package can be granted
+pub fn grant_permission(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+    permission: &str,
+) -> AdbResult<()> {
+    checked_shell(
+        adb_path,
+        device_serial,
+        &["pm", "grant", application_id, permission],
+    )
+    .context(format!("Failed to grant {permission} to {application_id}"))?;
+
+    Ok(())
+}
+
+pub fn uninstall_package(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<()> {
+    run_adb(&mut get_command(
+        adb_path,
+        &["-s", device_serial, "uninstall", application_id],
+    ))
+    .context(format!("Failed to uninstall {application_id}"))?;
+
+    Ok(())
+}
+
+pub fn list_installed_packages(adb_path: &str, device_serial: &str) -> AdbResult<HashSet<String>> {
+    let text = shell(adb_path, device_serial, &["pm", "list", "package"])
+        .context("Failed to list installed packages")?;
+    let packages = text.lines().map(|l| l.replace("package:", "")).collect();
+
+    Ok(packages)
+}
+
+// Disabled packages are still listed as installed, but cannot be launched
+pub fn list_disabled_packages(adb_path: &str, device_serial: &str) -> AdbResult<HashSet<String>> {
+    let text = shell(adb_path, device_serial, &["pm", "list", "packages", "-d"])
+        .context("Failed to list disabled packages")?;
+    let packages = text.lines().map(|l| l.replace("package:", "")).collect();
+
+    Ok(packages)
+}
+
+// False if `pm disable` or a launcher disabled the package. A package that is not installed is
+// reported as enabled.
+pub fn is_package_enabled(
+    adb_path: &str,
+    device_serial: &str,
+    application_id: &str,
+) -> AdbResult<bool> {
+    Ok(!list_disabled_packages(adb_path, device_serial)?.contains(application_id))
+}
+
+pub fn enable_package(adb_path: &str, device_serial: &str, application_id: &str) -> AdbResult<()> {
+    let text = shell(adb_path, device_serial, &["pm", "enable", application_id])
+        .context(format!("Failed to enable {application_id}"))?;
+    // pm prints "Package <package> new state: enabled" on success, device policies can prevent it
+    if !text.contains("new state: enabled") {
+        return fail(AdbError::Other(anyhow!(
+            "Failed to enable {application_id}: {}",
+            text.trim()
+        )));
+    }
+
+    Ok(())
+}
+
+////////
+// Paths
+
+/// Returns the path of a local (i.e. installed by ALVR) or OS version of `adb` if found, `None` otherwise.
+pub fn get_adb_path(layout: &afs::Layout) -> Option<String> {
+    let exe_name = afs::exec_fname("adb").to_owned();
+    let adb_path = get_command(&exe_name, &[])
+        .output()
+        .is_ok()
+        .then_some(exe_name);
+
+    adb_path.or_else(|| {
+        let path = layout.local_adb_exe();
+
+        path.try_exists()
+            .unwrap_or(false)
+            .then(|| path.to_string_lossy().to_string())
+    })
+}
+
+////////
+// Power
+
+pub fn is_screen_on(adb_path: &str, device_serial: &str) -> AdbResult<bool> {
+    let text =
+        dumpsys(adb_path, device_serial, &["power"]).context("Failed to get screen state")?;
+
+    parse::parse_screen_on(&text).parse_context("Failed to parse screen state")
+}
+
+pub fn wake_screen(adb_path: &str, device_serial: &str) -> AdbResult<()> {
+    shell(
+        adb_path,
+        device_serial,
+        &["input", "keyevent", "KEYCODE_WAKEUP"],
+    )
+    .context("Failed to wake screen")?;
+
+    Ok(())
+}
+
+pub fn list_displays(adb_path: &str, device_serial: &str) -> AdbResult<Vec<Display>> {
+    let text = dumpsys(adb_path, device_serial, &["display"]).context("Failed to list displays")?;
+
+    Ok(parse::parse_displays(&text))
+}
+
+pub fn get_battery_state(adb_path: &str, device_serial: &str) -> AdbResult<BatteryState> {
+    let text =
+        dumpsys(adb_path, device_serial, &["battery"]).context("Failed to get battery state")?;
+
+    parse::parse_battery_state(&text).parse_context("Failed to parse battery state")
+}
+
+pub fn get_keyguard_state(adb_path: &str, device_serial: &str) -> AdbResult<KeyguardState> {
+    let text = dumpsys(adb_path, device_serial, &["window", "policy"])
+        .context("Failed to get lock screen state")?;
+
+    parse::parse_keyguard_state(&text).parse_context("Failed to parse lock screen state")
+}
+
+pub fn is_keyguard_showing(adb_path: &str, device_serial: &str) -> AdbResult<bool> {
+    Ok(get_keyguard_state(adb_path, device_serial)?.showing)
+}
+
+pub fn get_stay_on_while_plugged_in(adb_path: &str, device_serial: &str) -> AdbResult<u32> {
+    let text = shell(
+        adb_path,
+        device_serial,
+        &["settings", "get", "global", "stay_on_while_plugged_in"],
+    )
+    .context("Failed to get stay on while plugged in setting")?;
+
+    parse::parse_stay_on_while_plugged_in(&text)
+        .parse_context("Failed to parse stay on while plugged in setting")
+}
+
+// Restores a value read with `get_stay_on_while_plugged_in`
+pub fn set_stay_on_while_plugged_in(
+    adb_path: &str,
+    device_serial: &str,
+    value: u32,
+) -> AdbResult<()> {
+    shell(
+        adb_path,
+        device_serial,
+        &[
+            "settings",
+            "put",
+            "global",
+            "stay_on_while_plugged_in",
+            &value.to_string(),
+        ],
+    )
+    .context("Failed to set stay on while plugged in setting")?;
+
+    Ok(())
+}
+
+// `namespace` is one of "system", "secure" or "global". Returns `None` if the setting is not set.
+pub fn get_setting(
+    adb_path: &str,
+    device_serial: &str,
+    namespace: &str,
+    key: &str,
+) -> AdbResult<Option<String>> {
+    let output = checked_shell(
+        adb_path,
+        device_serial,
+        &["settings", "get", namespace, key],
+    )
+    .context(format!("Failed to get setting {namespace}/{key}"))?;
+
+    Ok(parse::parse_setting_value(&output))
+}
+
+pub fn put_setting(
+    adb_path: &str,
+    device_serial: &str,
+    namespace: &str,
+    key: &str,
+    value: &str,
+) -> AdbResult<()> {
+    checked_shell(
+        adb_path,
+        device_serial,
+        &["settings", "put", namespace, key, value],
+    )
+    .context(format!("Failed to set setting {namespace}/{key}"))?;
+
+    Ok(())
+}
+
+pub fn delete_setting(
+    adb_path: &str,
+    device_serial: &str,
+    namespace: &str,
+    key: &str,
+) -> AdbResult<()> {
+    checked_shell(
+        adb_path,
+        device_serial,
+        &["settings", "delete", namespace, key],
+    )
+    .context(format!("Failed to delete setting {namespace}/{key}"))?;
+
+    Ok(())
+}
+
+// Unset properties are empty
+pub fn get_prop(adb_path: &str, device_serial: &str, key: &str) -> AdbResult<String> {
+    let output = checked_shell(adb_path, device_serial, &["getprop", key])
+        .context(format!("Failed to get property {key}"))?;
+
+    Ok(output.trim().to_owned())
+}
+
+/// Package of the runtime selected in the Khronos OpenXR runtime broker, which the loader of the
+/// apps prefers to the runtime of the system. None if no broker is installed or no runtime is
+/// selected.
+pub fn get_active_openxr_runtime(adb_path: &str, device_serial: &str) -> AdbResult<Option<String>> {
+    let abi = get_prop(adb_path, device_serial, "ro.product.cpu.abi")?;
+    let uri = format!("{OPENXR_RUNTIME_BROKER_URI}/{abi}/runtimes/active/0");
+    let output = shell(
+        adb_path,
+        device_serial,
+        &[
+            "content",
+            "query",
+            "--uri",
+            &uri,
+            "--projection",
+            "package_name",
+        ],
+    )?;
+
+    Ok(parse::parse_content_query_value(&output, "package_name"))
+}
+
+/// How a device can be connected to over the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WirelessConnectPath {
+    // Wireless debugging is enabled (Android 11+): `adb pair` then `adb connect` to the port shown
+    // in the developer options
+    Tls,
+    // `adb tcpip` while the device is connected with USB, then `adb connect`
+    Tcpip,
+}
+
+// Set by the wireless debugging toggle of the developer options
+pub fn is_wireless_debugging_enabled(adb_path: &str, device_serial: &str) -> AdbResult<bool> {
+    let value = get_prop(adb_path, device_serial, "persist.adb.tls_server.enable")?;
+
+    Ok(parse::parse_prop_bool(&value).unwrap_or(false))
+}
+
+pub fn get_wireless_connect_path(
+    adb_path: &str,
+    device_serial: &str,
+) -> AdbResult<WirelessConnectPath> {
+    if is_wireless_debugging_enabled(adb_path, device_serial)? {
+        Ok(WirelessConnectPath::Tls)
+    } else {
+        Ok(WirelessConnectPath::Tcpip)
+    }
+}
+
+// For the TLS path, which cannot be used until the user enables wireless debugging
+pub fn require_wireless_debugging(adb_path: &str, device_serial: &str) -> AdbResult<()> {
+    if !is_wireless_debugging_enabled(adb_path, device_serial)? {
+        return fail(AdbError::Other(anyhow!(
+            "Wireless debugging is disabled on {device_serial}, enable it in the developer \
+             options of the headset"
+        )));
+    }
+
+    Ok(())
+}
+
+// Without root, only properties whose SELinux context allows the shell user can be set, e.g.
+// "debug.*"
+pub fn set_prop(adb_path: &str, device_serial: &str, key: &str, value: &str) -> AdbResult<()> {
+    checked_shell(adb_path, device_serial, &["setprop", key, value])
+        .context(format!("Failed to set property {key}"))?;
+
+    Ok(())
+}
+
+// Keeps the screen on while the device is powered over USB
+pub fn stay_on_usb(adb_path: &str, device_serial: &str) -> AdbResult<()> {
+    shell(adb_path, device_serial, &["svc", "power", "stayon", "usb"])
+        .context("Failed to keep screen on")?;
+
+    Ok(())
+}
+
+// Only insecure keyguards can be dismissed. `wm dismiss-keyguard` is available since Android 8, the
+// menu key unlocks older versions.
+pub fn dismiss_keyguard(adb_path: &str, device_serial: &str) -> AdbResult<()> {
+    shell(adb_path, device_serial, &["wm", "dismiss-keyguard"])
+        .context("Failed to dismiss lock screen")?;
+    shell(
+        adb_path,
+        device_serial,
+        &["input", "keyevent", "KEYCODE_MENU"],
+    )
+    .context("Failed to dismiss lock screen")?;
+
+    Ok(())
+}
+
+////////
+// Utility
+pub fn get_uptime(adb_path: &str, device_serial: &str) -> AdbResult<Duration> {
+    let output = shell(adb_path, device_serial, &["cat", "/proc/uptime"])
+        .context("Failed to get system uptime")?;
+
+    let uptime_string = output
+        .split_ascii_whitespace()
+        .next()
+        .context("Empty result from /proc/uptime")?;
+
+    let uptime = f64::from_str(uptime_string).parse_context("Cannot parse uptime into an f64")?;
+
+    Duration::try_from_secs_f64(uptime).parse_context("Invalid f64 value for a duration ")
+}
+
+// `adb get-state` fails instead of printing the states other than "device", "bootloader" and
+// "recovery", e.g. with `AdbError::DeviceUnauthorized` while the device is still authorizing
+pub fn get_state(adb_path: &str, device_serial: &str) -> AdbResult<Option<ConnectionState>> {
+    let output = run_adb(&mut get_command(
+        adb_path,
+        &["-s", device_serial, "get-state"],
+    ))?;
+    if !output.status.success() {
+        return fail(command_failed(&output));
+    }
+
+    Ok(parse::parse_connection_state(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+// `sys.boot_completed` is set once the package and activity managers are up. Older devices only
+// set `dev.bootcomplete`.
+pub fn is_boot_completed(adb_path: &str, device_serial: &str) -> AdbResult<bool> {
+    for property in ["sys.boot_completed", "dev.bootcomplete"] {
+        let value = shell(adb_path, device_serial, &["getprop", property])
+            .context("Failed to get boot state")?;
+        if value.trim() == "1" {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+pub fn get_device_clock(adb_path: &str, device_serial: &str) -> AdbResult<DeviceClock> {
+    let text =
+        shell(adb_path, device_serial, &["date", "+%s %z"]).context("Failed to get device time")?;
+
+    parse::parse_device_clock(&text).parse_context("Failed to parse device time")
+}
+
+//////////////////
+// Port forwarding
+
+pub fn list_forwarded_ports(adb_path: &str, device_serial: &str) -> AdbResult<Vec<ForwardedPorts>> {
+    let output = run_adb(&mut get_command(
+        adb_path,
+        &["-s", device_serial, "forward", "--list"],
+    ))
+    .context(format!(
+        "Failed to list forwarded ports of device {device_serial:?}"
+    ))?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let forwarded_ports = text
+        .lines()
+        .filter_map(parse::parse_forwarded_ports)
+        .collect();
+
+    Ok(forwarded_ports)
+}
+
+pub fn forward_port(adb_path: &str, device_serial: &str, port: u16) -> AdbResult<()> {
+    run_adb(&mut get_command(
+        adb_path,
+        &[
+            "-s",
+            device_serial,
+            "forward",
+            &format!("tcp:{port}"),
+            &format!("tcp:{port}"),
+        ],
+    ))
+    .context(format!(
+        "Failed to forward port {port:?} of device {device_serial:?}"
+    ))?;
+
+    Ok(())
+}
+
+// Forwards a free local port, picked by adb, to `remote_port`. Returns the local port.
+pub fn forward_free_port(adb_path: &str, device_serial: &str, remote_port: u16) -> AdbResult<u16> {
+    let output = run_adb(&mut get_command(
+        adb_path,
+        &[
+            "-s",
+            device_serial,
+            "forward",
+            "tcp:0",
+            &format!("tcp:{remote_port}"),
+        ],
+    ))
+    .context(format!(
+        "Failed to forward port {remote_port:?} of device {device_serial:?}"
+    ))?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    text.trim().parse().parse_context(format!(
+        "Failed to forward port {remote_port:?} of device {device_serial:?}: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+pub fn remove_forward(adb_path: &str, device_serial: &str, local_port: u16) -> AdbResult<()> {
+    run_adb(&mut get_command(
+        adb_path,
+        &[
+            "-s",
+            device_serial,
+            "forward",
+            "--remove",
+            &format!("tcp:{local_port}"),
+        ],
+    ))
+    .context(format!(
+        "Failed to remove forward of port {local_port:?} of device {device_serial:?}"
+    ))?;
+
+    Ok(())
+}
+
+///////
+// Root
+
+// Production headsets refuse with "adbd cannot run as root in production builds". The wired
+// connection never calls these.
+#[cfg(feature = "root")]
+pub fn root(adb_path: &str, device_serial: &str) -> AdbResult<()> {
+    restart_adbd(adb_path, device_serial, "root")
+}
+
+#[cfg(feature = "root")]
+pub fn unroot(adb_path: &str, device_serial: &str) -> AdbResult<()> {
+    restart_adbd(adb_path, device_serial, "unroot")
+}
+
+// adbd restarts with the new user, which briefly drops the transport of the device. Returns once
+// the device is back.
+#[cfg(feature = "root")]
+fn restart_adbd(adb_path: &str, device_serial: &str, command: &str) -> AdbResult<()> {
+    let output = run_adb(&mut get_command(adb_path, &["-s", device_serial, command]))
+        .context(format!("Failed to run adb {command}"))?;
+    // adb exits successfully even when adbd refuses to restart
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let text = text.trim();
+    if !output.status.success() || text.contains("cannot") {
+        return fail(AdbError::CommandFailed {
+            stderr: text.to_owned(),
+            exit_code: output.status.code(),
+        });
+    }
+    // "adbd is already running as root" or "adbd not running as root"
+    if !text.starts_with("restarting") {
+        return Ok(());
+    }
+
+    let deadline = std::time::Instant::now() + ADBD_RESTART_TIMEOUT;
+    loop {
+        // The old transport can still be listed right after the restart
+        thread::sleep(ADBD_RESTART_POLL_INTERVAL);
+
+        if list_devices(adb_path)?.iter().any(|device| {
+            device.serial.as_deref() == Some(device_serial)
+                && device.state == Some(parse::ConnectionState::Device)
+        }) {
+            return Ok(());
+        }
+        if std::time::Instant::now() >= deadline {
+            return fail(AdbError::Timeout(format!(
+                "{device_serial} did not reconnect after adb {command}"
+            )));
+        }
+    }
+}
+
+/////////
+// Server
+
+pub fn start_server(adb_path: &str) -> AdbResult<()> {
+    let output = run_adb(&mut get_command(adb_path, &["start-server"]))
+        .context("Failed to start ADB server")?;
+    if !output.status.success() {
+        return fail(AdbError::ServerUnreachable(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Whoever started it, the server listens on its port as long as it runs
+pub fn is_server_running() -> bool {
+    TcpStream::connect_timeout(
+        &SocketAddr::from((Ipv4Addr::LOCALHOST, get_server_port())),
+        SERVER_CONNECT_TIMEOUT,
+    )
+    .is_ok()
+}
+
+fn get_server_port() -> u16 {
+    let adb_env = ADB_ENV.lock().unwrap();
+    adb_env
+        .iter()
+        .find(|(key, _)| key == SERVER_PORT_ENV_VAR)
+        .map(|(_, port)| port.clone())
+        .or_else(|| env::var(SERVER_PORT_ENV_VAR).ok())
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_SERVER_PORT)
+}
+
+pub fn kill_server(adb_path: &str) -> AdbResult<()> {
+    run_adb(&mut get_command(adb_path, &["kill-server"])).context("Failed to kill ADB server")?;
+
+    Ok(())
+}
+
+/// Like `start_server`, but if the server port is held by a process that doesn't answer as an ADB
+/// server, e.g. a server left behind by a crash, the stray servers are terminated with
+/// `cleanup_stray_servers` and the server is started again.
+pub fn start_server_with_cleanup(adb_path: &str) -> AdbResult<()> {
+    let Err(e) = start_server(adb_path) else {
+        return Ok(());
+    };
+    if !parse::is_server_port_in_use(&format!("{e:#}")) {
+        return Err(e);
+    }
+
+    #[cfg_attr(not(debug_assertions), expect(unused_variables))]
+    let count = cleanup_stray_servers().context("Failed to clean up stray ADB servers")?;
+    dbg_connection!("start_server_with_cleanup: Terminated {count} stray ADB servers");
+
+    start_server(adb_path)
+}
+
+/// Force-terminates the processes named adb that listen on the ADB server port (5037, or
+/// ANDROID_ADB_SERVER_PORT), which `kill-server` cannot reach when they don't answer. Returns how
+/// many were terminated. Other programs holding the port are left alone.
+///
+/// The listening processes are found with `netstat -ano` on Windows and with `lsof` on Linux and
+/// macOS, which must be installed. Processes owned by other users can't be found or terminated
+/// without elevated rights.
+pub fn cleanup_stray_servers() -> AdbResult<usize> {
+    let mut count = 0;
+    for pid in list_listening_pids(get_server_port())? {
+        let Some(name) = get_process_name_by_pid(pid)? else {
+            continue;
+        };
+        if Path::new(&name)
+            .file_stem()
+            .is_some_and(|stem| stem == "adb")
+        {
+            dbg_connection!("cleanup_stray_servers: Terminating {name} ({pid})");
+            terminate_process(pid)?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(windows)]
+fn list_listening_pids(port: u16) -> AdbResult<Vec<u32>> {
+    let output = get_command("netstat", &["-ano", "-p", "TCP"])
+        .output()
+        .context("Failed to run netstat")?;
+
+    Ok(parse::parse_netstat_listening_pids(
+        &String::from_utf8_lossy(&output.stdout),
+        port,
+    ))
+}
+
+#[cfg(not(windows))]
+fn list_listening_pids(port: u16) -> AdbResult<Vec<u32>> {
+    // lsof exits with an error if nothing matches
+    let output = get_command(
+        "lsof",
+        &["-nP", "-t", &format!("-iTCP:{port}"), "-sTCP:LISTEN"],
+    )
+    .output()
+    .context("Failed to run lsof")?;
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect())
+}
+
+#[cfg(windows)]
+fn get_process_name_by_pid(pid: u32) -> AdbResult<Option<String>> {
+    let output = get_command(
+        "tasklist",
+        &["/FI", &format!("PID eq {pid}"), "/FO", "CSV", "/NH"],
+    )
+    .output()
+    .context("Failed to run tasklist")?;
+
+    Ok(parse::parse_tasklist_image_name(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+// macOS prints the full path of the executable
+#[cfg(not(windows))]
+fn get_process_name_by_pid(pid: u32) -> AdbResult<Option<String>> {
+    let output = get_command("ps", &["-p", &pid.to_string(), "-o", "comm="])
+        .output()
+        .context("Failed to run ps")?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+    Ok((!name.is_empty()).then_some(name))
+}
+
+#[cfg(windows)]
+fn terminate_process(pid: u32) -> AdbResult<()> {
+    let output = get_command("taskkill", &["/F", "/PID", &pid.to_string()])
+        .output()
+        .context("Failed to run taskkill")?;
+    if !output.status.success() {
+        return fail(command_failed(&output));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn terminate_process(pid: u32) -> AdbResult<()> {
+    let output = get_command("kill", &["-9", &pid.to_string()])
+        .output()
+        .context("Failed to run kill")?;
+    if !output.status.success() {
+        return fail(command_failed(&output));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AdbTransport, MockResponse, MockTransport};
+    use std::cell::RefCell;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = env::temp_dir().join(format!("alvr_adb_test_{}", std::process::id()));
+        File::create(&path).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        ensure_executable(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o755);
+
+        assert!(ensure_executable(&path).is_err());
+    }
+
+    #[test]
+    fn test_check_install_output() {
+        let mock = MockTransport::new();
+        let install = |mock: &MockTransport| {
+            mock.run_command("adb", &["install".to_owned(), "client.apk".to_owned()])
+                .unwrap()
+        };
+
+        mock.respond(&["install"], "Performing Streamed Install\nSuccess\n");
+        check_install_output(&install(&mock)).unwrap();
+
+        // Printed with a successful exit status by some adb versions
+        mock.respond(
+            &["install"],
+            "Performing Streamed Install\nFailure [INSTALL_FAILED_UPDATE_INCOMPATIBLE]\n",
+        );
+        assert!(matches!(
+            as_adb_error(&check_install_output(&install(&mock)).unwrap_err()),
+            Some(AdbError::CommandFailed { stderr, .. })
+                if stderr.contains("INSTALL_FAILED_UPDATE_INCOMPATIBLE")
+        ));
+
+        mock.respond_with(
+            &["install"],
+            MockResponse {
+                stderr: "adb: failed to install client.apk: Failure \
+                    [INSTALL_FAILED_INSUFFICIENT_STORAGE]\n"
+                    .to_owned(),
+                exit_code: 1,
+                ..Default::default()
+            },
+        );
+        assert!(check_install_output(&install(&mock)).is_err());
+    }
+
+    #[test]
+    fn test_set_adb_env() {
+        let missing_key = env::temp_dir().join(format!("alvr_adb_key_{}", std::process::id()));
+        let adb_env = HashMap::from([(
+            VENDOR_KEYS_ENV_VAR.to_owned(),
+            missing_key.to_string_lossy().into_owned(),
+        )]);
+        assert!(set_adb_env(&adb_env).is_err());
+
+        File::create(&missing_key).unwrap();
+        let res = set_adb_env(&adb_env);
+        fs::remove_file(&missing_key).unwrap();
+        res.unwrap();
+        let command = get_command("adb", &["devices"]);
+        assert!(
+            command
+                .get_envs()
+                .any(|(key, _)| key == VENDOR_KEYS_ENV_VAR)
+        );
+
+        let adb_env = HashMap::from([(SERVER_PORT_ENV_VAR.to_owned(), "port".to_owned())]);
+        assert!(set_adb_env(&adb_env).is_err());
+        set_adb_env(&HashMap::new()).unwrap();
+    }
+
+    #[test]
+    fn test_verify_sha1() {
+        assert!(verify_sha1(b"abc", "a9993e364706816aba3e25717850c26c9cd0d89d").is_ok());
+        assert!(verify_sha1(b"abc", "A9993E364706816ABA3E25717850C26C9CD0D89D\n").is_ok());
+        assert!(verify_sha1(b"abd", "a9993e364706816aba3e25717850c26c9cd0d89d").is_err());
+    }
+
+    #[test]
+    fn test_hash_local_apk() {
+        let path = env::temp_dir().join(format!("alvr_adb_test_{}.apk", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+        let hash = hash_local_apk(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(hash.unwrap(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+
+        assert!(hash_local_apk(&path).is_err());
+    }
+
+    #[test]
+    fn test_detect_package_format() {
+        assert_eq!(
+            detect_package_format(&["AndroidManifest.xml", "classes.dex", "lib/arm64-v8a/a.so"]),
+            Some(PackageFormat::Apk)
+        );
+        assert_eq!(
+            detect_package_format(&["toc.pb", "splits/base-master.apk"]),
+            Some(PackageFormat::Apks)
+        );
+        assert_eq!(
+            detect_package_format(&["info.json", "base.apk", "split_config.en.apk"]),
+            Some(PackageFormat::Apkm)
+        );
+        assert_eq!(detect_package_format(&["readme.txt"]), None);
+    }
+
+    #[test]
+    fn test_select_splits() {
+        let apks = [
+            "splits/base-master.apk",
+            "splits/base-arm64_v8a.apk",
+            "splits/base-armeabi_v7a.apk",
+            "splits/base-x86_64.apk",
+            "splits/base-xhdpi.apk",
+            "splits/base-xxhdpi.apk",
+            "splits/base-en.apk",
+        ];
+        assert_eq!(
+            select_splits(&apks, &["arm64_v8a", "armeabi_v7a"], Some(400)),
+            [
+                "splits/base-master.apk",
+                "splits/base-arm64_v8a.apk",
+                "splits/base-xxhdpi.apk",
+                "splits/base-en.apk",
+            ]
+        );
+        // Denser than every split, and an ABI without split
+        assert_eq!(
+            select_splits(&apks, &["riscv64", "armeabi_v7a"], Some(640)),
+            [
+                "splits/base-master.apk",
+                "splits/base-armeabi_v7a.apk",
+                "splits/base-xxhdpi.apk",
+                "splits/base-en.apk",
+            ]
+        );
+
+        let apkm = [
+            "base.apk",
+            "split_config.arm64_v8a.apk",
+            "split_config.x86.apk",
+            "split_config.mdpi.apk",
+            "split_config.hdpi.apk",
+        ];
+        assert_eq!(
+            select_splits(&apkm, &["arm64_v8a"], None),
+            [
+                "base.apk",
+                "split_config.arm64_v8a.apk",
+                "split_config.mdpi.apk",
+                "split_config.hdpi.apk",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_platform_tools_sha1() {
+        let pin = PlatformToolsPin {
+            version: "35.0.0".to_owned(),
+            sha1: "a9993e364706816aba3e25717850c26c9cd0d89d".to_owned(),
+        };
+        let mirror_sha1 = "84983e441c3bd26ebaae4aa1f95129e5e54670f1";
+        assert_eq!(
+            get_platform_tools_sha1(Some(&pin), None, false).unwrap(),
+            pin.sha1
+        );
+        assert_eq!(
+            get_platform_tools_sha1(None, None, false).ok().as_deref(),
+            PLATFORM_TOOLS_SHA1
+        );
+
+        // The checksum of a mirror is used even if a version is pinned
+        assert_eq!(
+            get_platform_tools_sha1(Some(&pin), Some(mirror_sha1.to_owned()), true).unwrap(),
+            mirror_sha1
+        );
+        assert_eq!(
+            get_platform_tools_sha1(None, None, true).ok().as_deref(),
+            PLATFORM_TOOLS_SHA1
+        );
+    }
+
+    #[test]
+    fn test_read_offline_bundle() {
+        let path = env::temp_dir().join(format!("alvr_adb_bundle_{}.zip", std::process::id()));
+        let checksum_path = PathBuf::from(format!("{}.sha1", path.display()));
+        let pin = |sha1: &str| PlatformToolsPin {
+            version: "35.0.0".to_owned(),
+            sha1: sha1.to_owned(),
+        };
+
+        assert!(read_offline_bundle(&path, None).unwrap().is_none());
+
+        fs::write(&path, b"abc").unwrap();
+        fs::write(
+            &checksum_path,
+            "a9993e364706816aba3e25717850c26c9cd0d89d  platform-tools.zip\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_offline_bundle(&path, None).unwrap().as_deref(),
+            Some(&b"abc"[..])
+        );
+        assert!(
+            read_offline_bundle(
+                &path,
+                Some(&pin("A9993E364706816ABA3E25717850C26C9CD0D89D"))
+            )
+            .unwrap()
+            .is_some()
+        );
+        assert!(
+            read_offline_bundle(
+                &path,
+                Some(&pin("0000000000000000000000000000000000000000"))
+            )
+            .unwrap()
+            .is_none()
+        );
+
+        fs::write(&path, b"abd").unwrap();
+        assert!(read_offline_bundle(&path, None).is_err());
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(&checksum_path).ok();
+    }
+
+    #[test]
+    fn test_parse_content_length() {
+        assert_eq!(parse_content_length(Some("1234"), None), Some(1234));
+        assert_eq!(
+            parse_content_length(Some("1234"), Some("identity")),
+            Some(1234)
+        );
+        assert_eq!(parse_content_length(Some("1234"), Some("gzip")), None);
+        assert_eq!(parse_content_length(None, None), None);
+        assert_eq!(parse_content_length(Some("chunked"), None), None);
+    }
+
+    #[test]
+    fn test_copy_with_progress_unknown_size() {
+        let data = vec![0; 200_000];
+        let progress = RefCell::new(vec![]);
+
+        // A response without Content-Length, then one shorter than the content
+        for maybe_total_size in [None, Some(100_000)] {
+            progress.borrow_mut().clear();
+            copy_with_progress(
+                &mut Cursor::new(&data),
+                vec![],
+                maybe_total_size,
+                &WiredCancelHandle::default(),
+                |copied, total| progress.borrow_mut().push((copied, total)),
+            )
+            .unwrap();
+
+            let progress = progress.borrow();
+            assert!(progress.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert_eq!(progress.last(), Some(&(data.len(), None)));
+        }
+    }
+
+    #[test]
+    fn test_copy_with_progress_cancelled() {
+        let cancel_handle = WiredCancelHandle::default();
+        let data = vec![0; 200_000];
+        let mut copied = vec![];
+
+        let res = copy_with_progress(
+            &mut Cursor::new(&data),
+            &mut copied,
+            Some(data.len()),
+            &cancel_handle.clone(),
+            |_, _| cancel_handle.cancel(),
+        );
+
+        assert!(matches!(
+            res.as_ref().map_err(crate::as_adb_error),
+            Err(Some(AdbError::Cancelled))
+        ));
+        assert!(!copied.is_empty() && copied.len() < data.len());
+    }
+}
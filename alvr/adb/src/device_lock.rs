@@ -0,0 +1,132 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant, SystemTime};
+
+// The owner rewrites the lock file while it sets the device up. A lock that was not refreshed for
+// longer was left by a process that exited or stopped managing the device.
+const LOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+const LOCK_EXPIRY: Duration = Duration::from_secs(10);
+
+/// Lock file telling the other ALVR instances that this process manages a device. It contains the
+/// process id of the owner, connections of the same process share it. The file is removed on drop.
+pub(crate) struct DeviceLock {
+    device_serial: String,
+    path: PathBuf,
+    refresh_time: Instant,
+}
+
+impl DeviceLock {
+    /// Returns None if another process holds the lock
+    pub(crate) fn acquire(dir: &Path, device_serial: &str) -> io::Result<Option<Self>> {
+        let path = dir.join(lock_file_name(device_serial));
+        if is_held_by_other_process(&path)? {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(dir)?;
+        fs::write(&path, process::id().to_string())?;
+
+        Ok(Some(Self {
+            device_serial: device_serial.to_owned(),
+            path,
+            refresh_time: Instant::now(),
+        }))
+    }
+
+    pub(crate) fn device_serial(&self) -> &str {
+        &self.device_serial
+    }
+
+    /// Returns false if another process took the lock over
+    pub(crate) fn refresh(&mut self) -> io::Result<bool> {
+        if self.refresh_time.elapsed() < LOCK_REFRESH_INTERVAL {
+            return Ok(true);
+        }
+        if is_held_by_other_process(&self.path)? {
+            return Ok(false);
+        }
+
+        fs::write(&self.path, process::id().to_string())?;
+        self.refresh_time = Instant::now();
+
+        Ok(true)
+    }
+}
+
+impl Drop for DeviceLock {
+    fn drop(&mut self) {
+        if fs::read_to_string(&self.path)
+            .is_ok_and(|owner| owner.trim() == process::id().to_string())
+        {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+// Serials of network devices contain a colon, which Windows doesn't allow in file names
+fn lock_file_name(device_serial: &str) -> String {
+    let serial = device_serial
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+
+    format!("wired_{serial}.lock")
+}
+
+fn is_held_by_other_process(path: &Path) -> io::Result<bool> {
+    let owner = match fs::read_to_string(path) {
+        Ok(owner) => owner,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if owner.trim() == process::id().to_string() {
+        return Ok(false);
+    }
+
+    let modified = fs::metadata(path)?.modified()?;
+
+    Ok(SystemTime::now()
+        .duration_since(modified)
+        .is_ok_and(|age| age < LOCK_EXPIRY)
+        // Written after now, by a process with another clock or on a file system ahead of it
+        || modified > SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+
+    #[test]
+    fn test_device_lock() {
+        let dir = env::temp_dir().join(format!("alvr_device_lock_test_{}", process::id()));
+        let serial = "192.168.1.20:5555";
+        let path = dir.join(lock_file_name(serial));
+
+        // Connections of the same process share the lock
+        let lock = DeviceLock::acquire(&dir, serial).unwrap().unwrap();
+        assert!(DeviceLock::acquire(&dir, serial).unwrap().is_some());
+        drop(lock);
+        assert!(!path.exists());
+
+        // Held by another instance
+        fs::write(&path, "1").unwrap();
+        assert!(DeviceLock::acquire(&dir, serial).unwrap().is_none());
+
+        // Left by an instance that exited
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - LOCK_EXPIRY * 2)
+            .unwrap();
+        let lock = DeviceLock::acquire(&dir, serial).unwrap();
+        assert!(lock.is_some());
+
+        drop(lock);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,250 @@
+use crate::commands::{self, AdbSource};
+use crate::parse::Device;
+use crate::server_lease::ServerLease;
+use crate::{AdbError, AdbResult, AdbTransport, fail, with_transport};
+use alvr_common::anyhow::anyhow;
+use alvr_common::dbg_connection;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub type DeviceWatcherEventHandler = Box<dyn Fn(DeviceWatcherEvent) + Send>;
+
+/// Changes of the devices matching the filter of a `DeviceWatcher`, found by `poll`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DeviceWatcherEvent {
+    Added(Device),
+    Removed { serial: String },
+}
+
+/// Lists the wired devices without setting anything up: no forwards, installs or launches, e.g. for
+/// the dashboard to show the connected headsets while wired mode is off. `WiredConnection` finds
+/// its device through one.
+pub struct DeviceWatcher {
+    pub(crate) adb_path: String,
+    adb_source: AdbSource,
+    // Only the device with this serial, instead of every device
+    pub(crate) device_filter: Option<String>,
+    transport: Option<Arc<dyn AdbTransport>>,
+    // Every listed device, including the ones the filter excludes
+    devices: Vec<Device>,
+    // Set while the listed devices can be reused instead of listing them again
+    cache_time: Option<Instant>,
+    // Serials of the matching devices found by the last `poll`
+    reported_serials: Vec<String>,
+    event_handler: Option<DeviceWatcherEventHandler>,
+    // Only held by the watchers that are not part of a connection
+    _server_lease: Option<ServerLease>,
+}
+
+impl DeviceWatcher {
+    /// Uses the ADB installed by a previous run or found in PATH. Without one, ADB is downloaded
+    /// only if `allow_download`, otherwise this fails, so that showing the devices doesn't start a
+    /// download behind the user's back.
+    pub fn new(layout: &alvr_filesystem::Layout, allow_download: bool) -> AdbResult<Self> {
+        let (adb_path, adb_source) = match commands::get_adb_path(layout) {
+            Some(adb_path) if Path::new(&adb_path) == layout.local_adb_exe() => {
+                (adb_path, AdbSource::Bundled)
+            }
+            Some(adb_path) => (adb_path, AdbSource::UserProvided),
+            None if allow_download => commands::require_adb(layout, |_, _| ())?,
+            None => return fail(AdbError::Other(anyhow!("ADB is not installed"))),
+        };
+
+        let mut watcher = Self::with_adb_path(adb_path.clone(), adb_source, None);
+        watcher._server_lease = Some(ServerLease::acquire(adb_path));
+
+        Ok(watcher)
+    }
+
+    pub(crate) fn with_adb_path(
+        adb_path: String,
+        adb_source: AdbSource,
+        device_filter: Option<String>,
+    ) -> Self {
+        Self {
+            adb_path,
+            adb_source,
+            device_filter,
+            transport: None,
+            devices: vec![],
+            cache_time: None,
+            reported_serials: vec![],
+            event_handler: None,
+            _server_lease: None,
+        }
+    }
+
+    pub fn adb_source(&self) -> AdbSource {
+        self.adb_source
+    }
+
+    /// Only considers the device with this serial, like `WiredConnectionBuilder::device_filter`.
+    /// The devices it excludes are reported as removed by the next `poll`.
+    pub fn set_device_filter(&mut self, device_serial: Option<String>) {
+        self.device_filter = device_serial;
+    }
+
+    /// Calls `handler` from `poll` for each device that appeared or disappeared. It should not
+    /// block.
+    pub fn set_event_handler(&mut self, handler: impl Fn(DeviceWatcherEvent) + Send + 'static) {
+        self.event_handler = Some(Box::new(handler));
+    }
+
+    /// Runs the commands of `poll` through `transport` instead of the adb executable
+    pub fn set_transport(&mut self, transport: Arc<dyn AdbTransport>) {
+        self.transport = Some(transport);
+    }
+
+    /// Lists the devices again and returns the ones matching the filter
+    pub fn poll(&mut self) -> AdbResult<Vec<Device>> {
+        match self.transport.clone() {
+            Some(transport) => with_transport(transport, || self.list_devices()),
+            None => self.list_devices(),
+        }?;
+
+        Ok(self.devices().cloned().collect())
+    }
+
+    /// Devices matching the filter found by the last `poll`
+    pub fn devices(&self) -> impl Iterator<Item = &Device> {
+        self.devices
+            .iter()
+            .filter(|device| self.matches_filter(device))
+    }
+
+    /// Device a `WiredConnection` with the same filter would set up
+    pub fn selected_device(&self) -> Option<&Device> {
+        self.select_device(&self.devices)
+    }
+
+    pub(crate) fn select_device<'a>(&self, devices: &'a [Device]) -> Option<&'a Device> {
+        devices.iter().find(|d| match &self.device_filter {
+            Some(_) => self.matches_filter(d),
+            None => d
+                .serial
+                .as_ref()
+                .is_some_and(|s| !s.starts_with("127.0.0.1")),
+        })
+    }
+
+    pub(crate) fn matches_filter(&self, device: &Device) -> bool {
+        self.device_filter.is_none() || device.serial == self.device_filter
+    }
+
+    // Reuses the devices listed less than `max_age` ago if they were kept with `keep_devices`
+    pub(crate) fn cached_devices(&mut self, max_age: Duration) -> AdbResult<Vec<Device>> {
+        if self.cache_time.is_none_or(|time| time.elapsed() >= max_age) {
+            self.list_devices()?;
+        }
+
+        Ok(self.devices.clone())
+    }
+
+    pub(crate) fn keep_devices(&mut self) {
+        self.cache_time.get_or_insert_with(Instant::now);
+    }
+
+    pub(crate) fn drop_devices(&mut self) {
+        self.cache_time = None;
+    }
+
+    fn list_devices(&mut self) -> AdbResult<()> {
+        self.devices = commands::list_devices(&self.adb_path)?;
+        self.cache_time = None;
+
+        let serials = self
+            .devices()
+            .filter_map(|device| device.serial.clone())
+            .collect::<Vec<_>>();
+        for serial in &self.reported_serials {
+            if !serials.contains(serial) {
+                self.emit_event(DeviceWatcherEvent::Removed {
+                    serial: serial.clone(),
+                });
+            }
+        }
+        for device in self.devices() {
+            if device
+                .serial
+                .as_ref()
+                .is_some_and(|serial| !self.reported_serials.contains(serial))
+            {
+                self.emit_event(DeviceWatcherEvent::Added(device.clone()));
+            }
+        }
+        self.reported_serials = serials;
+
+        Ok(())
+    }
+
+    fn emit_event(&self, event: DeviceWatcherEvent) {
+        dbg_connection!("device_watcher: {event:?}");
+        if let Some(handler) = &self.event_handler {
+            handler(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockTransport;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_poll_events() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(
+            &["devices"],
+            "List of devices attached\n1WMHH000000000 device usb:1-1 model:Quest_3\n2G0000000000 unauthorized usb:1-2\n",
+        );
+        let mut watcher =
+            DeviceWatcher::with_adb_path("adb".to_owned(), AdbSource::UserProvided, None);
+        watcher.set_transport(Arc::clone(&mock) as Arc<dyn AdbTransport>);
+        let events = Arc::new(Mutex::new(vec![]));
+        watcher.set_event_handler({
+            let events = Arc::clone(&events);
+            move |event| events.lock().unwrap().push(event)
+        });
+
+        let devices = watcher.poll().unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(
+            watcher.selected_device().and_then(|device| device.model()),
+            Some("Quest_3")
+        );
+        assert_eq!(events.lock().unwrap().len(), 2);
+
+        // Nothing changed
+        events.lock().unwrap().clear();
+        watcher.poll().unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        mock.respond(
+            &["devices"],
+            "List of devices attached\n2G0000000000 device usb:1-2\n",
+        );
+        watcher.poll().unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [DeviceWatcherEvent::Removed {
+                serial: "1WMHH000000000".to_owned()
+            }]
+        );
+
+        // The filtered out devices are removed too
+        events.lock().unwrap().clear();
+        watcher.set_device_filter(Some("1WMHH000000000".to_owned()));
+        let devices = watcher.poll().unwrap();
+        assert!(devices.is_empty());
+        assert!(watcher.selected_device().is_none());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [DeviceWatcherEvent::Removed {
+                serial: "2G0000000000".to_owned()
+            }]
+        );
+        assert!(!mock.was_called(&["forward"]));
+    }
+}
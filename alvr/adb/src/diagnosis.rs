@@ -0,0 +1,386 @@
+use crate::metrics::record_metrics;
+use crate::parse::{ActivityState, ConnectionState};
+use crate::{
+    WiredConnection, commands, device_state_message, no_permissions_message, with_transport,
+};
+use alvr_common::anyhow::{Result, bail};
+use alvr_common::semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// The checks which would start later are skipped. A check which started runs to the end, so the
+// run can take one adb command longer.
+const DIAGNOSIS_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiagnosisCheck {
+    Adb,
+    DeviceFound,
+    DeviceAuthorized,
+    PortsFree,
+    ClientInstalled,
+    ClientVersion,
+    ClientRunning,
+    ClientResumed,
+}
+
+impl Display for DiagnosisCheck {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            DiagnosisCheck::Adb => "ADB",
+            DiagnosisCheck::DeviceFound => "Device found",
+            DiagnosisCheck::DeviceAuthorized => "Device authorized",
+            DiagnosisCheck::PortsFree => "Ports free",
+            DiagnosisCheck::ClientInstalled => "Client installed",
+            DiagnosisCheck::ClientVersion => "Client version compatible",
+            DiagnosisCheck::ClientRunning => "Client running",
+            DiagnosisCheck::ClientResumed => "Client resumed",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiagnosisResult {
+    Pass,
+    Fail,
+    // A check it depends on failed, or the run took too long
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosisItem {
+    pub check: DiagnosisCheck,
+    pub result: DiagnosisResult,
+    pub detail: String,
+}
+
+/// Result of `WiredConnection::diagnose`, with one item per check in the order of the setup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosisReport {
+    pub items: Vec<DiagnosisItem>,
+    pub duration: Duration,
+}
+
+impl DiagnosisReport {
+    pub fn passed(&self) -> bool {
+        self.items
+            .iter()
+            .all(|item| item.result == DiagnosisResult::Pass)
+    }
+
+    /// First failed check, the one to fix first
+    pub fn first_failure(&self) -> Option<&DiagnosisItem> {
+        self.items
+            .iter()
+            .find(|item| item.result == DiagnosisResult::Fail)
+    }
+
+    // Returns the value if the check passed, so that the checks depending on it can run
+    fn check<T>(
+        &mut self,
+        check: DiagnosisCheck,
+        enabled: bool,
+        start: Instant,
+        run: impl FnOnce() -> Result<(T, String)>,
+    ) -> Option<T> {
+        let (value, result, detail) = if !enabled {
+            (
+                None,
+                DiagnosisResult::Skip,
+                "A previous check failed".to_owned(),
+            )
+        } else if start.elapsed() >= DIAGNOSIS_TIMEOUT {
+            (
+                None,
+                DiagnosisResult::Skip,
+                format!(
+                    "The diagnosis took longer than {}s",
+                    DIAGNOSIS_TIMEOUT.as_secs()
+                ),
+            )
+        } else {
+            match run() {
+                Ok((value, detail)) => (Some(value), DiagnosisResult::Pass, detail),
+                Err(e) => (None, DiagnosisResult::Fail, format!("{e:#}")),
+            }
+        };
+        self.items.push(DiagnosisItem {
+            check,
+            result,
+            detail,
+        });
+
+        value
+    }
+}
+
+impl Display for DiagnosisReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for item in &self.items {
+            let result = match item.result {
+                DiagnosisResult::Pass => "pass",
+                DiagnosisResult::Fail => "FAIL",
+                DiagnosisResult::Skip => "skipped",
+            };
+            writeln!(f, "{}: {result} ({})", item.check, item.detail)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl WiredConnection {
+    /// Runs the checks of the setup with the parameters of `tick` and explains the first one that
+    /// fails. Unlike the setup, it doesn't forward ports, install, grant or launch anything, and
+    /// it leaves the state of the connection untouched. It takes about `DIAGNOSIS_TIMEOUT` at
+    /// most.
+    pub fn diagnose(&self) -> DiagnosisReport {
+        record_metrics(Arc::clone(&self.metrics), || match self.transport.clone() {
+            Some(transport) => with_transport(transport, || self.run_diagnosis()),
+            None => self.run_diagnosis(),
+        })
+    }
+
+    fn run_diagnosis(&self) -> DiagnosisReport {
+        let start = Instant::now();
+        let mut report = DiagnosisReport::default();
+        let params = &self.setup_params;
+
+        let adb_found = report
+            .check(DiagnosisCheck::Adb, true, start, || {
+                if self.is_downloading_adb() {
+                    bail!("ADB is still being downloaded");
+                }
+                let version = commands::get_adb_version(&self.adb_path)?;
+
+                Ok(((), format!("version {version} at {}", self.adb_path)))
+            })
+            .is_some();
+
+        let device = report.check(DiagnosisCheck::DeviceFound, adb_found, start, || {
+            let devices = commands::list_devices(&self.adb_path)?;
+            let device = self
+                .device_watcher
+                .select_device(&devices)
+                .filter(|device| device.serial.is_some());
+            let Some(device) = device else {
+                match devices
+                    .iter()
+                    .find(|d| d.state == Some(ConnectionState::NoPermissions))
+                {
+                    Some(device) => bail!("{}", no_permissions_message(device)),
+                    None => bail!("No wired devices found"),
+                }
+            };
+            let serial = device.serial.clone().unwrap_or_default();
+            let detail = match device.model() {
+                Some(model) => format!("{model} ({serial})"),
+                None => serial,
+            };
+
+            Ok((device.clone(), detail))
+        });
+
+        let device_serial = report.check(
+            DiagnosisCheck::DeviceAuthorized,
+            device.is_some(),
+            start,
+            || {
+                let device = device.as_ref().unwrap();
+                let serial = device.serial.clone().unwrap_or_default();
+                match device.state {
+                    Some(ConnectionState::Device) => (),
+                    Some(ConnectionState::NoPermissions) => {
+                        bail!("{}", no_permissions_message(device))
+                    }
+                    state => bail!("{}", device_state_message(state)),
+                }
+
+                Ok((serial, "authorized".to_owned()))
+            },
+        );
+        let device_serial = device_serial.as_deref();
+
+        report.check(
+            DiagnosisCheck::PortsFree,
+            device_serial.is_some(),
+            start,
+            || {
+                let device_serial = device_serial.unwrap_or_default();
+                let stream_serial = self
+                    .stream_device_serial
+                    .as_deref()
+                    .filter(|_| self.device_serial.as_deref() == Some(device_serial))
+                    .unwrap_or(device_serial);
+                let forwarded_ports =
+                    commands::list_forwarded_ports(&self.adb_path, stream_serial)?;
+
+                let mut details = vec![];
+                for port in [params.control_port, params.stream_port] {
+                    if forwarded_ports.iter().any(|forward| forward.local == port) {
+                        details.push(format!("{port} forwarded to the device"));
+                    } else if TcpListener::bind((Ipv4Addr::LOCALHOST, port)).is_ok() {
+                        details.push(format!("{port} free"));
+                    } else {
+                        bail!("Port {port} is used by another program");
+                    }
+                }
+
+                Ok(((), details.join(", ")))
+            },
+        );
+
+        let process_name = report.check(
+            DiagnosisCheck::ClientInstalled,
+            device_serial.is_some(),
+            start,
+            || {
+                let device_serial = device_serial.unwrap_or_default();
+                let Some((process_name, _)) = self.find_client(device_serial, &params.client_type)
+                else {
+                    bail!("No suitable ALVR client is installed");
+                };
+
+                Ok((process_name.clone(), process_name))
+            },
+        );
+        let process_name = process_name.as_deref();
+
+        report.check(
+            DiagnosisCheck::ClientVersion,
+            process_name.is_some(),
+            start,
+            || {
+                let device_serial = device_serial.unwrap_or_default();
+                let process_name = process_name.unwrap_or_default();
+                let Some(version) =
+                    commands::get_package_version(&self.adb_path, device_serial, process_name)?
+                else {
+                    bail!("The version of {process_name} is unknown");
+                };
+                let server_version = &*alvr_common::ALVR_VERSION;
+                match Version::parse(&version) {
+                    Ok(client_version) if alvr_common::is_version_compatible(&client_version) => {
+                        Ok(((), format!("{version}, server {server_version}")))
+                    }
+                    Ok(_) => {
+                        bail!("Client {version} is not compatible with server {server_version}")
+                    }
+                    Err(e) => bail!("Invalid client version {version:?}: {e}"),
+                }
+            },
+        );
+
+        let client_running = report
+            .check(
+                DiagnosisCheck::ClientRunning,
+                process_name.is_some(),
+                start,
+                || {
+                    let device_serial = device_serial.unwrap_or_default();
+                    let process_name = process_name.unwrap_or_default();
+                    let Some(process_id) =
+                        commands::get_process_id(&self.adb_path, device_serial, process_name)?
+                    else {
+                        bail!("{process_name} is not running");
+                    };
+
+                    Ok(((), format!("process {process_id}")))
+                },
+            )
+            .is_some();
+
+        report.check(DiagnosisCheck::ClientResumed, client_running, start, || {
+            let device_serial = device_serial.unwrap_or_default();
+            let process_name = process_name.unwrap_or_default();
+            match commands::get_activity_state(&self.adb_path, device_serial, process_name)? {
+                ActivityState::Resumed => Ok(((), "resumed".to_owned())),
+                ActivityState::Visible if params.allow_background_client => {
+                    Ok(((), "visible but not focused, allowed".to_owned()))
+                }
+                ActivityState::Visible => {
+                    bail!("{process_name} is visible but not focused, put on the headset")
+                }
+                ActivityState::Paused => {
+                    bail!("{process_name} is paused, put on the headset")
+                }
+                ActivityState::Unknown => bail!("The activity state of {process_name} is unknown"),
+            }
+        });
+
+        report.duration = start.elapsed();
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::AdbSource;
+    use crate::{AdbTransport, MockTransport};
+
+    fn result(report: &DiagnosisReport, check: DiagnosisCheck) -> Option<DiagnosisResult> {
+        report
+            .items
+            .iter()
+            .find(|item| item.check == check)
+            .map(|item| item.result)
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(
+            &["version"],
+            "Android Debug Bridge version 1.0.41\nVersion 35.0.2-12147458\n",
+        );
+        mock.respond(
+            &["devices"],
+            "List of devices attached\n1WMHH000000000 device usb:1-1 model:Quest_3\n",
+        );
+        mock.respond(&["pm", "list", "package"], "package:alvr.client\n");
+        mock.respond(&["dumpsys", "package"], "    versionName=1.0.0\n");
+        let mut connection = WiredConnection::with_adb_path(
+            "adb".to_owned(),
+            AdbSource::UserProvided,
+            Some("1WMHH000000000".to_owned()),
+        );
+        connection.set_transport(Arc::clone(&mock) as Arc<dyn AdbTransport>);
+        connection.set_process_name_resolver(|_| Some("alvr.client".to_owned()));
+
+        let report = connection.diagnose();
+
+        assert!(!report.passed());
+        assert_eq!(
+            result(&report, DiagnosisCheck::DeviceAuthorized),
+            Some(DiagnosisResult::Pass)
+        );
+        assert_eq!(
+            result(&report, DiagnosisCheck::ClientInstalled),
+            Some(DiagnosisResult::Pass)
+        );
+        assert_eq!(
+            report.first_failure().map(|item| item.check),
+            Some(DiagnosisCheck::ClientVersion)
+        );
+        assert_eq!(
+            result(&report, DiagnosisCheck::ClientRunning),
+            Some(DiagnosisResult::Fail)
+        );
+        assert_eq!(
+            result(&report, DiagnosisCheck::ClientResumed),
+            Some(DiagnosisResult::Skip)
+        );
+        // Read-only
+        assert!(!mock.was_called(&["forward", "tcp:9943"]));
+        assert!(!mock.was_called(&["pm", "grant"]));
+        assert!(!mock.was_called(&["am", "start"]));
+        assert!(connection.device_serial.is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::commands::AdbSource;
+use crate::parse::{ConnectionState, ProcessImportance};
+use crate::{WiredConnection, WiredConnectionStatus, WiredSetupParams, WiredSetupState};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Everything the connection knows about the wired state, to attach to support requests. See
+/// `WiredConnection::diagnostics`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WiredDiagnostics {
+    pub adb_path: String,
+    pub adb_source: AdbSource,
+    // None if `adb version` failed when the connection was created
+    pub adb_version: Option<String>,
+    pub device_serial: Option<String>,
+    // Device the setup keeps while it is listed, see `WiredConnection::select_device`
+    pub pinned_device_serial: Option<String>,
+    pub device_model: Option<String>,
+    // None while the device is missing from the device list
+    pub device_state: Option<ConnectionState>,
+    // Network transport of the device the ports are forwarded over, while the commands use its USB
+    // transport of `device_serial`. None if both use `device_serial`.
+    pub stream_device_serial: Option<String>,
+    // Ports found forwarded to the device, sorted
+    pub forwarded_ports: Vec<u16>,
+    pub client_package: Option<String>,
+    pub client_version: Option<String>,
+    pub client_running: bool,
+    pub client_resumed: bool,
+    // Importance of the client process when it was last found paused or stopped, e.g. to tell if the
+    // headset can kill it. None if it was not running or not queried yet.
+    pub client_importance: Option<ProcessImportance>,
+    pub setup_state: WiredSetupState,
+    pub last_status: Option<WiredConnectionStatus>,
+    pub last_error: Option<WiredErrorRecord>,
+    // Options of the connection. The parameters are the ones used by `tick`.
+    pub setup_params: WiredSetupParams,
+    pub device_filter: Option<String>,
+    pub cleanup_stray_servers: bool,
+    pub device_lost_grace: Duration,
+    pub min_tick_interval: Duration,
+}
+
+/// Last error of `setup`, kept after the next setups succeed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WiredErrorRecord {
+    pub message: String,
+    pub time: SystemTime,
+}
+
+impl WiredConnection {
+    /// Snapshot of the state found by the last setups. It doesn't run adb, so it can be taken after
+    /// every `tick`.
+    pub fn diagnostics(&self) -> WiredDiagnostics {
+        let device = self
+            .listed_device
+            .as_ref()
+            .filter(|device| device.serial.is_some() && device.serial == self.device_serial);
+        let mut forwarded_ports = self
+            .forwards_check
+            .as_ref()
+            .map(|(ports, _)| ports.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        forwarded_ports.sort_unstable();
+
+        WiredDiagnostics {
+            adb_path: self.adb_path.clone(),
+            adb_source: self.adb_source,
+            adb_version: self.adb_version.clone(),
+            device_serial: self.device_serial.clone(),
+            pinned_device_serial: self.pinned_device_serial.clone(),
+            device_model: device.and_then(|device| device.model()).map(str::to_owned),
+            device_state: device.and_then(|device| device.state),
+            stream_device_serial: self.stream_device_serial.clone(),
+            forwarded_ports,
+            client_package: self.client_process_name.clone(),
+            client_version: self.client_version.clone(),
+            client_running: self.client_was_running,
+            client_resumed: self.client_resumed,
+            client_importance: self
+                .client_importance
+                .as_ref()
+                .and_then(|(_, importance)| importance.clone()),
+            setup_state: self.setup_state,
+            last_status: self.last_status.clone(),
+            last_error: self.last_error.clone(),
+            setup_params: self.setup_params.clone(),
+            device_filter: self.device_watcher.device_filter.clone(),
+            cleanup_stray_servers: self.cleanup_stray_servers,
+            device_lost_grace: self.device_lost_grace,
+            min_tick_interval: self.min_tick_interval,
+        }
+    }
+}
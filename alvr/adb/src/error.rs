@@ -0,0 +1,131 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+/// Error of the commands and of `WiredConnection`. The variants tell apart the failures that can
+/// be retried, the ones fixed by reinstalling adb and the ones to report to the user.
+#[derive(Debug)]
+pub enum AdbError {
+    // The adb executable is missing or cannot be run
+    SpawnFailed(io::Error),
+    // The adb server didn't start or stopped answering
+    ServerUnreachable(String),
+    // No device, or the device disconnected while the command ran
+    DeviceNotFound(String),
+    // The device did not accept the debugging key, or the USB device is not accessible
+    DeviceUnauthorized(String),
+    CommandFailed {
+        stderr: String,
+        exit_code: Option<i32>,
+    },
+    // The output of a command is not in the expected format
+    ParseError(String),
+    Timeout(String),
+    Io(io::Error),
+    // The device doesn't let the shell user dump the service, which some enterprise builds do
+    DumpsysRestricted {
+        service: String,
+    },
+    // Aborted through a `WiredCancelHandle`
+    Cancelled,
+    Other(anyhow::Error),
+}
+
+impl Display for AdbError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AdbError::SpawnFailed(e) => write!(f, "Failed to run ADB: {e}"),
+            AdbError::ServerUnreachable(message) => {
+                write!(f, "ADB server is unreachable: {message}")
+            }
+            AdbError::DeviceNotFound(message) => write!(f, "Device not found: {message}"),
+            AdbError::DeviceUnauthorized(message) => write!(f, "Device unauthorized: {message}"),
+            AdbError::CommandFailed {
+                stderr,
+                exit_code: Some(exit_code),
+            } => write!(f, "Command failed with exit code {exit_code}: {stderr}"),
+            AdbError::CommandFailed {
+                stderr,
+                exit_code: None,
+            } => write!(f, "Command failed: {stderr}"),
+            AdbError::ParseError(message) | AdbError::Timeout(message) => write!(f, "{message}"),
+            AdbError::Io(e) => write!(f, "{e}"),
+            AdbError::DumpsysRestricted { service } => {
+                write!(f, "dumpsys {service} is restricted on this device")
+            }
+            AdbError::Cancelled => write!(f, "Cancelled"),
+            AdbError::Other(e) => write!(f, "{e:#}"),
+        }
+    }
+}
+
+impl AdbError {
+    /// Whether the adb executable doesn't exist, e.g. deleted while the connection is running
+    pub fn is_adb_missing(&self) -> bool {
+        matches!(self, AdbError::SpawnFailed(e) if e.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+impl std::error::Error for AdbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AdbError::SpawnFailed(e) | AdbError::Io(e) => Some(e),
+            AdbError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AdbError {
+    fn from(e: io::Error) -> Self {
+        AdbError::Io(e)
+    }
+}
+
+// Lets `?` be used on anyhow results, e.g. with added context. An AdbError wrapped by the context
+// is recovered, without the context.
+impl From<anyhow::Error> for AdbError {
+    fn from(e: anyhow::Error) -> Self {
+        e.downcast::<AdbError>().unwrap_or_else(AdbError::Other)
+    }
+}
+
+#[cfg(not(feature = "anyhow"))]
+pub type AdbResult<T = ()> = Result<T, AdbError>;
+
+// Signatures from before AdbError, kept for one release. The AdbError can be found with
+// `anyhow::Error::downcast_ref`.
+#[cfg(feature = "anyhow")]
+pub type AdbResult<T = ()> = anyhow::Result<T>;
+
+#[cfg(not(feature = "anyhow"))]
+pub(crate) fn as_adb_error(e: &AdbError) -> Option<&AdbError> {
+    Some(e)
+}
+
+#[cfg(feature = "anyhow")]
+pub(crate) fn as_adb_error(e: &anyhow::Error) -> Option<&AdbError> {
+    e.downcast_ref()
+}
+
+// Result with the error type selected by the `anyhow` feature
+#[cfg_attr(not(feature = "anyhow"), expect(clippy::useless_conversion))]
+pub(crate) fn fail<T>(e: AdbError) -> AdbResult<T> {
+    Err(e.into())
+}
+
+pub(crate) trait ParseContext<T> {
+    /// Maps a missing value or a parse error to `AdbError::ParseError`
+    fn parse_context(self, message: impl Into<String>) -> AdbResult<T>;
+}
+
+impl<T> ParseContext<T> for Option<T> {
+    fn parse_context(self, message: impl Into<String>) -> AdbResult<T> {
+        self.map_or_else(|| fail(AdbError::ParseError(message.into())), Ok)
+    }
+}
+
+impl<T, E: Display> ParseContext<T> for Result<T, E> {
+    fn parse_context(self, message: impl Into<String>) -> AdbResult<T> {
+        self.or_else(|e| fail(AdbError::ParseError(format!("{}: {e}", message.into()))))
+    }
+}
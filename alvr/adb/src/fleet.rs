@@ -0,0 +1,151 @@
+use crate::commands::AdbSource;
+use crate::server_lease::ServerLease;
+use crate::{AdbResult, WiredConnection, WiredConnectionStatus, WiredSetupParams, commands};
+use alvr_common::{dbg_connection, info};
+use std::path::PathBuf;
+
+pub struct WiredFleetDeviceStatus {
+    pub device_serial: String,
+    pub active: bool,
+    pub status: AdbResult<WiredConnectionStatus>,
+}
+
+/// Manages the wired connections of several devices, e.g. to drive headsets in rotation at events.
+/// The ports can only be forwarded to one device at a time, so only the active device can stream.
+/// The clients of the other devices are still launched and monitored, so that they are ready when
+/// their turn comes.
+pub struct WiredFleet {
+    adb_path: String,
+    adb_source: AdbSource,
+    adb_version: Option<String>,
+    max_devices: usize,
+    // Directory of the lock files of the devices
+    lock_dir: PathBuf,
+    // Sorted by the time the devices were found, which is the rotation order
+    connections: Vec<WiredConnection>,
+    active_device_serial: Option<String>,
+    // Declared last, the connections need the server to tear down their devices
+    _server_lease: ServerLease,
+}
+
+impl WiredFleet {
+    pub fn new(
+        layout: &alvr_filesystem::Layout,
+        download_progress_callback: impl Fn(usize, Option<usize>),
+        max_devices: usize,
+    ) -> AdbResult<Self> {
+        let (adb_path, adb_source) = commands::require_adb(layout, download_progress_callback)?;
+        let adb_version = commands::get_adb_version(&adb_path).ok();
+        let server_lease = ServerLease::acquire(adb_path.clone());
+
+        Ok(Self {
+            adb_path,
+            adb_source,
+            adb_version,
+            max_devices,
+            lock_dir: layout.config_dir.clone(),
+            connections: Vec::new(),
+            active_device_serial: None,
+            _server_lease: server_lease,
+        })
+    }
+
+    /// Runs `WiredConnection::setup` for every device and returns their statuses in rotation
+    /// order. Devices found after `max_devices` is reached are ignored.
+    pub fn setup(&mut self, params: &WiredSetupParams) -> AdbResult<Vec<WiredFleetDeviceStatus>> {
+        let device_serials = commands::list_devices(&self.adb_path)?
+            .into_iter()
+            .filter_map(|d| d.serial)
+            .filter(|s| !s.starts_with("127.0.0.1"))
+            .collect::<Vec<_>>();
+
+        self.connections.retain(|connection| {
+            let serial = connection.device_watcher.device_filter.as_ref();
+            let found = serial.is_some_and(|s| device_serials.contains(s));
+            if !found {
+                dbg_connection!("wired_fleet: Removing device {serial:?}");
+            }
+
+            found
+        });
+        for serial in device_serials {
+            if self.connections.len() >= self.max_devices {
+                break;
+            }
+
+            if !self
+                .connections
+                .iter()
+                .any(|c| c.device_watcher.device_filter.as_ref() == Some(&serial))
+            {
+                dbg_connection!("wired_fleet: Adding device {serial}");
+                let mut connection = WiredConnection::with_adb_path(
+                    self.adb_path.clone(),
+                    self.adb_source,
+                    Some(serial),
+                );
+                connection.adb_version = self.adb_version.clone();
+                connection.lock_dir = Some(self.lock_dir.clone());
+                self.connections.push(connection);
+            }
+        }
+
+        if !self
+            .connections
+            .iter()
+            .any(|c| c.device_watcher.device_filter == self.active_device_serial)
+        {
+            self.active_device_serial = self
+                .connections
+                .first()
+                .and_then(|c| c.device_watcher.device_filter.clone());
+        }
+
+        Ok(self
+            .connections
+            .iter_mut()
+            .map(|connection| {
+                let active = connection.device_watcher.device_filter == self.active_device_serial;
+                connection.forward_ports = active;
+
+                WiredFleetDeviceStatus {
+                    device_serial: connection
+                        .device_watcher
+                        .device_filter
+                        .clone()
+                        .unwrap_or_default(),
+                    active,
+                    status: connection.setup_with_params(params),
+                }
+            })
+            .collect())
+    }
+
+    pub fn adb_source(&self) -> AdbSource {
+        self.adb_source
+    }
+
+    pub fn active_device_serial(&self) -> Option<&str> {
+        self.active_device_serial.as_deref()
+    }
+
+    /// Makes the next device active, in the order the devices were found. The ports are moved to
+    /// it on the next `setup`.
+    pub fn rotate(&mut self) -> Option<&str> {
+        let active_index = self
+            .connections
+            .iter()
+            .position(|c| c.device_watcher.device_filter == self.active_device_serial);
+        let next_index = active_index.map_or(0, |index| index + 1);
+
+        self.active_device_serial = self
+            .connections
+            .get(next_index % self.connections.len().max(1))
+            .and_then(|c| c.device_watcher.device_filter.clone());
+        if let Some(serial) = &self.active_device_serial {
+            info!("wired_fleet: Device {serial} is now active");
+        }
+
+        self.active_device_serial.as_deref()
+    }
+}
@@ -1,151 +1,3937 @@
+mod adb_download;
+#[cfg(feature = "tokio")]
+mod async_connection;
+mod builder;
+mod device_lock;
+mod device_watcher;
+mod diagnosis;
+mod diagnostics;
+mod error;
+mod fleet;
+mod messages;
+mod metrics;
+mod multi_device;
+mod self_test;
+mod server_lease;
+mod shared_connection;
+mod teardown;
+#[cfg(any(test, feature = "test-support"))]
+mod testing;
+mod timing_state;
+mod transport;
+
 pub mod commands;
-mod parse;
+pub mod parse;
+
+#[cfg(feature = "tokio")]
+pub use async_connection::*;
+pub use builder::*;
+pub use device_watcher::*;
+pub use diagnosis::*;
+pub use diagnostics::*;
+pub use error::*;
+pub use fleet::*;
+pub use messages::*;
+pub use metrics::*;
+pub use self_test::*;
+pub use shared_connection::*;
+pub use teardown::*;
+#[cfg(any(test, feature = "test-support"))]
+pub use testing::*;
+pub use timing_state::*;
+pub use transport::*;
 
-use alvr_common::anyhow::Result;
-use alvr_common::{dbg_connection, error, warn};
-use alvr_session::WiredClientAutoLaunchConfig;
+use crate::adb_download::{AdbDownload, AdbLocation};
+use crate::device_lock::DeviceLock;
+use crate::metrics::{CallMetrics, record_metrics};
+use crate::server_lease::ServerLease;
+
+use crate::commands::{AdbSource, BroadcastExtra};
+use crate::parse::{
+    ActivityState, BatteryState, ConnectionState, CrashKind, CrashReport, Device, DeviceClock,
+    GraphicsInfo, KeyguardState, LaunchError, LaunchResult, MemoryInfo, ProcessImportance,
+};
+use alvr_common::anyhow::{Context, anyhow};
+use alvr_common::{dbg_connection, info, warn};
+use alvr_session::{DeviceSettingNamespace, WiredClientAutoLaunchConfig, WiredDeviceSetting};
 use alvr_system_info::{
-    ClientFlavor, PACKAGE_NAME_GITHUB_DEV, PACKAGE_NAME_GITHUB_STABLE, PACKAGE_NAME_STORE,
+    ACTIVITY_NAME_GITHUB_DEV, ACTIVITY_NAME_GITHUB_STABLE, ACTIVITY_NAME_STORE, ClientFlavor,
+    PACKAGE_NAME_GITHUB_DEV, PACKAGE_NAME_GITHUB_STABLE, PACKAGE_NAME_STORE,
 };
-use std::collections::HashSet;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+const MAX_LAUNCH_DELAY: Duration = Duration::from_secs(120);
+const MIN_LAUNCH_RETRY_DELAY: Duration = Duration::from_secs(1);
+const SCREEN_WAKE_TIMEOUT: Duration = Duration::from_secs(1);
+const SCREEN_WAKE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const RESUMED_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const DEVICE_READY_TIMEOUT: Duration = Duration::from_secs(2);
+const DEVICE_READY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Once a device is usable, the device list and the forwarded ports are queried less often than on
+// every setup. A disconnect makes the other commands fail, which clears the caches.
+const DEVICE_LIST_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+const FORWARDS_CHECK_TICKS: u32 = 10;
+// Checks of a running client that don't need to run on every setup, see `CheckSchedule`. The
+// update time of the package only changes when the client is reinstalled, and a client is only
+// restarted after several seconds of not responding.
+const CLIENT_OUTDATED_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const CLIENT_NOT_RESPONDING_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+// The importance of a paused or stopped client only matters for the diagnostics
+const CLIENT_IMPORTANCE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+// Default of `WiredConnection::set_device_lost_grace`. USB devices vanish for under a second when
+// the headset switches charge modes.
+const DEFAULT_DEVICE_LOST_GRACE: Duration = Duration::from_secs(3);
+// Default of `WiredConnection::set_min_tick_interval`, every setup queries the device
+const DEFAULT_MIN_TICK_INTERVAL: Duration = Duration::ZERO;
+const OUTDATED_PROCESS_TOLERANCE_SECS: i64 = 2;
+// Ports of the default settings
+const DEFAULT_CONTROL_PORT: u16 = 9943;
+const DEFAULT_STREAM_PORT: u16 = 9944;
+// Crashes are looked up right after the client process disappears or a launch failed, which is at
+// most one launch retry delay later
+const MAX_CRASH_AGE_SECS: i64 = MAX_LAUNCH_DELAY.as_secs() as i64;
+const DEVICE_TEARDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+// A client paused again this soon after bringing it to the foreground was left by the user
+const FOREGROUND_GIVE_UP_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum WiredConnectionStatus {
+    Ready(WiredSetupTimings),
+    // The message is meant for users and its wording can change, match on the kind instead
+    NotReady {
+        kind: WiredStatusKind,
+        message: WiredMessage,
+    },
+}
+
+impl WiredConnectionStatus {
+    fn not_ready(kind: WiredStatusKind, message: WiredMessage) -> Self {
+        Self::NotReady { kind, message }
+    }
+
+    // Whether both are the same state, ignoring the counters and remaining times of the kind
+    fn is_same_state(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Ready(_), Self::Ready(_)) => true,
+            (
+                Self::NotReady { kind, .. },
+                Self::NotReady {
+                    kind: other_kind, ..
+                },
+            ) => mem::discriminant(kind) == mem::discriminant(other_kind),
+            _ => false,
+        }
+    }
+}
+
+impl Display for WiredConnectionStatus {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WiredConnectionStatus::Ready(_) => write!(f, "Ready"),
+            WiredConnectionStatus::NotReady { message, .. } => write!(f, "{message}"),
+        }
+    }
+}
+
+/// Why the wired connection is not ready
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WiredStatusKind {
+    NoDevice,
+    Reconnecting {
+        attempt: u32,
+    },
+    // A USB device disappeared from adb less than the grace period ago
+    DeviceReconnecting,
+    // Another ALVR instance of this host holds the lock of the device
+    DeviceManagedElsewhere,
+    // The udev rules don't give access to the USB device (Linux only)
+    NoPermissions,
+    Unauthorized,
+    // Offline, connecting, or in recovery or bootloader mode
+    DeviceUnavailable,
+    Booting,
+    ClientNotInstalled,
+    // Auto-launch is disabled
+    ClientStopped,
+    ClientDisabled,
+    AwaitingLaunchDelay {
+        remaining_secs: u64,
+    },
+    // The headset must be woken up or unlocked before launching
+    AwaitingUser,
+    Launching,
+    // `am` reported an error, the launch is retried
+    LaunchFailed,
+    LaunchGaveUp {
+        attempts: u32,
+    },
+    ClientOutdated,
+    ClientPaused,
+    HeadsetAsleep,
+    ClientNotResponding,
+    // ADB is installed in the background, the total is None if the server didn't tell the size
+    DownloadingAdb {
+        downloaded: usize,
+        total: Option<usize>,
+    },
+    // The adb executable disappeared and installing it again failed. The next setup tries again.
+    AdbMissing,
+    // Another operation of a `SharedWiredConnection` is running, e.g. an install
+    Busy,
+}
+
+/// Phases of the wired setup, in the order `setup` goes through them. `setup` runs the phases until
+/// one is not complete, which becomes the current state. A state compares greater than the states
+/// of the phases before it, so it can be rendered as a progress indicator.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum WiredSetupState {
+    NoDevice,
+    // Not authorized, without USB permissions or in another state than "device"
+    DeviceUnauthorized,
+    Booting,
+    ForwardingPorts,
+    // Looking for an installed client
+    VerifyingClient,
+    // The client is not running and auto-launch is disabled
+    ClientStopped,
+    // Boot and pre-launch delays
+    AwaitingLaunchDelay,
+    Launching,
+    LaunchFailed,
+    // The client is running but paused, outdated or not responding
+    AwaitingResume,
+    Ready,
+}
+
+/// Transitions of the wired connection, passed to the handler set with
+/// `WiredConnection::set_event_handler`. Each one is emitted once when it happens, not on every
+/// `setup`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WiredEvent {
+    DeviceConnected {
+        serial: String,
+        model: Option<String>,
+    },
+    DeviceLost {
+        serial: String,
+    },
+    // The client was found on the device, it can have been installed before the device was
+    ClientInstalled {
+        package: String,
+        version: Option<String>,
+    },
+    ClientLaunched {
+        package: String,
+    },
     Ready,
-    NotReady(String),
+    // `setup` failed. Repeated failures with the same message are only emitted once.
+    Error {
+        message: String,
+    },
+}
+
+impl Display for WiredEvent {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WiredEvent::DeviceConnected {
+                serial,
+                model: Some(model),
+            } => write!(f, "Wired headset {model} ({serial}) connected"),
+            WiredEvent::DeviceConnected {
+                serial,
+                model: None,
+            } => write!(f, "Wired headset {serial} connected"),
+            WiredEvent::DeviceLost { serial } => write!(f, "Wired headset {serial} disconnected"),
+            WiredEvent::ClientInstalled {
+                package,
+                version: Some(version),
+            } => write!(f, "Found ALVR client {package} {version}"),
+            WiredEvent::ClientInstalled {
+                package,
+                version: None,
+            } => write!(f, "Found ALVR client {package}"),
+            WiredEvent::ClientLaunched { package } => write!(f, "Launched ALVR client {package}"),
+            WiredEvent::Ready => write!(f, "Wired connection ready"),
+            WiredEvent::Error { message } => write!(f, "Wired connection failed: {message}"),
+        }
+    }
+}
+
+/// Time from when the device was found to each phase of the setup, recorded the first time the
+/// phase is reached. Phases that were not needed stay None, e.g. the launch of a client that was
+/// already running.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct WiredSetupTimings {
+    pub ports_forwarded: Option<Duration>,
+    pub client_found: Option<Duration>,
+    pub launch_issued: Option<Duration>,
+    pub client_resumed: Option<Duration>,
+    pub ready: Option<Duration>,
+}
+
+// Actions for the client to receive while it is running, without restarting it. The client has no
+// receiver for them yet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClientCommand {
+    Reconnect,
+    RotateLogs,
+    SetDebugOverlay(bool),
+}
+
+/// Aborts the downloads, file transfers, install sessions and logcat streams it was passed to, and
+/// the setup of the connection it was taken from. They fail with `AdbError::Cancelled`. Clones
+/// share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct WiredCancelHandle {
+    cancelled: Arc<AtomicBool>,
+    // Set for the handles created by `child`
+    parent: Option<Arc<AtomicBool>>,
+}
+
+impl WiredCancelHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.load(Ordering::Relaxed))
+    }
+
+    // Handle cancelled with this one, which can also be cancelled without cancelling this one
+    #[cfg(feature = "tokio")]
+    pub(crate) fn child(&self) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            parent: Some(Arc::clone(&self.cancelled)),
+        }
+    }
+
+    // Fails once cancelled, for the loops of the long operations
+    pub(crate) fn check(&self) -> AdbResult<()> {
+        if self.is_cancelled() {
+            fail(AdbError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WiredSetupParams {
+    pub control_port: u16,
+    pub stream_port: u16,
+    pub client_type: ClientFlavor,
+    pub allow_background_client: bool,
+    pub keep_display_awake: bool,
+    pub client_autolaunch: Option<WiredClientAutoLaunchConfig>,
+    pub device_settings: Vec<WiredDeviceSetting>,
+    // Shell command lines run on the device before each launch of the client, e.g.
+    // "setprop debug.example 1"
+    pub pre_launch_shell: Vec<String>,
+    // Runtime permissions granted to the client when it is found, e.g.
+    // "android.permission.RECORD_AUDIO"
+    pub client_permissions: Vec<String>,
+}
+
+impl Default for WiredSetupParams {
+    fn default() -> Self {
+        Self {
+            control_port: DEFAULT_CONTROL_PORT,
+            stream_port: DEFAULT_STREAM_PORT,
+            client_type: if alvr_common::is_stable() {
+                ClientFlavor::Store
+            } else {
+                ClientFlavor::Github
+            },
+            allow_background_client: false,
+            keep_display_awake: false,
+            client_autolaunch: None,
+            device_settings: vec![],
+            pre_launch_shell: vec![],
+            client_permissions: vec![],
+        }
+    }
+}
+
+/// Builds the parameters from the defaults of the other options, e.g.
+/// `WiredSetupParams::new(9943, 9944).client_type(flavor).autolaunch(Some(config))`
+impl WiredSetupParams {
+    pub fn new(control_port: u16, stream_port: u16) -> Self {
+        Self {
+            control_port,
+            stream_port,
+            ..Default::default()
+        }
+    }
+
+    pub fn client_type(mut self, client_type: ClientFlavor) -> Self {
+        self.client_type = client_type;
+        self
+    }
+
+    pub fn allow_background_client(mut self, allow: bool) -> Self {
+        self.allow_background_client = allow;
+        self
+    }
+
+    pub fn keep_display_awake(mut self, keep_awake: bool) -> Self {
+        self.keep_display_awake = keep_awake;
+        self
+    }
+
+    /// None disables auto-launch
+    pub fn autolaunch(mut self, config: Option<WiredClientAutoLaunchConfig>) -> Self {
+        self.client_autolaunch = config;
+        self
+    }
+
+    pub fn device_settings(mut self, settings: Vec<WiredDeviceSetting>) -> Self {
+        self.device_settings = settings;
+        self
+    }
+
+    pub fn pre_launch_shell(mut self, command_lines: Vec<String>) -> Self {
+        self.pre_launch_shell = command_lines;
+        self
+    }
+
+    pub fn client_permissions(mut self, permissions: Vec<String>) -> Self {
+        self.client_permissions = permissions;
+        self
+    }
+}
+
+// Memory and frame time stats of the client, either can be missing if the device does not report
+// them
+#[derive(Debug, Clone)]
+pub struct ClientPerformanceSample {
+    pub memory: Option<MemoryInfo>,
+    pub graphics: Option<GraphicsInfo>,
+}
+
+// A device setting changed by the connection
+#[derive(Clone)]
+struct AppliedDeviceSetting {
+    setting: WiredDeviceSetting,
+    // None if the setting did not exist before
+    original_value: Option<String>,
+    // Failed settings are kept so that they are only reported once. There is nothing to restore.
+    failed: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum ScheduledCheck {
+    ClientOutdated,
+    ClientNotResponding,
+}
+
+// Last run of the checks that have their own interval. Checks that were never run are due.
+#[derive(Default)]
+struct CheckSchedule(HashMap<ScheduledCheck, Instant>);
+
+impl CheckSchedule {
+    // Marks the check as run if it is due
+    fn run_if_due(&mut self, check: ScheduledCheck, interval: Duration) -> bool {
+        let due = self
+            .0
+            .get(&check)
+            .is_none_or(|last_run| last_run.elapsed() >= interval);
+        if due {
+            self.0.insert(check, Instant::now());
+        }
+
+        due
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
 }
 
+/// Picks the client among the installed candidate packages, in the order of preference of the
+/// client flavor. Returns the package or a "<package>/<activity>" component.
+pub type ProcessNameResolver = Box<dyn Fn(&[&str]) -> Option<String> + Send>;
+
+pub type WiredEventHandler = Box<dyn Fn(WiredEvent) + Send>;
+
 pub struct WiredConnection {
     adb_path: String,
+    adb_source: AdbSource,
+    // Queried once when the connection is created
+    adb_version: Option<String>,
+    // Set until ADB is installed, if it is installed in the background
+    adb_download: Option<AdbDownload>,
+    // None if the connection was given an adb path, which can't be installed again
+    adb_location: Option<AdbLocation>,
+    cancel_handle: WiredCancelHandle,
+    // Lists the devices. Only the device of its filter is considered if set, otherwise the first
+    // wired device is used.
+    device_watcher: DeviceWatcher,
+    // Device kept while it is listed, so that a device connected later doesn't take over. Set once
+    // the setup is ready, or by `select_device`.
+    pinned_device_serial: Option<String>,
+    forward_ports: bool,
+    // Set unless the connection is part of a fleet, which holds the lease instead
+    server_lease: Option<ServerLease>,
+    cleanup_stray_servers: bool,
+    device_serial: Option<String>,
+    // Entry of the device in the last device list, for `diagnostics`
+    listed_device: Option<Device>,
+    // Network transport the ports are forwarded over while the commands use the USB transport of
+    // the same device
+    stream_device_serial: Option<String>,
+    // android_id of the listed devices, None if they have none
+    android_ids: HashMap<String, Option<String>>,
+    device_found_time: Instant,
+    // Applied when the setup finds its device, see `restore_timing_state`
+    restored_timing_state: Option<(WiredTimingState, Instant)>,
+    // When the device disappeared, while its state is kept for `device_lost_grace`
+    device_missing_since: Option<Instant>,
+    device_lost_grace: Duration,
+    // Directory of the lock files of the devices, unset for connections created without a layout
+    lock_dir: Option<PathBuf>,
+    device_lock: Option<DeviceLock>,
+    setup_state: WiredSetupState,
+    setup_timings: WiredSetupTimings,
+    // Whether `adb get-state` agreed with the device list, checked once per device
+    device_ready: bool,
+    // Only queried until the device finished booting
+    device_booted: bool,
+    // The active OpenXR runtime is checked once per device
+    openxr_runtime_checked: bool,
+    // dumpsys services the device refused to dump, only reported once
+    restricted_dumpsys_services: HashSet<String>,
+    launch_time: Option<Instant>,
+    launch_attempts: u32,
+    // Why `am` failed to start the client on the last attempt
+    launch_failure: Option<WiredMessage>,
+    network_device_serial: Option<String>,
+    reconnect_attempts: u32,
+    client_resumed: bool,
+    client_in_background: bool,
+    client_not_responding_since: Option<Instant>,
+    // Result of the last check, reused until the next one is due
+    client_outdated: bool,
+    check_schedule: CheckSchedule,
+    client_foreground_time: Option<Instant>,
+    client_foreground_given_up: bool,
+    // Importance of the client process the last time it was found paused or stopped, and when it
+    // was queried
+    client_importance: Option<(Instant, Option<ProcessImportance>)>,
+    process_name_resolver: Option<ProcessNameResolver>,
+    event_handler: Option<WiredEventHandler>,
+    // Message of the last `setup` error, so that it is emitted once
+    last_setup_error: Option<String>,
+    // Unlike `last_setup_error`, kept once a setup succeeds
+    last_error: Option<WiredErrorRecord>,
+    last_status: Option<WiredConnectionStatus>,
+    status_entered_time: Instant,
+    // Time and status of the last successful setup, returned again until `min_tick_interval`
+    last_setup: Option<(Instant, WiredConnectionStatus)>,
+    min_tick_interval: Duration,
+    // Used by `tick`
+    setup_params: WiredSetupParams,
+    // Replaces the adb executable during `setup` and `teardown` if set
+    transport: Option<Arc<dyn AdbTransport>>,
+    // Caches of the setup phases that rarely change, see `force_refresh`. The watcher caches the
+    // device list.
+    // Ports found forwarded and the number of setups since they were listed
+    forwards_check: Option<(HashSet<u16>, u32)>,
+    // Ports forwarded by this connection, removed by `teardown`
+    forwarded_ports: HashSet<u16>,
+    // Packages the installed client was looked up among
+    client_candidates: Vec<String>,
+    client_process_name: Option<String>,
+    client_version: Option<String>,
+    // Activity chosen by the resolver, otherwise the one of the flavor is used
+    client_activity_name: Option<String>,
+    client_was_running: bool,
+    // Whether the client was started by this connection. Clients started by the user are left
+    // running on disconnect.
+    client_launched: bool,
+    client_crash: Option<CrashReport>,
+    crash_checked_launch_attempts: u32,
+    // Process ID and start time of the client in the device time zone
+    client_start_time: Option<(usize, i64)>,
+    stop_client_on_disconnect: bool,
+    launch_display_id: Option<u32>,
+    // Value of stay_on_while_plugged_in before the display was kept awake
+    stay_on_restore_value: Option<u32>,
+    device_settings: Vec<WiredDeviceSetting>,
+    applied_device_settings: Vec<AppliedDeviceSetting>,
+    pre_launch_shell: Vec<String>,
+    client_permissions: Vec<String>,
+    last_performance_sample_time: Option<Instant>,
+    // adb calls of `setup` and `teardown`, see `take_metrics`
+    metrics: Arc<Mutex<CallMetrics>>,
+    // Connections of the devices other than this one, driven by `setup_all`
+    other_devices: HashMap<String, WiredConnection>,
+    // Set by `teardown`, so that dropping the connection does not clean up again
+    torn_down: bool,
 }
 
 impl WiredConnection {
+    /// `adb_dir` replaces the executables directory of the layout as the location where ADB is
+    /// downloaded, e.g. for portable installs in a read-only location. It must be writable.
+    /// `adb_env` is set for every adb command, see `commands::set_adb_env`.
     pub fn new(
         layout: &alvr_filesystem::Layout,
+        adb_dir: Option<&Path>,
+        adb_env: &HashMap<String, String>,
+        download_progress_callback: impl Fn(usize, Option<usize>),
+    ) -> AdbResult<Self> {
+        Self::new_cancellable(
+            layout,
+            adb_dir,
+            adb_env,
+            WiredCancelHandle::default(),
+            download_progress_callback,
+        )
+    }
+
+    /// Like `new`, `cancel_handle` can abort the download of ADB and becomes the handle of the
+    /// connection.
+    pub fn new_cancellable(
+        layout: &alvr_filesystem::Layout,
+        adb_dir: Option<&Path>,
+        adb_env: &HashMap<String, String>,
+        cancel_handle: WiredCancelHandle,
         download_progress_callback: impl Fn(usize, Option<usize>),
-    ) -> Result<Self> {
-        let adb_path = commands::require_adb(layout, download_progress_callback)?;
+    ) -> AdbResult<Self> {
+        let location = AdbLocation {
+            layout: layout.clone(),
+            adb_dir: adb_dir.map(Path::to_owned),
+            adb_env: adb_env.clone(),
+        };
+        let (adb_path, adb_source) =
+            adb_download::require_adb_in(&location, &cancel_handle, download_progress_callback)?;
+
+        let adb_version = commands::get_adb_version(&adb_path).ok();
+        let mut connection = Self::with_adb_path(adb_path, adb_source, None);
+        connection.adb_version = adb_version;
+        connection.adb_location = Some(location);
+        connection.cancel_handle = cancel_handle;
+        connection.lock_dir = Some(layout.config_dir.clone());
+
+        Ok(connection)
+    }
+
+    fn with_adb_path(
+        adb_path: String,
+        adb_source: AdbSource,
+        pinned_device_serial: Option<String>,
+    ) -> Self {
+        // ADB installed in the background has no path yet, the lease is acquired once installed
+        let server_lease = (pinned_device_serial.is_none() && !adb_path.is_empty())
+            .then(|| ServerLease::acquire(adb_path.clone()));
+        let device_watcher =
+            DeviceWatcher::with_adb_path(adb_path.clone(), adb_source, pinned_device_serial);
+
+        Self {
+            adb_path,
+            adb_source,
+            adb_version: None,
+            adb_download: None,
+            adb_location: None,
+            cancel_handle: WiredCancelHandle::default(),
+            server_lease,
+            device_watcher,
+            pinned_device_serial: None,
+            forward_ports: true,
+            cleanup_stray_servers: false,
+            device_serial: None,
+            listed_device: None,
+            stream_device_serial: None,
+            android_ids: HashMap::new(),
+            device_found_time: Instant::now(),
+            restored_timing_state: None,
+            device_missing_since: None,
+            device_lost_grace: DEFAULT_DEVICE_LOST_GRACE,
+            lock_dir: None,
+            device_lock: None,
+            setup_state: WiredSetupState::NoDevice,
+            setup_timings: WiredSetupTimings::default(),
+            device_ready: false,
+            device_booted: false,
+            openxr_runtime_checked: false,
+            restricted_dumpsys_services: HashSet::new(),
+            launch_time: None,
+            launch_attempts: 0,
+            launch_failure: None,
+            network_device_serial: None,
+            reconnect_attempts: 0,
+            client_resumed: false,
+            client_in_background: false,
+            client_not_responding_since: None,
+            client_outdated: false,
+            check_schedule: CheckSchedule::default(),
+            client_foreground_time: None,
+            client_foreground_given_up: false,
+            client_importance: None,
+            process_name_resolver: None,
+            event_handler: None,
+            last_setup_error: None,
+            last_error: None,
+            last_status: None,
+            status_entered_time: Instant::now(),
+            setup_params: WiredSetupParams::default(),
+            transport: None,
+            forwards_check: None,
+            forwarded_ports: HashSet::new(),
+            client_candidates: vec![],
+            client_process_name: None,
+            client_version: None,
+            client_activity_name: None,
+            client_was_running: false,
+            client_launched: false,
+            client_crash: None,
+            crash_checked_launch_attempts: 0,
+            client_start_time: None,
+            stop_client_on_disconnect: false,
+            launch_display_id: None,
+            stay_on_restore_value: None,
+            device_settings: vec![],
+            applied_device_settings: vec![],
+            pre_launch_shell: vec![],
+            client_permissions: vec![],
+            last_performance_sample_time: None,
+            metrics: Arc::new(Mutex::new(CallMetrics::new())),
+            other_devices: HashMap::new(),
+            last_setup: None,
+            min_tick_interval: DEFAULT_MIN_TICK_INTERVAL,
+            torn_down: false,
+        }
+    }
+
+    // Whether the adb used by this connection was downloaded while creating it. Downloaded until
+    // ADB installed in the background is ready.
+    pub fn adb_source(&self) -> AdbSource {
+        self.adb_source
+    }
+
+    pub fn builder() -> WiredConnectionBuilder {
+        WiredConnectionBuilder::new()
+    }
+
+    /// Runs the setup with the parameters of the builder or of the last `set_setup_params`.
+    pub fn tick(&mut self) -> AdbResult<WiredConnectionStatus> {
+        let params = self.setup_params.clone();
+
+        self.setup_with_params(&params)
+    }
+
+    pub fn setup_params(&self) -> &WiredSetupParams {
+        &self.setup_params
+    }
+
+    /// Sets up the device with this serial from the next setup on instead of the pinned one, e.g.
+    /// picked by the user. Like the device pinned once the setup is ready, it is kept while it is
+    /// listed, even if adb lists another device first. It must match the device filter.
+    pub fn select_device(&mut self, serial: impl Into<String>) -> AdbResult<()> {
+        let serial = serial.into();
+        if self
+            .device_watcher
+            .device_filter
+            .as_ref()
+            .is_some_and(|filter| *filter != serial)
+        {
+            return fail(AdbError::Other(anyhow!(
+                "{serial} is excluded by the device filter"
+            )));
+        }
+
+        info!("wired_connection: Selected {serial}");
+        self.pinned_device_serial = Some(serial);
+        self.force_refresh();
+
+        Ok(())
+    }
+
+    /// Serial of the device the setup keeps, see `select_device`
+    pub fn pinned_device_serial(&self) -> Option<&str> {
+        self.pinned_device_serial.as_deref()
+    }
 
-        Ok(Self { adb_path })
+    /// Replaces the parameters used by `tick`, e.g. when the settings changed
+    pub fn set_setup_params(&mut self, params: WiredSetupParams) {
+        self.setup_params = params;
     }
 
+    /// Signature from before `WiredSetupParams`, kept for one release. It ignores the parameters
+    /// set on the connection.
+    #[deprecated(note = "use `setup_with_params` or `tick`")]
     pub fn setup(
-        &self,
+        &mut self,
         control_port: u16,
         stream_port: u16,
         client_type: &ClientFlavor,
+        allow_background_client: bool,
+        keep_display_awake: bool,
         client_autolaunch: Option<WiredClientAutoLaunchConfig>,
-    ) -> Result<WiredConnectionStatus> {
-        let Some(device_serial) = commands::list_devices(&self.adb_path)?
-            .into_iter()
-            .filter_map(|d| d.serial)
-            .find(|s| !s.starts_with("127.0.0.1"))
-        else {
-            return Ok(WiredConnectionStatus::NotReady(
-                "No wired devices found".to_owned(),
-            ));
+    ) -> AdbResult<WiredConnectionStatus> {
+        self.run_setup(
+            control_port,
+            stream_port,
+            client_type,
+            allow_background_client,
+            keep_display_awake,
+            client_autolaunch,
+        )
+    }
+
+    // Runs the setup with the parameters that are not stored on the connection
+    fn run_setup(
+        &mut self,
+        control_port: u16,
+        stream_port: u16,
+        client_type: &ClientFlavor,
+        allow_background_client: bool,
+        keep_display_awake: bool,
+        client_autolaunch: Option<WiredClientAutoLaunchConfig>,
+    ) -> AdbResult<WiredConnectionStatus> {
+        if let Some((time, status)) = &self.last_setup
+            && time.elapsed() < self.min_tick_interval
+        {
+            return Ok(status.clone());
+        }
+
+        let was_ready = self.setup_state == WiredSetupState::Ready;
+
+        let mut res = self.setup_with_transport(
+            control_port,
+            stream_port,
+            client_type,
+            allow_background_client,
+            keep_display_awake,
+            client_autolaunch.clone(),
+        );
+        // The setup runs again once with ADB installed again, a failure is a status instead of an
+        // error so that it isn't reported by every setup
+        if let Err(e) = &res
+            && as_adb_error(e).is_some_and(AdbError::is_adb_missing)
+        {
+            res = match self.reinstall_adb() {
+                Ok(()) => self.setup_with_transport(
+                    control_port,
+                    stream_port,
+                    client_type,
+                    allow_background_client,
+                    keep_display_awake,
+                    client_autolaunch,
+                ),
+                Err(e) => {
+                    warn!("wired_connection: Installing ADB again failed with {e:#}");
+
+                    Ok(adb_download::adb_missing_status(format!("{e:#}")))
+                }
+            };
+        }
+
+        match &res {
+            Ok(status) => {
+                if !self
+                    .last_status
+                    .as_ref()
+                    .is_some_and(|last_status| last_status.is_same_state(status))
+                {
+                    self.status_entered_time = Instant::now();
+                }
+                self.last_status = Some(status.clone());
+                self.last_setup = Some((Instant::now(), status.clone()));
+                self.last_setup_error = None;
+                if !was_ready && self.setup_state == WiredSetupState::Ready {
+                    self.emit_event(WiredEvent::Ready);
+                }
+                if self.setup_state == WiredSetupState::Ready && self.pinned_device_serial.is_none()
+                {
+                    // The serial that was selected, not the one of its USB transport
+                    self.pinned_device_serial = self
+                        .stream_device_serial
+                        .clone()
+                        .or_else(|| self.device_serial.clone());
+                }
+            }
+            // Cancelling is how the caller shuts the connection down, it is not an error
+            Err(e) if matches!(as_adb_error(e), Some(AdbError::Cancelled)) => {
+                self.last_setup = None
+            }
+            Err(e) => {
+                self.last_setup = None;
+                self.force_refresh();
+
+                let message = format!("{e:#}");
+                self.last_error = Some(WiredErrorRecord {
+                    message: message.clone(),
+                    time: SystemTime::now(),
+                });
+                if self.last_setup_error.as_ref() != Some(&message) {
+                    self.emit_event(WiredEvent::Error {
+                        message: message.clone(),
+                    });
+                    self.last_setup_error = Some(message);
+                }
+            }
+        }
+
+        res
+    }
+
+    fn setup_with_transport(
+        &mut self,
+        control_port: u16,
+        stream_port: u16,
+        client_type: &ClientFlavor,
+        allow_background_client: bool,
+        keep_display_awake: bool,
+        client_autolaunch: Option<WiredClientAutoLaunchConfig>,
+    ) -> AdbResult<WiredConnectionStatus> {
+        let transport = self.transport.clone();
+        let metrics = Arc::clone(&self.metrics);
+        let try_setup = || {
+            self.try_setup(
+                control_port,
+                stream_port,
+                client_type,
+                allow_background_client,
+                keep_display_awake,
+                client_autolaunch,
+            )
         };
 
-        let ports = HashSet::from([control_port, stream_port]);
-        let forwarded_ports: HashSet<u16> =
-            commands::list_forwarded_ports(&self.adb_path, &device_serial)?
-                .into_iter()
-                .map(|f| f.local)
-                .collect();
-        let missing_ports = ports.difference(&forwarded_ports);
-        for port in missing_ports {
-            commands::forward_port(&self.adb_path, &device_serial, *port)?;
-            dbg_connection!(
-                "setup_wired_connection: Forwarded port {port} of device {device_serial}"
-            );
+        record_metrics(metrics, || match transport {
+            Some(transport) => with_transport(transport, try_setup),
+            None => try_setup(),
+        })
+    }
+
+    fn try_setup(
+        &mut self,
+        control_port: u16,
+        stream_port: u16,
+        client_type: &ClientFlavor,
+        allow_background_client: bool,
+        keep_display_awake: bool,
+        client_autolaunch: Option<WiredClientAutoLaunchConfig>,
+    ) -> AdbResult<WiredConnectionStatus> {
+        self.cancel_handle.check()?;
+
+        if let Some(status) = self.poll_adb_download()? {
+            return Ok(status);
         }
 
-        let Some(process_name) = get_process_name(&self.adb_path, &device_serial, client_type)
-        else {
-            return Ok(WiredConnectionStatus::NotReady(
-                "No suitable ALVR client is installed".to_owned(),
-            ));
-        };
-
-        if commands::get_process_id(&self.adb_path, &device_serial, &process_name)?.is_none() {
-            if let Some(client_autolaunch) = client_autolaunch {
-                if client_autolaunch.boot_delay > 0 {
-                    match commands::get_uptime(&self.adb_path, &device_serial) {
-                        Ok(uptime) => {
-                            if uptime < Duration::from_secs(client_autolaunch.boot_delay.into()) {
-                                return Ok(WiredConnectionStatus::NotReady(
-                                    "Waiting for device boot".to_owned(),
-                                ));
-                            }
-                        }
-                        Err(failure) => {
-                            warn!("wired_connection: get_uptime failed with {}", failure);
-                        }
+        self.stop_client_on_disconnect = client_autolaunch
+            .as_ref()
+            .is_some_and(|c| c.stop_client_on_disconnect);
+        self.launch_display_id = client_autolaunch
+            .as_ref()
+            .and_then(|c| c.launch_display.as_option().copied());
+
+        let mut tick = SetupTick {
+            control_port,
+            stream_port,
+            client_type,
+            allow_background_client,
+            keep_display_awake,
+            client_autolaunch,
+            client_not_responding_since: self.client_not_responding_since.take(),
+            device: None,
+            device_serial: String::new(),
+            device_state: None,
+            process_name: String::new(),
+            client_process_id: None,
+            launch_step: None,
+        };
+
+        // Every setup starts from the device lookup. The phases that are already complete only
+        // check that they still are, so a single setup can go from any phase to ready.
+        let mut state = WiredSetupState::NoDevice;
+        loop {
+            match self.run_setup_phase(state, &mut tick)? {
+                SetupTransition::Next(next) => {
+                    debug_assert!(next > state, "Setup went back from {state:?} to {next:?}");
+                    // A cancelled setup stops at the next phase instead of running the remaining
+                    // ones
+                    self.cancel_handle.check()?;
+                    self.enter_setup_state(next);
+                    state = next;
+                }
+                // A crash of the last run explains why the client is not running
+                SetupTransition::Stop(status)
+                    if (WiredSetupState::ClientStopped..=WiredSetupState::LaunchFailed)
+                        .contains(&state) =>
+                {
+                    return Ok(self.with_crash_report(status));
+                }
+                SetupTransition::Stop(status) => return Ok(status),
+            }
+        }
+    }
+
+    // Runs the handler of a setup phase. A complete phase names the phase that follows it, which
+    // is always a later one.
+    fn run_setup_phase(
+        &mut self,
+        state: WiredSetupState,
+        tick: &mut SetupTick,
+    ) -> AdbResult<SetupTransition> {
+        match state {
+            WiredSetupState::NoDevice => self.setup_no_device(tick),
+            WiredSetupState::DeviceUnauthorized => self.setup_device_unauthorized(tick),
+            WiredSetupState::Booting => self.setup_booting(tick),
+            WiredSetupState::ForwardingPorts => self.setup_forwarding_ports(tick),
+            WiredSetupState::VerifyingClient => self.setup_verifying_client(tick),
+            WiredSetupState::ClientStopped => self.setup_client_stopped(tick),
+            WiredSetupState::AwaitingLaunchDelay => self.setup_awaiting_launch_delay(tick),
+            WiredSetupState::Launching => self.setup_launching(tick),
+            WiredSetupState::LaunchFailed => Ok(self.setup_launch_failed()),
+            WiredSetupState::AwaitingResume => self.setup_awaiting_resume(tick),
+            WiredSetupState::Ready => self.setup_ready(tick),
+        }
+    }
+
+    // Selects the device, or keeps the phases it reached if it disappeared for a short while
+    fn setup_no_device(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        let devices = self
+            .device_watcher
+            .cached_devices(DEVICE_LIST_REFRESH_INTERVAL)?;
+        let device = match &self.pinned_device_serial {
+            Some(serial) => devices.iter().find(|d| {
+                d.serial.as_ref() == Some(serial) && self.device_watcher.matches_filter(d)
+            }),
+            None => self.device_watcher.select_device(&devices),
+        };
+        self.listed_device = device.cloned();
+        let (device_serial, device_state) = match device {
+            Some(Device {
+                serial: Some(serial),
+                state,
+                ..
+            }) if !(is_network_serial(serial) && *state == Some(ConnectionState::Offline)) => {
+                (serial.clone(), *state)
+            }
+            _ => {
+                // The device and the phases it reached are kept for a while, so that the launch
+                // delays don't start over
+                if let Some(serial) = &self.device_serial
+                    && !is_network_serial(serial)
+                {
+                    let missing_since = *self.device_missing_since.get_or_insert_with(Instant::now);
+                    if missing_since.elapsed() < self.device_lost_grace {
+                        self.force_refresh();
+
+                        return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                            WiredStatusKind::DeviceReconnecting,
+                            WiredMessage::DeviceReconnecting,
+                        )));
                     }
                 }
+                self.device_missing_since = None;
+                self.device_lock = None;
+                self.pinned_device_serial = None;
 
-                commands::start_application(&self.adb_path, &device_serial, &process_name)?;
-                Ok(WiredConnectionStatus::NotReady(
-                    "Starting ALVR client".to_owned(),
-                ))
-            } else {
-                Ok(WiredConnectionStatus::NotReady(
-                    "ALVR client is not running".to_owned(),
-                ))
-            }
-        } else if !commands::is_activity_resumed(&self.adb_path, &device_serial, &process_name)? {
-            Ok(WiredConnectionStatus::NotReady(
-                "ALVR client is paused".to_owned(),
-            ))
-        } else {
-            Ok(WiredConnectionStatus::Ready)
+                self.enter_setup_state(WiredSetupState::NoDevice);
+                self.force_refresh();
+                if let Some(serial) = self.device_serial.take() {
+                    self.emit_event(WiredEvent::DeviceLost { serial });
+                }
+                self.setup_timings = WiredSetupTimings::default();
+                self.stay_on_restore_value = None;
+                self.applied_device_settings.clear();
+                self.client_importance = None;
+
+                // `adb devices` lists nothing if the server failed to start
+                if self.cleanup_stray_servers
+                    && self.server_lease.is_some()
+                    && let Err(failure) = commands::start_server_with_cleanup(&self.adb_path)
+                {
+                    warn!(
+                        "wired_connection: start_server_with_cleanup failed with {:#}",
+                        failure
+                    );
+                }
+
+                if let Some(status) = self.reconnect_network_device() {
+                    return Ok(SetupTransition::Stop(status));
+                }
+
+                // Without permissions, adb can also fail to read the serial number
+                if let Some(device) = devices
+                    .iter()
+                    .find(|d| d.state == Some(ConnectionState::NoPermissions))
+                {
+                    self.enter_setup_state(WiredSetupState::DeviceUnauthorized);
+
+                    return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                        WiredStatusKind::NoPermissions,
+                        no_permissions_message(device),
+                    )));
+                }
+
+                return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                    WiredStatusKind::NoDevice,
+                    WiredMessage::NoDevice,
+                )));
+            }
+        };
+        // A headset connected with a cable and over the network is listed twice. The commands are
+        // faster over USB, the ports are still forwarded over the transport that was selected.
+        let (device, device_serial, device_state, stream_device_serial) =
+            match self.usb_transport(&devices, &device_serial, device_state) {
+                Some((usb_device, usb_serial)) => (
+                    Some(usb_device),
+                    usb_serial,
+                    usb_device.state,
+                    Some(device_serial),
+                ),
+                None => (device, device_serial, device_state, None),
+            };
+        if self.stream_device_serial != stream_device_serial {
+            if let Some(stream_serial) = &stream_device_serial {
+                info!(
+                    "wired_connection: Using the USB transport {device_serial} for the commands \
+                    of {stream_serial}"
+                );
+            }
+            self.stream_device_serial = stream_device_serial;
+            self.forwards_check = None;
+        }
+
+        self.device_missing_since = None;
+        if self.device_serial.as_ref() != Some(&device_serial) {
+            if let Some(serial) = self.device_serial.replace(device_serial.clone()) {
+                self.emit_event(WiredEvent::DeviceLost { serial });
+            }
+            self.emit_event(WiredEvent::DeviceConnected {
+                serial: device_serial.clone(),
+                model: device.and_then(Device::model).map(str::to_owned),
+            });
+            self.device_found_time = Instant::now();
+            self.setup_timings = WiredSetupTimings::default();
+            self.device_ready = false;
+            self.device_booted = false;
+            self.openxr_runtime_checked = false;
+            self.forwarded_ports.clear();
+            self.restricted_dumpsys_services.clear();
+            self.launch_time = None;
+            self.launch_attempts = 0;
+            self.launch_failure = None;
+            self.stay_on_restore_value = None;
+            self.applied_device_settings.clear();
+            self.client_was_running = false;
+            self.client_launched = false;
+            self.client_crash = None;
+            self.client_foreground_time = None;
+            self.client_foreground_given_up = false;
+            self.client_importance = None;
+            self.client_process_name = None;
+            self.client_version = None;
+            self.client_outdated = false;
+            self.check_schedule.clear();
+            self.force_refresh();
+            self.apply_timing_state(&device_serial);
+        }
+        if is_network_serial(&device_serial) {
+            self.network_device_serial = Some(device_serial.clone());
+            self.reconnect_attempts = 0;
         }
+
+        tick.device = device.cloned();
+        tick.device_serial = device_serial;
+        tick.device_state = device_state;
+
+        Ok(SetupTransition::Next(WiredSetupState::DeviceUnauthorized))
     }
-}
 
-impl Drop for WiredConnection {
-    fn drop(&mut self) {
-        dbg_connection!("wired_connection: Killing ADB server");
-        if let Err(e) = commands::kill_server(&self.adb_path) {
-            error!("{e:?}");
+    // Every following command needs an authorized device, fail early instead of halfway
+    fn setup_device_unauthorized(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        if !self.lock_device(&tick.device_serial) {
+            return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                WiredStatusKind::DeviceManagedElsewhere,
+                WiredMessage::DeviceManagedElsewhere,
+            )));
+        }
+        if let Some(device) = &tick.device
+            && device.state == Some(ConnectionState::NoPermissions)
+        {
+            return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                WiredStatusKind::NoPermissions,
+                no_permissions_message(device),
+            )));
+        }
+        if tick.device_state != Some(ConnectionState::Device) {
+            self.device_watcher.drop_devices();
+
+            return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                device_state_kind(tick.device_state),
+                device_state_message(tick.device_state),
+            )));
+        }
+        if !self.device_ready {
+            if !self.wait_for_device_ready(&tick.device_serial)? {
+                return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                    WiredStatusKind::DeviceUnavailable,
+                    WiredMessage::FinishingAuthorization,
+                )));
+            }
+            self.device_ready = true;
         }
+        self.device_watcher.keep_devices();
+
+        Ok(SetupTransition::Next(WiredSetupState::Booting))
     }
-}
 
-pub fn get_process_name(
-    adb_path: &str,
-    device_serial: &str,
-    flavor: &ClientFlavor,
-) -> Option<String> {
-    let fallbacks = match flavor {
-        ClientFlavor::Store => {
-            if alvr_common::is_stable() {
-                vec![PACKAGE_NAME_STORE, PACKAGE_NAME_GITHUB_STABLE]
-            } else {
-                vec![PACKAGE_NAME_GITHUB_DEV]
+    // adb is available long before the package and activity managers while booting, the commands
+    // of the next phases would fail with confusing errors. The launch delays keep running.
+    fn setup_booting(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        let device_serial = &tick.device_serial;
+        if !self.device_booted {
+            match commands::is_boot_completed(&self.adb_path, device_serial) {
+                Ok(true) => self.device_booted = true,
+                Ok(false) => {
+                    return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                        WiredStatusKind::Booting,
+                        WiredMessage::Booting,
+                    )));
+                }
+                Err(failure) => {
+                    warn!(
+                        "wired_connection: is_boot_completed failed with {}",
+                        failure
+                    );
+                }
             }
         }
-        ClientFlavor::Github => {
-            if alvr_common::is_stable() {
-                vec![PACKAGE_NAME_GITHUB_STABLE, PACKAGE_NAME_STORE]
-            } else {
-                vec![PACKAGE_NAME_GITHUB_DEV]
+
+        // Best-effort: the broker is queried through the package manager, so only once booted
+        if self.device_booted && !self.openxr_runtime_checked {
+            self.openxr_runtime_checked = true;
+            if tick
+                .device
+                .as_ref()
+                .and_then(Device::model)
+                .is_some_and(has_system_openxr_runtime)
+            {
+                self.warn_foreign_openxr_runtime(device_serial);
             }
         }
-        ClientFlavor::Custom(name) => {
-            if alvr_common::is_stable() {
-                vec![name, PACKAGE_NAME_STORE, PACKAGE_NAME_GITHUB_STABLE]
-            } else {
-                vec![name, PACKAGE_NAME_GITHUB_DEV]
+
+        if !tick.keep_display_awake
+            && let Some(value) = self.stay_on_restore_value.take()
+        {
+            commands::set_stay_on_while_plugged_in(&self.adb_path, device_serial, value)?;
+        }
+
+        // Settings removed from the list or with a new value are restored first, so that the new
+        // value is applied on top of the original one
+        let (applied_device_settings, removed_device_settings) =
+            mem::take(&mut self.applied_device_settings)
+                .into_iter()
+                .partition::<Vec<_>, _>(|applied| self.device_settings.contains(&applied.setting));
+        self.applied_device_settings = applied_device_settings;
+        restore_device_settings(&self.adb_path, device_serial, &removed_device_settings);
+
+        Ok(SetupTransition::Next(WiredSetupState::ForwardingPorts))
+    }
+
+    // Devices of a fleet which are not active leave the ports to the active one
+    fn setup_forwarding_ports(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        if self.forward_ports {
+            let stream_serial = self
+                .stream_device_serial
+                .clone()
+                .unwrap_or_else(|| tick.device_serial.clone());
+            let ports = HashSet::from([tick.control_port, tick.stream_port]);
+            match &mut self.forwards_check {
+                Some((checked_ports, setups))
+                    if *checked_ports == ports && *setups < FORWARDS_CHECK_TICKS =>
+                {
+                    *setups += 1;
+                }
+                _ => {
+                    let forwarded_ports: HashSet<u16> =
+                        commands::list_forwarded_ports(&self.adb_path, &stream_serial)?
+                            .into_iter()
+                            .map(|f| f.local)
+                            .collect();
+                    let missing_ports = ports.difference(&forwarded_ports);
+                    for port in missing_ports {
+                        commands::forward_port(&self.adb_path, &stream_serial, *port)?;
+                        self.forwarded_ports.insert(*port);
+                        dbg_connection!(
+                            "setup_wired_connection: Forwarded port {port} of device {stream_serial}"
+                        );
+                    }
+                    self.forwards_check = Some((ports, 0));
+                }
             }
+        } else {
+            // The active device of a fleet can change, the ports must be checked when it is this
+            // one again
+            self.forwards_check = None;
         }
-    };
+        let elapsed = self.device_found_time.elapsed();
+        self.setup_timings.ports_forwarded.get_or_insert(elapsed);
 
-    fallbacks
-        .iter()
-        .find(|name| {
-            commands::is_package_installed(adb_path, device_serial, name)
-                .is_ok_and(|installed| installed)
-        })
-        .map(|name| (*name).to_string())
+        Ok(SetupTransition::Next(WiredSetupState::VerifyingClient))
+    }
+
+    // Installing or uninstalling the client stops it, so the installed client is only looked up
+    // again once it is not running
+    fn setup_verifying_client(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        let device_serial = &tick.device_serial;
+        let client_type = tick.client_type;
+        let client_candidates = process_name_candidates(client_type)
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let cached_process_name = self
+            .client_process_name
+            .clone()
+            .filter(|_| self.client_was_running && self.client_candidates == client_candidates);
+        let process_name = match cached_process_name {
+            Some(process_name) => Some(process_name),
+            None => self.resolve_process_name(device_serial, client_type),
+        };
+        self.client_candidates = client_candidates;
+        let Some(process_name) = process_name else {
+            self.client_process_name = None;
+            self.client_version = None;
+
+            return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                WiredStatusKind::ClientNotInstalled,
+                WiredMessage::ClientNotInstalled,
+            )));
+        };
+        if self.client_process_name.as_ref() != Some(&process_name) {
+            let version =
+                commands::get_package_version(&self.adb_path, device_serial, &process_name)
+                    .unwrap_or_else(|failure| {
+                        warn!(
+                            "wired_connection: get_package_version failed with {}",
+                            failure
+                        );
+
+                        None
+                    });
+            self.client_version = version.clone();
+            self.emit_event(WiredEvent::ClientInstalled {
+                package: process_name.clone(),
+                version,
+            });
+
+            // Clearing the data of the client or updating the OS can revoke them
+            self.client_process_name = Some(process_name.clone());
+            if let Err(failure) = self.ensure_permissions(client_type, &self.client_permissions) {
+                warn!(
+                    "wired_connection: ensure_permissions failed with {}",
+                    failure
+                );
+            }
+        }
+        self.client_process_name = Some(process_name.clone());
+        let elapsed = self.device_found_time.elapsed();
+        self.setup_timings.client_found.get_or_insert(elapsed);
+
+        let client_process_id =
+            commands::get_process_id(&self.adb_path, device_serial, &process_name)?;
+        let next_state = if client_process_id.is_some() {
+            self.launch_time = None;
+            self.launch_attempts = 0;
+            self.launch_failure = None;
+            self.crash_checked_launch_attempts = 0;
+            self.client_was_running = true;
+            self.client_crash = None;
+
+            WiredSetupState::AwaitingResume
+        } else {
+            self.client_resumed = false;
+            self.client_foreground_time = None;
+            self.client_foreground_given_up = false;
+            self.client_outdated = false;
+            self.check_schedule.clear();
+
+            if self.client_was_running {
+                self.client_was_running = false;
+                self.client_launched = false;
+                self.check_client_crash(device_serial, &process_name);
+            }
+
+            if tick.client_autolaunch.is_some() {
+                WiredSetupState::AwaitingLaunchDelay
+            } else {
+                WiredSetupState::ClientStopped
+            }
+        };
+        tick.process_name = process_name;
+        tick.client_process_id = client_process_id;
+
+        Ok(SetupTransition::Next(next_state))
+    }
+
+    // The client is not running and auto-launch is disabled
+    fn setup_client_stopped(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        self.update_client_importance(&tick.device_serial, &tick.process_name);
+
+        // The user can't start a disabled client either, it's not just stopped
+        if !commands::is_package_enabled(&self.adb_path, &tick.device_serial, &tick.process_name)? {
+            return Ok(SetupTransition::Stop(client_disabled_status()));
+        }
+
+        Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+            WiredStatusKind::ClientStopped,
+            WiredMessage::ClientStopped,
+        )))
+    }
+
+    fn setup_awaiting_launch_delay(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        let client_autolaunch = tick
+            .client_autolaunch
+            .as_ref()
+            .context("Auto-launch is disabled")?;
+        if client_autolaunch.boot_delay > 0 {
+            match commands::get_uptime(&self.adb_path, &tick.device_serial) {
+                Ok(uptime) => {
+                    let boot_delay = Duration::from_secs(client_autolaunch.boot_delay.into());
+                    if uptime < boot_delay {
+                        let remaining_secs = remaining_secs(boot_delay - uptime);
+
+                        return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                            WiredStatusKind::AwaitingLaunchDelay { remaining_secs },
+                            WiredMessage::AwaitingBoot {
+                                seconds_remaining: remaining_secs,
+                            },
+                        )));
+                    }
+                }
+                Err(failure) => {
+                    warn!("wired_connection: get_uptime failed with {}", failure);
+                }
+            }
+        }
+
+        let step = launch_step(
+            client_autolaunch,
+            self.device_found_time,
+            self.launch_time,
+            self.launch_attempts,
+            Instant::now(),
+        );
+
+        // The last launch did not bring up the client, it may have crashed
+        if self.launch_time.is_some()
+            && matches!(step, LaunchStep::Launch | LaunchStep::GiveUp)
+            && self.crash_checked_launch_attempts != self.launch_attempts
+        {
+            self.crash_checked_launch_attempts = self.launch_attempts;
+            self.check_client_crash(&tick.device_serial, &tick.process_name);
+        }
+
+        if let LaunchStep::WaitForDevice(remaining) = step {
+            let remaining_secs = remaining_secs(remaining);
+
+            return Ok(SetupTransition::Stop(WiredConnectionStatus::not_ready(
+                WiredStatusKind::AwaitingLaunchDelay { remaining_secs },
+                WiredMessage::AwaitingLaunchDelay {
+                    seconds_remaining: remaining_secs,
+                },
+            )));
+        }
+        let next_state = launch_step_setup_state(&step);
+        tick.launch_step = Some(step);
+
+        Ok(SetupTransition::Next(next_state))
+    }
+
+    fn setup_launching(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        if tick.launch_step == Some(LaunchStep::WaitForClient) {
+            return Ok(SetupTransition::Stop(launch_failure_status(
+                self.launch_failure.clone(),
+                WiredStatusKind::Launching,
+                WiredMessage::StartingClient,
+            )));
+        }
+
+        let device_serial = &tick.device_serial;
+        let process_name = &tick.process_name;
+        if let Some(packages) = tick
+            .client_autolaunch
+            .as_ref()
+            .and_then(|c| c.stop_competing_apps.as_option())
+        {
+            self.stop_competing_apps(device_serial, process_name, packages);
+        }
+
+        if let Some(status) = self.prepare_launch(device_serial)? {
+            return Ok(SetupTransition::Stop(status));
+        }
+
+        // Some launchers disable the app instead of uninstalling it, the launch would silently
+        // have no effect
+        if !commands::is_package_enabled(&self.adb_path, device_serial, process_name)? {
+            if let Err(failure) =
+                commands::enable_package(&self.adb_path, device_serial, process_name)
+            {
+                warn!("wired_connection: enable_package failed with {}", failure);
+
+                return Ok(SetupTransition::Stop(client_disabled_status()));
+            }
+            info!("wired_connection: Enabled disabled package {process_name}");
+        }
+
+        // A failure reported by `am` is shown right away instead of after the post launch delay.
+        // The launch is still retried, the cause can be transient.
+        self.launch_failure = self.start_client(device_serial, process_name, tick.client_type)?;
+        self.launch_time = Some(Instant::now());
+        self.launch_attempts += 1;
+        let elapsed = self.device_found_time.elapsed();
+        self.setup_timings.launch_issued.get_or_insert(elapsed);
+
+        Ok(SetupTransition::Stop(launch_failure_status(
+            self.launch_failure.clone(),
+            WiredStatusKind::Launching,
+            WiredMessage::StartingClient,
+        )))
+    }
+
+    fn setup_launch_failed(&self) -> SetupTransition {
+        SetupTransition::Stop(WiredConnectionStatus::not_ready(
+            WiredStatusKind::LaunchGaveUp {
+                attempts: self.launch_attempts,
+            },
+            WiredMessage::LaunchGaveUp {
+                attempts: self.launch_attempts,
+            },
+        ))
+    }
+
+    // The client is running but can be paused, outdated or not responding
+    fn setup_awaiting_resume(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        let device_serial = &tick.device_serial;
+        let process_name = &tick.process_name;
+        let client_autolaunch = tick.client_autolaunch.as_ref();
+
+        let status = if let Some(process_id) = tick.client_process_id
+            && self.scheduled_client_outdated(device_serial, process_name, process_id)
+        {
+            if client_autolaunch.is_some_and(|c| c.restart_outdated_client) {
+                warn!("wired_connection: Restarting outdated client {process_name}");
+                commands::force_stop_application(&self.adb_path, device_serial, process_name)?;
+                let failure = self.start_client(device_serial, process_name, tick.client_type)?;
+
+                launch_failure_status(
+                    failure,
+                    WiredStatusKind::ClientOutdated,
+                    WiredMessage::RestartingOutdatedClient,
+                )
+            } else {
+                WiredConnectionStatus::not_ready(
+                    WiredStatusKind::ClientOutdated,
+                    WiredMessage::ClientOutdated,
+                )
+            }
+        } else if !self.is_client_resumed(
+            device_serial,
+            process_name,
+            tick.allow_background_client,
+        )? && !self.foreground_client(
+            device_serial,
+            process_name,
+            tick.client_type,
+            tick.allow_background_client,
+            client_autolaunch,
+        )? {
+            self.update_client_importance(device_serial, process_name);
+
+            self.paused_client_status(device_serial)
+        } else if self.scheduled_client_not_responding(
+            device_serial,
+            process_name,
+            tick.client_not_responding_since.is_some(),
+        )? {
+            let not_responding_since = tick
+                .client_not_responding_since
+                .unwrap_or_else(Instant::now);
+
+            if let Some(restart_delay) =
+                client_autolaunch.and_then(|c| c.unresponsive_restart_delay.as_option())
+                && not_responding_since.elapsed() >= Duration::from_secs((*restart_delay).into())
+            {
+                warn!("wired_connection: Restarting unresponsive client {process_name}");
+                commands::force_stop_application(&self.adb_path, device_serial, process_name)?;
+                let failure = self.start_client(device_serial, process_name, tick.client_type)?;
+
+                launch_failure_status(
+                    failure,
+                    WiredStatusKind::ClientNotResponding,
+                    WiredMessage::RestartingUnresponsiveClient,
+                )
+            } else {
+                self.client_not_responding_since = Some(not_responding_since);
+
+                WiredConnectionStatus::not_ready(
+                    WiredStatusKind::ClientNotResponding,
+                    WiredMessage::ClientNotResponding,
+                )
+            }
+        } else {
+            return Ok(SetupTransition::Next(WiredSetupState::Ready));
+        };
+
+        Ok(SetupTransition::Stop(status))
+    }
+
+    fn setup_ready(&mut self, tick: &mut SetupTick) -> AdbResult<SetupTransition> {
+        if tick.keep_display_awake && self.stay_on_restore_value.is_none() {
+            self.keep_display_awake(&tick.device_serial)?;
+        }
+        self.apply_device_settings(&tick.device_serial);
+
+        if self.setup_timings.ready.is_none() {
+            let elapsed = self.device_found_time.elapsed();
+            self.setup_timings.ready = Some(elapsed);
+            info!(
+                "wired_connection: Ready {:.1}s after finding the device ({:?})",
+                elapsed.as_secs_f32(),
+                self.setup_timings
+            );
+        }
+
+        Ok(SetupTransition::Stop(WiredConnectionStatus::Ready(
+            self.setup_timings.clone(),
+        )))
+    }
+
+    // Two instances would undo each other's forwards and launches. Returns false if another
+    // instance manages the device. The lock is only a safeguard, locking is disabled if it fails.
+    fn lock_device(&mut self, device_serial: &str) -> bool {
+        let Some(lock_dir) = &self.lock_dir else {
+            return true;
+        };
+
+        let res = match &mut self.device_lock {
+            Some(lock) if lock.device_serial() == device_serial => lock.refresh(),
+            _ => {
+                self.device_lock = None;
+                DeviceLock::acquire(lock_dir, device_serial).map(|lock| {
+                    let acquired = lock.is_some();
+                    self.device_lock = lock;
+
+                    acquired
+                })
+            }
+        };
+        match res {
+            Ok(true) => true,
+            Ok(false) => {
+                self.device_lock = None;
+
+                false
+            }
+            Err(e) => {
+                warn!("wired_connection: Locking the device failed with {e}, locking is disabled");
+                self.lock_dir = None;
+
+                true
+            }
+        }
+    }
+
+    // USB transport of the device of the network transport `serial`, recognized by its android_id
+    fn usb_transport<'a>(
+        &mut self,
+        devices: &'a [Device],
+        serial: &str,
+        state: Option<ConnectionState>,
+    ) -> Option<(&'a Device, String)> {
+        if !is_network_serial(serial) || state != Some(ConnectionState::Device) {
+            return None;
+        }
+        let usb_devices = devices
+            .iter()
+            .filter_map(|d| Some((d, d.serial.clone()?)))
+            .filter(|(d, s)| !is_network_serial(s) && d.state == Some(ConnectionState::Device))
+            .collect::<Vec<_>>();
+        if usb_devices.is_empty() {
+            return None;
+        }
+
+        self.android_ids
+            .retain(|s, _| devices.iter().any(|d| d.serial.as_ref() == Some(s)));
+        let android_id = self.android_id(serial)?;
+
+        usb_devices
+            .into_iter()
+            .find(|(_, usb_serial)| self.android_id(usb_serial).as_ref() == Some(&android_id))
+    }
+
+    // Cached, the android_id of a device only changes on factory reset
+    fn android_id(&mut self, device_serial: &str) -> Option<String> {
+        if let Some(android_id) = self.android_ids.get(device_serial) {
+            return android_id.clone();
+        }
+
+        match commands::get_setting(&self.adb_path, device_serial, "secure", "android_id") {
+            Ok(android_id) => {
+                self.android_ids
+                    .insert(device_serial.to_owned(), android_id.clone());
+
+                android_id
+            }
+            Err(failure) => {
+                warn!("wired_connection: get_setting failed with {}", failure);
+
+                None
+            }
+        }
+    }
+
+    /// Drops the cached device list, forwarded ports and installed client, so that the next setup
+    /// queries them again, e.g. after changing the device from outside of ALVR.
+    pub fn force_refresh(&mut self) {
+        self.device_watcher.drop_devices();
+        self.forwards_check = None;
+        self.client_candidates.clear();
+    }
+
+    /// Time during which a USB device that disappeared from adb is reported as reconnecting, before
+    /// its state is reset like for a new device. 3 seconds by default.
+    pub fn set_device_lost_grace(&mut self, grace: Duration) {
+        self.device_lost_grace = grace;
+    }
+
+    /// Makes `setup` return the status of the last successful setup without querying the device
+    /// until `interval` elapsed, for callers that run it more often than the device can change,
+    /// e.g. on every frame of a UI. Disabled by default.
+    pub fn set_min_tick_interval(&mut self, interval: Duration) {
+        self.min_tick_interval = interval;
+    }
+
+    /// Runs the setup like `setup` and returns the state it reached instead of the status. Each
+    /// setup goes through the phases from `NoDevice` until one is not complete.
+    pub fn step(&mut self, params: &WiredSetupParams) -> AdbResult<WiredSetupState> {
+        self.setup_with_params(params)?;
+
+        Ok(self.setup_state)
+    }
+
+    /// Time since `setup` first returned the current status, e.g. to show how long the connection
+    /// has been waiting for the client. Changes of the message, counters or remaining times of the
+    /// status do not count as a new status.
+    pub fn time_in_current_state(&self) -> Duration {
+        self.status_entered_time.elapsed()
+    }
+
+    /// Phase reached by the last `setup`. If it failed, this is the phase that failed.
+    pub fn setup_state(&self) -> WiredSetupState {
+        self.setup_state
+    }
+
+    // Replaces the result of a command based on dumpsys with `default` if the device restricts
+    // dumpsys, so that locked-down devices stay usable
+    fn unless_dumpsys_restricted<T>(&mut self, res: AdbResult<T>, default: T) -> AdbResult<T> {
+        match res {
+            Ok(value) => Ok(value),
+            Err(e) => match as_adb_error(&e) {
+                Some(restricted @ AdbError::DumpsysRestricted { service }) => {
+                    if self.restricted_dumpsys_services.insert(service.clone()) {
+                        warn!("wired_connection: {restricted}, using a default instead");
+                    }
+
+                    Ok(default)
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    // Right after the debugging prompt is accepted, the device can be listed as "device" while the
+    // first commands still fail. Waits for `adb get-state` to report it too.
+    fn wait_for_device_ready(&self, device_serial: &str) -> AdbResult<bool> {
+        let start_time = Instant::now();
+        loop {
+            self.cancel_handle.check()?;
+            if matches!(
+                commands::get_state(&self.adb_path, device_serial),
+                Ok(Some(ConnectionState::Device))
+            ) {
+                return Ok(true);
+            }
+            if start_time.elapsed() >= DEVICE_READY_TIMEOUT {
+                return Ok(false);
+            }
+
+            thread::sleep(DEVICE_READY_POLL_INTERVAL);
+        }
+    }
+
+    fn enter_setup_state(&mut self, state: WiredSetupState) {
+        if self.setup_state != state {
+            dbg_connection!(
+                "wired_connection: Setup state {:?} -> {state:?}",
+                self.setup_state
+            );
+            self.setup_state = state;
+        }
+    }
+
+    /// Runs the setup with `params` instead of the parameters of the connection, see
+    /// `WiredSetupParams::new`. The wrappers that take the parameters as a whole call it.
+    pub fn setup_with_params(
+        &mut self,
+        params: &WiredSetupParams,
+    ) -> AdbResult<WiredConnectionStatus> {
+        self.set_device_settings(params.device_settings.clone());
+        self.pre_launch_shell = params.pre_launch_shell.clone();
+        self.client_permissions = params.client_permissions.clone();
+
+        self.run_setup(
+            params.control_port,
+            params.stream_port,
+            &params.client_type,
+            params.allow_background_client,
+            params.keep_display_awake,
+            params.client_autolaunch.clone(),
+        )
+    }
+
+    /// Handle to abort the setup and the long operations from another thread, e.g. when the user
+    /// disables the wired connection during a download
+    pub fn cancel_handle(&self) -> WiredCancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    /// Runs the commands of `setup`, `teardown` and `diagnose` through `transport` instead of the adb
+    /// executable, e.g. a `MockTransport` in tests
+    pub fn set_transport(&mut self, transport: Arc<dyn AdbTransport>) {
+        self.transport = Some(transport);
+    }
+
+    /// Calls `handler` on the transitions of the connection, e.g. to show notifications. It runs on
+    /// the thread calling `setup` and should not block.
+    pub fn set_event_handler(&mut self, handler: impl Fn(WiredEvent) + Send + 'static) {
+        self.event_handler = Some(Box::new(handler));
+    }
+
+    fn emit_event(&self, event: WiredEvent) {
+        dbg_connection!("wired_connection: {event:?}");
+        if let Some(handler) = &self.event_handler {
+            handler(event);
+        }
+    }
+
+    /// Replaces the choice of the installed client, e.g. for forks whose activity cannot be
+    /// derived from the package.
+    pub fn set_process_name_resolver(
+        &mut self,
+        resolver: impl Fn(&[&str]) -> Option<String> + Send + 'static,
+    ) {
+        self.process_name_resolver = Some(Box::new(resolver));
+    }
+
+    fn resolve_process_name(
+        &mut self,
+        device_serial: &str,
+        client_type: &ClientFlavor,
+    ) -> Option<String> {
+        let (process_name, activity_name) = self.find_client(device_serial, client_type)?;
+        self.client_activity_name = activity_name;
+
+        Some(process_name)
+    }
+
+    // Returns the process name and the activity name the resolver picked, if any
+    fn find_client(
+        &self,
+        device_serial: &str,
+        client_type: &ClientFlavor,
+    ) -> Option<(String, Option<String>)> {
+        let Some(resolver) = &self.process_name_resolver else {
+            return get_process_name(&self.adb_path, device_serial, client_type)
+                .map(|process_name| (process_name, None));
+        };
+
+        let installed_names = process_name_candidates(client_type)
+            .into_iter()
+            .filter(|name| {
+                commands::is_package_installed(&self.adb_path, device_serial, name)
+                    .is_ok_and(|installed| installed)
+            })
+            .collect::<Vec<_>>();
+        let name = resolver(&installed_names)?;
+        let (process_name, activity_name) = split_custom_client(&name);
+
+        Some((process_name.to_owned(), activity_name.map(str::to_owned)))
+    }
+
+    pub fn setup_timings(&self) -> &WiredSetupTimings {
+        &self.setup_timings
+    }
+
+    /// Lets the connection terminate stray ADB servers that hold the server port without
+    /// answering, see `commands::cleanup_stray_servers`. Disabled by default since it kills
+    /// processes that ALVR did not start.
+    pub fn set_cleanup_stray_servers(&mut self, enabled: bool) {
+        self.cleanup_stray_servers = enabled;
+    }
+
+    /// Sets the Android settings and system properties to change while the connection is ready.
+    /// They are applied the next time the connection is ready and restored when it is dropped.
+    pub fn set_device_settings(&mut self, settings: Vec<WiredDeviceSetting>) {
+        self.device_settings = settings;
+    }
+
+    fn apply_device_settings(&mut self, device_serial: &str) {
+        for setting in &self.device_settings {
+            if self
+                .applied_device_settings
+                .iter()
+                .any(|applied| applied.setting == *setting)
+            {
+                continue;
+            }
+
+            let applied = match apply_device_setting(&self.adb_path, device_serial, setting) {
+                Ok(original_value) => {
+                    dbg_connection!(
+                        "wired_connection: Set {:?} {} to {:?}",
+                        setting.namespace,
+                        setting.key,
+                        setting.value
+                    );
+
+                    AppliedDeviceSetting {
+                        setting: setting.clone(),
+                        original_value,
+                        failed: false,
+                    }
+                }
+                Err(failure) => {
+                    warn!(
+                        "wired_connection: apply_device_setting failed with {:#}",
+                        failure
+                    );
+
+                    AppliedDeviceSetting {
+                        setting: setting.clone(),
+                        original_value: None,
+                        failed: true,
+                    }
+                }
+            };
+            self.applied_device_settings.push(applied);
+        }
+    }
+
+    // A runtime selected in the OpenXR runtime broker takes the session from the runtime of the
+    // headset, which the client needs to stream
+    fn warn_foreign_openxr_runtime(&self, device_serial: &str) {
+        match commands::get_active_openxr_runtime(&self.adb_path, device_serial) {
+            Ok(Some(package)) => warn!(
+                "wired_connection: OpenXR runtime {package} is active instead of the one of the headset, the client may fail to stream"
+            ),
+            Ok(None) => (),
+            Err(failure) => warn!(
+                "wired_connection: get_active_openxr_runtime failed with {}",
+                failure
+            ),
+        }
+    }
+
+    fn keep_display_awake(&mut self, device_serial: &str) -> AdbResult<()> {
+        let value = commands::get_stay_on_while_plugged_in(&self.adb_path, device_serial)?;
+        commands::stay_on_usb(&self.adb_path, device_serial)?;
+        self.stay_on_restore_value = Some(value);
+
+        Ok(())
+    }
+
+    // Only crashes that happened recently can explain why the client is not running
+    fn check_client_crash(&mut self, device_serial: &str, process_name: &str) {
+        match recent_crash(&self.adb_path, device_serial, process_name) {
+            Ok(Some(crash)) => {
+                warn!(
+                    "wired_connection: {process_name} {}",
+                    crash_description(&crash)
+                );
+                self.client_crash = Some(crash);
+            }
+            Ok(None) => (),
+            Err(failure) => {
+                warn!(
+                    "wired_connection: check_client_crash failed with {}",
+                    failure
+                );
+            }
+        }
+    }
+
+    fn with_crash_report(&self, status: WiredConnectionStatus) -> WiredConnectionStatus {
+        match (status, &self.client_crash) {
+            (WiredConnectionStatus::NotReady { kind, message }, Some(crash)) => {
+                WiredConnectionStatus::not_ready(
+                    kind,
+                    WiredMessage::LastRunCrashed {
+                        message: Box::new(message),
+                        crash: crash.kind,
+                        summary: crash.summary.clone(),
+                    },
+                )
+            }
+            (status, _) => status,
+        }
+    }
+
+    // Sends the command to the client resolved by the last setup
+    pub fn send_client_command(&self, command: ClientCommand) -> AdbResult<()> {
+        let device_serial = self
+            .device_serial
+            .as_ref()
+            .context("No wired device is connected")?;
+        let process_name = self
+            .client_process_name
+            .as_ref()
+            .context("No ALVR client found")?;
+
+        let (action, extras) = match command {
+            ClientCommand::Reconnect => ("alvr.client.action.RECONNECT", vec![]),
+            ClientCommand::RotateLogs => ("alvr.client.action.ROTATE_LOGS", vec![]),
+            ClientCommand::SetDebugOverlay(enabled) => (
+                "alvr.client.action.SET_DEBUG_OVERLAY",
+                vec![("enabled", BroadcastExtra::Bool(enabled))],
+            ),
+        };
+
+        commands::send_broadcast(&self.adb_path, device_serial, process_name, action, &extras)
+    }
+
+    /// Grants the permissions the installed client is missing, without reinstalling it. Returns the
+    /// permissions that were granted.
+    pub fn ensure_permissions(
+        &self,
+        flavor: &ClientFlavor,
+        permissions: &[impl AsRef<str>],
+    ) -> AdbResult<Vec<String>> {
+        if permissions.is_empty() {
+            return Ok(vec![]);
+        }
+        let device_serial = self
+            .device_serial
+            .as_ref()
+            .context("No wired device is connected")?;
+        let process_name = match self
+            .client_process_name
+            .clone()
+            .filter(|name| process_name_candidates(flavor).contains(&name.as_str()))
+        {
+            Some(process_name) => process_name,
+            None => get_process_name(&self.adb_path, device_serial, flavor)
+                .context("No ALVR client found")?,
+        };
+
+        let granted_permissions =
+            commands::list_granted_permissions(&self.adb_path, device_serial, &process_name)?;
+        let mut newly_granted = vec![];
+        for permission in permissions.iter().map(AsRef::as_ref) {
+            if !granted_permissions.contains(permission) {
+                commands::grant_permission(
+                    &self.adb_path,
+                    device_serial,
+                    &process_name,
+                    permission,
+                )?;
+                info!("wired_connection: Granted {permission} to {process_name}");
+                newly_granted.push(permission.to_owned());
+            }
+        }
+
+        Ok(newly_granted)
+    }
+
+    /// Blocks until the client resolved by the last setup is resumed and focused. Returns false if
+    /// it is still not resumed after the timeout. Meant for scripted flows, `setup` keeps returning
+    /// the paused status without waiting.
+    pub fn wait_for_resumed(&self, timeout: Duration) -> AdbResult<bool> {
+        let device_serial = self
+            .device_serial
+            .as_ref()
+            .context("No wired device is connected")?;
+        let process_name = self
+            .client_process_name
+            .as_ref()
+            .context("No ALVR client found")?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.cancel_handle.check()?;
+
+            let state = commands::get_activity_state(&self.adb_path, device_serial, process_name)?;
+            if state == ActivityState::Resumed {
+                return Ok(true);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                dbg_connection!(
+                    "wired_connection: {process_name} not resumed, last state {state:?}"
+                );
+
+                return Ok(false);
+            }
+            thread::sleep(RESUMED_POLL_INTERVAL.min(remaining));
+        }
+    }
+
+    // Battery state of the device selected by the last setup
+    pub fn battery_state(&self) -> AdbResult<BatteryState> {
+        let device_serial = self
+            .device_serial
+            .as_ref()
+            .context("No wired device is connected")?;
+
+        commands::get_battery_state(&self.adb_path, device_serial)
+    }
+
+    pub fn is_charging(&self) -> AdbResult<bool> {
+        Ok(self.battery_state()?.is_charging())
+    }
+
+    /// Samples the memory usage and frame times of the running client. The dumps are expensive, so
+    /// this returns None if the last sample is more recent than `interval`. Meant to be polled by
+    /// the statistics while a wired session is live.
+    pub fn sample_client_performance(
+        &mut self,
+        interval: Duration,
+    ) -> AdbResult<Option<ClientPerformanceSample>> {
+        if !self.client_was_running
+            || self
+                .last_performance_sample_time
+                .is_some_and(|time| time.elapsed() < interval)
+        {
+            return Ok(None);
+        }
+        let device_serial = self
+            .device_serial
+            .as_ref()
+            .context("No wired device is connected")?;
+        let process_name = self
+            .client_process_name
+            .as_ref()
+            .context("No ALVR client found")?;
+        self.last_performance_sample_time = Some(Instant::now());
+
+        Ok(Some(ClientPerformanceSample {
+            memory: commands::get_memory_info(&self.adb_path, device_serial, process_name)?,
+            graphics: commands::get_graphics_info(&self.adb_path, device_serial, process_name)?,
+        }))
+    }
+
+    /// Polls `setup` and yields the status only when it changes. Errors are yielded as they happen
+    /// and the polling continues after them. The iterator never ends.
+    pub fn status_stream(&mut self, params: WiredSetupParams) -> WiredStatusStream<'_> {
+        WiredStatusStream {
+            connection: self,
+            params,
+            last_status: None,
+            polled: false,
+        }
+    }
+
+    // The activity state cannot be determined on some devices or while the activity manager is
+    // busy. Keep the last known state in that case to avoid flapping between statuses. A client
+    // that is visible but not focused (e.g. next to a panel on Quest) only counts as resumed when
+    // allowed.
+    fn is_client_resumed(
+        &mut self,
+        device_serial: &str,
+        process_name: &str,
+        allow_background_client: bool,
+    ) -> AdbResult<bool> {
+        // Without access to the activity manager the running client is assumed to be usable
+        let state = self.unless_dumpsys_restricted(
+            commands::get_activity_state(&self.adb_path, device_serial, process_name),
+            ActivityState::Resumed,
+        )?;
+
+        let in_background = state == ActivityState::Visible && allow_background_client;
+        if in_background && !self.client_in_background {
+            info!("wired_connection: {process_name} is visible but not focused, streaming anyway");
+        }
+        self.client_in_background = in_background;
+
+        match state {
+            ActivityState::Resumed => self.client_resumed = true,
+            ActivityState::Visible => self.client_resumed = allow_background_client,
+            ActivityState::Paused => self.client_resumed = false,
+            ActivityState::Unknown => {
+                dbg_connection!("wired_connection: Unknown activity state of {process_name}");
+            }
+        }
+        if self.client_resumed {
+            self.client_foreground_given_up = false;
+
+            let elapsed = self.device_found_time.elapsed();
+            self.setup_timings.client_resumed.get_or_insert(elapsed);
+        }
+
+        Ok(self.client_resumed)
+    }
+
+    // Relaunching the activity of a paused client brings its task back to the front. Returns true
+    // if the client is resumed afterwards. If the client is paused again right after, the user
+    // left it on purpose and this gives up until the user returns to the client.
+    fn foreground_client(
+        &mut self,
+        device_serial: &str,
+        process_name: &str,
+        client_type: &ClientFlavor,
+        allow_background_client: bool,
+        client_autolaunch: Option<&WiredClientAutoLaunchConfig>,
+    ) -> AdbResult<bool> {
+        let Some(cooldown) = client_autolaunch.and_then(|c| c.foreground_paused_client.as_option())
+        else {
+            return Ok(false);
+        };
+        if self.client_foreground_given_up {
+            return Ok(false);
+        }
+        if let Some(foreground_time) = self.client_foreground_time {
+            if foreground_time.elapsed() < FOREGROUND_GIVE_UP_WINDOW {
+                info!("wired_connection: {process_name} was paused again, leaving it paused");
+                self.client_foreground_given_up = true;
+
+                return Ok(false);
+            }
+            if foreground_time.elapsed() < Duration::from_secs((*cooldown).into()) {
+                return Ok(false);
+            }
+        }
+
+        info!("wired_connection: Bringing paused {process_name} to the foreground");
+        self.client_foreground_time = Some(Instant::now());
+        if let Some(failure) = self.start_client(device_serial, process_name, client_type)? {
+            warn!("wired_connection: start_client failed with {}", failure);
+
+            return Ok(false);
+        }
+
+        self.is_client_resumed(device_serial, process_name, allow_background_client)
+    }
+
+    // Tells apart a headset that was taken off from a client that was left for another app. Quest
+    // and Pico headsets turn the screen off when the proximity sensor is uncovered, so the screen
+    // state also tells whether the headset is worn. Only queried while the client is paused.
+    fn paused_client_status(&self, device_serial: &str) -> WiredConnectionStatus {
+        let (kind, message) = match commands::is_screen_on(&self.adb_path, device_serial) {
+            Ok(false) => (WiredStatusKind::HeadsetAsleep, WiredMessage::HeadsetAsleep),
+            Ok(true) => (
+                WiredStatusKind::ClientPaused,
+                WiredMessage::ClientInBackground,
+            ),
+            Err(failure) => {
+                warn!("wired_connection: is_screen_on failed with {}", failure);
+
+                (WiredStatusKind::ClientPaused, WiredMessage::ClientPaused)
+            }
+        };
+
+        WiredConnectionStatus::not_ready(kind, message)
+    }
+
+    // The package can be updated while the old process keeps running. A new process is checked
+    // right away, then the last result is reused until the check is due again.
+    fn scheduled_client_outdated(
+        &mut self,
+        device_serial: &str,
+        process_name: &str,
+        process_id: usize,
+    ) -> bool {
+        let new_process = self
+            .client_start_time
+            .is_none_or(|(id, _)| id != process_id);
+        if self.check_schedule.run_if_due(
+            ScheduledCheck::ClientOutdated,
+            CLIENT_OUTDATED_CHECK_INTERVAL,
+        ) || new_process
+        {
+            self.client_outdated = self
+                .is_client_outdated(device_serial, process_name, process_id)
+                .unwrap_or_else(|failure| {
+                    warn!(
+                        "wired_connection: is_client_outdated failed with {}",
+                        failure
+                    );
+
+                    false
+                });
+        }
+
+        self.client_outdated
+    }
+
+    fn scheduled_client_not_responding(
+        &mut self,
+        device_serial: &str,
+        process_name: &str,
+        was_not_responding: bool,
+    ) -> AdbResult<bool> {
+        if !self.check_schedule.run_if_due(
+            ScheduledCheck::ClientNotResponding,
+            CLIENT_NOT_RESPONDING_CHECK_INTERVAL,
+        ) {
+            return Ok(was_not_responding);
+        }
+
+        self.unless_dumpsys_restricted(
+            commands::is_process_not_responding(&self.adb_path, device_serial, process_name),
+            false,
+        )
+    }
+
+    fn is_client_outdated(
+        &mut self,
+        device_serial: &str,
+        process_name: &str,
+        process_id: usize,
+    ) -> AdbResult<bool> {
+        let start_time = match self.client_start_time {
+            Some((id, start_time)) if id == process_id => start_time,
+            _ => {
+                let start_time = process_start_local_secs(
+                    commands::get_device_clock(&self.adb_path, device_serial)?,
+                    commands::get_uptime(&self.adb_path, device_serial)?,
+                    commands::get_process_start_time(&self.adb_path, device_serial, process_id)?,
+                );
+                self.client_start_time = Some((process_id, start_time));
+
+                start_time
+            }
+        };
+        // The client is never restarted if its update time can't be read
+        let Some(update_time) = self.unless_dumpsys_restricted(
+            commands::get_package_update_time(&self.adb_path, device_serial, process_name)
+                .map(Some),
+            None,
+        )?
+        else {
+            return Ok(false);
+        };
+
+        Ok(is_process_outdated(start_time, update_time))
+    }
+
+    // Tells apart a cached process, which the headset can kill at any time, from a process kept alive
+    // by a foreground service. It costs one more adb command, so it is only queried again after
+    // CLIENT_IMPORTANCE_CHECK_INTERVAL. The last importance is kept if the query fails.
+    fn update_client_importance(&mut self, device_serial: &str, process_name: &str) {
+        if self
+            .client_importance
+            .as_ref()
+            .is_some_and(|(time, _)| time.elapsed() < CLIENT_IMPORTANCE_CHECK_INTERVAL)
+        {
+            return;
+        }
+
+        let res = commands::get_process_importance(&self.adb_path, device_serial, process_name);
+        let importance = match self.unless_dumpsys_restricted(res, None) {
+            Ok(importance) => {
+                dbg_connection!("wired_connection: Importance of {process_name}: {importance:?}");
+
+                importance
+            }
+            Err(failure) => {
+                warn!(
+                    "wired_connection: get_process_importance failed with {}",
+                    failure
+                );
+
+                self.client_importance
+                    .take()
+                    .and_then(|(_, importance)| importance)
+            }
+        };
+        self.client_importance = Some((Instant::now(), importance));
+    }
+
+    // Returns why the launch failed if `am` reports it
+    fn start_client(
+        &mut self,
+        device_serial: &str,
+        process_name: &str,
+        client_type: &ClientFlavor,
+    ) -> AdbResult<Option<WiredMessage>> {
+        // A failed command is reported like a failed launch, so the launch is retried
+        for command_line in &self.pre_launch_shell {
+            info!("wired_connection: Running pre-launch command `{command_line}`");
+            if let Err(e) =
+                commands::shell_command_line(&self.adb_path, device_serial, command_line)
+            {
+                let Some(AdbError::CommandFailed { stderr, .. }) = as_adb_error(&e) else {
+                    return Err(e);
+                };
+                warn!("wired_connection: Pre-launch command `{command_line}` failed with {stderr}");
+
+                return Ok(Some(WiredMessage::PreLaunchCommandFailed {
+                    command_line: command_line.clone(),
+                    stderr: stderr.clone(),
+                }));
+            }
+        }
+
+        let failure = self.start_client_component(device_serial, process_name, client_type)?;
+        if failure.is_none() {
+            self.client_launched = true;
+            self.emit_event(WiredEvent::ClientLaunched {
+                package: process_name.to_owned(),
+            });
+        }
+
+        Ok(failure)
+    }
+
+    // Launches the activity of the client explicitly, since the launcher intent can resolve to a
+    // different activity on some builds
+    fn start_client_component(
+        &self,
+        device_serial: &str,
+        process_name: &str,
+        client_type: &ClientFlavor,
+    ) -> AdbResult<Option<WiredMessage>> {
+        if let Some(activity_name) = self
+            .client_activity_name
+            .as_deref()
+            .or_else(|| get_activity_name(client_type, process_name))
+        {
+            let component = format!("{process_name}/{activity_name}");
+
+            match commands::start_activity(
+                &self.adb_path,
+                device_serial,
+                process_name,
+                activity_name,
+                self.launch_display(device_serial),
+            )? {
+                LaunchResult::Started {
+                    total_time_ms: Some(total_time_ms),
+                } => {
+                    info!("wired_connection: Started {component} in {total_time_ms}ms");
+
+                    return Ok(None);
+                }
+                LaunchResult::Started {
+                    total_time_ms: None,
+                } => {
+                    info!("wired_connection: Brought {component} to the front");
+
+                    return Ok(None);
+                }
+                LaunchResult::TimedOut => {
+                    info!("wired_connection: Started {component}, still launching");
+
+                    return Ok(None);
+                }
+                LaunchResult::Failed(LaunchError::ActivityNotFound) => {
+                    warn!("wired_connection: Activity {component} does not exist");
+                }
+                LaunchResult::Failed(error) => {
+                    warn!("wired_connection: Starting {component} failed with {error:?}");
+
+                    return Ok(Some(launch_error_message(&error)));
+                }
+            }
+        }
+
+        match commands::start_application(&self.adb_path, device_serial, process_name)? {
+            LaunchResult::Failed(error) => {
+                warn!("wired_connection: Starting {process_name} failed with {error:?}");
+
+                Ok(Some(launch_error_message(&error)))
+            }
+            _ => {
+                info!("wired_connection: Started {process_name} through its launcher intent");
+
+                Ok(None)
+            }
+        }
+    }
+
+    // Falls back to the default display if the configured one doesn't exist
+    fn launch_display(&self, device_serial: &str) -> Option<u32> {
+        let display_id = self.launch_display_id?;
+
+        match commands::list_displays(&self.adb_path, device_serial) {
+            Ok(displays) if !displays.iter().any(|display| display.id == display_id) => {
+                let ids = displays.iter().map(|d| d.id).collect::<Vec<_>>();
+                warn!(
+                    "wired_connection: Display {display_id} not found in {ids:?}, using the default"
+                );
+
+                None
+            }
+            Ok(_) => Some(display_id),
+            Err(failure) => {
+                warn!("wired_connection: list_displays failed with {}", failure);
+
+                Some(display_id)
+            }
+        }
+    }
+
+    // Other streaming apps can hold the VR runtime, which prevents the client from initializing.
+    // Only running apps are stopped, failures are not fatal.
+    fn stop_competing_apps(&self, device_serial: &str, process_name: &str, packages: &[String]) {
+        for package in packages.iter().filter(|p| *p != process_name) {
+            let res = commands::get_process_id(&self.adb_path, device_serial, package).and_then(
+                |process_id| {
+                    if process_id.is_some() {
+                        commands::force_stop_application(&self.adb_path, device_serial, package)?;
+                        info!("wired_connection: Stopped competing app {package}");
+                    }
+
+                    Ok(())
+                },
+            );
+            if let Err(failure) = res {
+                warn!(
+                    "wired_connection: stop_competing_apps failed with {}",
+                    failure
+                );
+            }
+        }
+    }
+
+    // Makes sure the launched activity can be resumed. Returns a status if the launch must wait
+    // for the user.
+    fn prepare_launch(&self, device_serial: &str) -> AdbResult<Option<WiredConnectionStatus>> {
+        match commands::is_screen_on(&self.adb_path, device_serial) {
+            Ok(true) => (),
+            Ok(false) => {
+                if !self.wake_screen(device_serial)? {
+                    return Ok(Some(WiredConnectionStatus::not_ready(
+                        WiredStatusKind::AwaitingUser,
+                        WiredMessage::HeadsetAsleepBeforeLaunch,
+                    )));
+                }
+            }
+            Err(failure) => {
+                warn!("wired_connection: is_screen_on failed with {}", failure);
+            }
+        }
+
+        match commands::get_keyguard_state(&self.adb_path, device_serial) {
+            Ok(KeyguardState { showing: false, .. }) => (),
+            Ok(KeyguardState { secure: true, .. }) => {
+                return Ok(Some(WiredConnectionStatus::not_ready(
+                    WiredStatusKind::AwaitingUser,
+                    WiredMessage::HeadsetLocked,
+                )));
+            }
+            Ok(KeyguardState { secure: false, .. }) => {
+                dbg_connection!("wired_connection: Dismissing lock screen");
+                commands::dismiss_keyguard(&self.adb_path, device_serial)?;
+
+                if commands::get_keyguard_state(&self.adb_path, device_serial)?.showing {
+                    return Ok(Some(WiredConnectionStatus::not_ready(
+                        WiredStatusKind::AwaitingUser,
+                        WiredMessage::LockScreenNotDismissed,
+                    )));
+                }
+            }
+            Err(failure) => {
+                warn!(
+                    "wired_connection: get_keyguard_state failed with {}",
+                    failure
+                );
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Returns false if the screen is still off after waiting, which happens on headsets that
+    // ignore the wake key event and only turn on the screen when the proximity sensor is covered
+    fn wake_screen(&self, device_serial: &str) -> AdbResult<bool> {
+        commands::wake_screen(&self.adb_path, device_serial)?;
+
+        let deadline = Instant::now() + SCREEN_WAKE_TIMEOUT;
+        while Instant::now() < deadline {
+            thread::sleep(SCREEN_WAKE_POLL_INTERVAL);
+            if commands::is_screen_on(&self.adb_path, device_serial)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Network devices drop when the headset sleeps or roams. The forwards are tied to the
+    // transport, so they are recreated by the next setup once the device is back.
+    fn reconnect_network_device(&mut self) -> Option<WiredConnectionStatus> {
+        let serial = self.network_device_serial.as_ref()?;
+
+        if self.reconnect_attempts >= MAX_RECONNECT_ATTEMPTS {
+            warn!("wired_connection: Giving up reconnecting to {serial}");
+            self.network_device_serial = None;
+            self.reconnect_attempts = 0;
+
+            return None;
+        }
+        self.reconnect_attempts += 1;
+
+        commands::disconnect(&self.adb_path, serial).ok();
+        if let Err(e) = commands::connect(&self.adb_path, serial) {
+            warn!("wired_connection: Reconnect failed with {e}");
+        }
+
+        Some(WiredConnectionStatus::not_ready(
+            WiredStatusKind::Reconnecting {
+                attempt: self.reconnect_attempts,
+            },
+            WiredMessage::Reconnecting {
+                serial: serial.clone(),
+                attempt: self.reconnect_attempts,
+                max_attempts: MAX_RECONNECT_ATTEMPTS,
+            },
+        ))
+    }
+}
+
+pub struct WiredStatusStream<'a> {
+    connection: &'a mut WiredConnection,
+    params: WiredSetupParams,
+    last_status: Option<WiredConnectionStatus>,
+    polled: bool,
+}
+
+impl Iterator for WiredStatusStream<'_> {
+    type Item = AdbResult<WiredConnectionStatus>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.polled {
+                thread::sleep(STATUS_POLL_INTERVAL);
+            }
+            self.polled = true;
+
+            match self.connection.setup_with_params(&self.params) {
+                Ok(status) => {
+                    if self.last_status.as_ref() != Some(&status) {
+                        self.last_status = Some(status.clone());
+
+                        return Some(Ok(status));
+                    }
+                }
+                Err(e) => {
+                    self.last_status = None;
+
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for WiredConnection {
+    // Also runs while unwinding from a panic in the connection loop, so the device settings are
+    // restored in that case too
+    fn drop(&mut self) {
+        // Before the server lease is released
+        self.other_devices.clear();
+
+        if self.torn_down {
+            return;
+        }
+
+        let stop_process_name = self
+            .client_process_name
+            .take()
+            .filter(|_| self.stop_client_on_disconnect && self.client_launched);
+        let stay_on_restore_value = self.stay_on_restore_value.take();
+        let applied_device_settings = mem::take(&mut self.applied_device_settings);
+        if let Some(device_serial) = self.device_serial.clone()
+            && (stop_process_name.is_some()
+                || stay_on_restore_value.is_some()
+                || applied_device_settings
+                    .iter()
+                    .any(|applied| !applied.failed))
+        {
+            // adb can hang on a device that is going away. Don't wait for it, killing the server
+            // below unblocks the thread.
+            let adb_path = self.adb_path.clone();
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                sender
+                    .send(teardown_device(
+                        &adb_path,
+                        &device_serial,
+                        stop_process_name.as_deref(),
+                        stay_on_restore_value,
+                        &applied_device_settings,
+                    ))
+                    .ok();
+            });
+
+            match receiver.recv_timeout(DEVICE_TEARDOWN_TIMEOUT) {
+                Ok(Ok(())) => (),
+                Ok(Err(failure)) => {
+                    warn!("wired_connection: teardown_device failed with {}", failure);
+                }
+                Err(_) => warn!("wired_connection: Timed out tearing down the device"),
+            }
+        }
+
+        // The server lease is dropped after this, which kills the server if no other connection
+        // uses it
+    }
+}
+
+// Does nothing if the device has been unplugged in the meantime
+fn teardown_device(
+    adb_path: &str,
+    device_serial: &str,
+    stop_process_name: Option<&str>,
+    stay_on_restore_value: Option<u32>,
+    applied_device_settings: &[AppliedDeviceSetting],
+) -> AdbResult<()> {
+    let device_connected = commands::list_devices(adb_path)?.into_iter().any(|d| {
+        d.serial.as_deref() == Some(device_serial) && d.state == Some(ConnectionState::Device)
+    });
+    if !device_connected {
+        return Ok(());
+    }
+
+    if let Some(value) = stay_on_restore_value {
+        commands::set_stay_on_while_plugged_in(adb_path, device_serial, value)?;
+    }
+
+    restore_device_settings(adb_path, device_serial, applied_device_settings);
+
+    if let Some(process_name) = stop_process_name {
+        dbg_connection!("wired_connection: Stopping {process_name}");
+        commands::force_stop_application(adb_path, device_serial, process_name)?;
+    }
+
+    Ok(())
+}
+
+// Returns the original value
+fn apply_device_setting(
+    adb_path: &str,
+    device_serial: &str,
+    setting: &WiredDeviceSetting,
+) -> AdbResult<Option<String>> {
+    let WiredDeviceSetting {
+        namespace,
+        key,
+        value,
+    } = setting;
+
+    match settings_namespace(*namespace) {
+        Some(namespace) => {
+            let original_value = commands::get_setting(adb_path, device_serial, namespace, key)?;
+            commands::put_setting(adb_path, device_serial, namespace, key, value)?;
+
+            Ok(original_value)
+        }
+        None => {
+            let original_value = commands::get_prop(adb_path, device_serial, key)?;
+            commands::set_prop(adb_path, device_serial, key, value)?;
+
+            Ok(Some(original_value))
+        }
+    }
+}
+
+// Keeps going on failure, the other settings should still be restored
+// Returns the failures, which are also logged
+fn restore_device_settings(
+    adb_path: &str,
+    device_serial: &str,
+    applied_device_settings: &[AppliedDeviceSetting],
+) -> Vec<String> {
+    let mut failures = vec![];
+    for applied in applied_device_settings.iter().filter(|a| !a.failed) {
+        let WiredDeviceSetting { namespace, key, .. } = &applied.setting;
+        let res = match (settings_namespace(*namespace), &applied.original_value) {
+            (Some(namespace), Some(value)) => {
+                commands::put_setting(adb_path, device_serial, namespace, key, value)
+            }
+            (Some(namespace), None) => {
+                commands::delete_setting(adb_path, device_serial, namespace, key)
+            }
+            (None, value) => commands::set_prop(
+                adb_path,
+                device_serial,
+                key,
+                value.as_deref().unwrap_or_default(),
+            ),
+        };
+        if let Err(failure) = res {
+            warn!(
+                "wired_connection: restore_device_settings failed with {:#}",
+                failure
+            );
+            failures.push(format!("{key}: {failure:#}"));
+        }
+    }
+
+    failures
+}
+
+// None for system properties
+fn settings_namespace(namespace: DeviceSettingNamespace) -> Option<&'static str> {
+    match namespace {
+        DeviceSettingNamespace::System => Some("system"),
+        DeviceSettingNamespace::Secure => Some("secure"),
+        DeviceSettingNamespace::Global => Some("global"),
+        DeviceSettingNamespace::Property => None,
+    }
+}
+
+pub fn get_process_name(
+    adb_path: &str,
+    device_serial: &str,
+    flavor: &ClientFlavor,
+) -> Option<String> {
+    process_name_candidates(flavor)
+        .iter()
+        .find(|name| {
+            commands::is_package_installed(adb_path, device_serial, name)
+                .is_ok_and(|installed| installed)
+        })
+        .map(|name| (*name).to_string())
+}
+
+// In order of preference
+fn process_name_candidates(flavor: &ClientFlavor) -> Vec<&str> {
+    match flavor {
+        ClientFlavor::Store => {
+            if alvr_common::is_stable() {
+                vec![PACKAGE_NAME_STORE, PACKAGE_NAME_GITHUB_STABLE]
+            } else {
+                vec![PACKAGE_NAME_GITHUB_DEV]
+            }
+        }
+        ClientFlavor::Github => {
+            if alvr_common::is_stable() {
+                vec![PACKAGE_NAME_GITHUB_STABLE, PACKAGE_NAME_STORE]
+            } else {
+                vec![PACKAGE_NAME_GITHUB_DEV]
+            }
+        }
+        ClientFlavor::Custom(name) => {
+            let name = split_custom_client(name).0;
+            if alvr_common::is_stable() {
+                vec![name, PACKAGE_NAME_STORE, PACKAGE_NAME_GITHUB_STABLE]
+            } else {
+                vec![name, PACKAGE_NAME_GITHUB_DEV]
+            }
+        }
+    }
+}
+
+// The uptime and the process start time are both measured from boot, which moves them to the
+// device clock
+fn process_start_local_secs(
+    clock: DeviceClock,
+    uptime: Duration,
+    process_start_time: Duration,
+) -> i64 {
+    let boot_time = clock.local_secs() - uptime.as_secs() as i64;
+
+    boot_time + process_start_time.as_secs() as i64
+}
+
+// The update time has a resolution of one second and the process is usually restarted right after
+// an update, so allow some slack
+fn is_process_outdated(process_start_secs: i64, package_update_secs: i64) -> bool {
+    package_update_secs > process_start_secs + OUTDATED_PROCESS_TOLERANCE_SECS
+}
+
+// Returns None for unknown packages, which should be started through their launcher intent
+pub fn get_activity_name<'a>(flavor: &'a ClientFlavor, process_name: &str) -> Option<&'a str> {
+    if let ClientFlavor::Custom(name) = flavor
+        && let (package, Some(activity_name)) = split_custom_client(name)
+        && package == process_name
+    {
+        return Some(activity_name);
+    }
+
+    match process_name {
+        PACKAGE_NAME_STORE => Some(ACTIVITY_NAME_STORE),
+        PACKAGE_NAME_GITHUB_DEV => Some(ACTIVITY_NAME_GITHUB_DEV),
+        PACKAGE_NAME_GITHUB_STABLE => Some(ACTIVITY_NAME_GITHUB_STABLE),
+        _ => None,
+    }
+}
+
+// The custom client can override the activity with the "<package>/<activity>" syntax of
+// `am start -n`
+fn split_custom_client(name: &str) -> (&str, Option<&str>) {
+    match name.split_once('/') {
+        Some((package, activity_name)) => (package, Some(activity_name)),
+        None => (name, None),
+    }
+}
+
+fn recent_crash(
+    adb_path: &str,
+    device_serial: &str,
+    process_name: &str,
+) -> AdbResult<Option<CrashReport>> {
+    let Some(crash) = commands::last_crash(adb_path, device_serial, process_name)? else {
+        return Ok(None);
+    };
+    let now = commands::get_device_clock(adb_path, device_serial)?.local_secs();
+
+    Ok((crash.time >= now - MAX_CRASH_AGE_SECS).then_some(crash))
+}
+
+fn crash_description(crash: &CrashReport) -> String {
+    match crash.kind {
+        CrashKind::Crash => format!("crashed: {}", crash.summary),
+        CrashKind::NativeCrash => format!("crashed in native code: {}", crash.summary),
+        CrashKind::Anr => format!("stopped responding: {}", crash.summary),
+    }
+}
+
+fn launch_error_message(error: &LaunchError) -> WiredMessage {
+    match error {
+        LaunchError::PermissionDenied => WiredMessage::LaunchPermissionDenied,
+        LaunchError::ActivityNotFound => WiredMessage::ActivityNotFound,
+        LaunchError::PackageNotFound => WiredMessage::PackageNotFound,
+        LaunchError::UserRestricted => WiredMessage::UserRestricted,
+        LaunchError::Unknown(message) => WiredMessage::LaunchFailed {
+            error: message.clone(),
+        },
+    }
+}
+
+// Failures reported by `am` replace the status of the launch
+fn launch_failure_status(
+    failure: Option<WiredMessage>,
+    kind: WiredStatusKind,
+    message: WiredMessage,
+) -> WiredConnectionStatus {
+    match failure {
+        Some(failure) => WiredConnectionStatus::not_ready(WiredStatusKind::LaunchFailed, failure),
+        None => WiredConnectionStatus::not_ready(kind, message),
+    }
+}
+
+// Installed but disabled, e.g. with `pm disable` or by a launcher
+fn client_disabled_status() -> WiredConnectionStatus {
+    WiredConnectionStatus::not_ready(
+        WiredStatusKind::ClientDisabled,
+        WiredMessage::ClientDisabled,
+    )
+}
+
+fn device_state_kind(state: Option<ConnectionState>) -> WiredStatusKind {
+    match state {
+        Some(ConnectionState::Unauthorized | ConnectionState::Authorizing) => {
+            WiredStatusKind::Unauthorized
+        }
+        Some(ConnectionState::NoPermissions) => WiredStatusKind::NoPermissions,
+        _ => WiredStatusKind::DeviceUnavailable,
+    }
+}
+
+fn device_state_message(state: Option<ConnectionState>) -> WiredMessage {
+    match state {
+        Some(ConnectionState::Unauthorized) => WiredMessage::Unauthorized,
+        Some(ConnectionState::Authorizing) => WiredMessage::Authorizing,
+        Some(ConnectionState::Connecting) => WiredMessage::Connecting,
+        Some(ConnectionState::Offline) => WiredMessage::Offline,
+        Some(ConnectionState::NoPermissions) => WiredMessage::NoPermissions { vendor_id: None },
+        Some(state) => WiredMessage::DeviceNotReady { state },
+        None => WiredMessage::DeviceStateUnknown,
+    }
+}
+
+// On Linux, adb can only open the USB devices that a udev rule gives access to
+fn no_permissions_message(device: &Device) -> WiredMessage {
+    WiredMessage::NoPermissions {
+        vendor_id: device
+            .attributes
+            .get("usb")
+            .and_then(|path| usb_vendor_id(path)),
+    }
+}
+
+// `usb_path` is the sysfs name of the device printed by `adb devices -l`, e.g. "1-1"
+#[cfg(target_os = "linux")]
+fn usb_vendor_id(usb_path: &str) -> Option<String> {
+    let vendor_id =
+        std::fs::read_to_string(format!("/sys/bus/usb/devices/{usb_path}/idVendor")).ok()?;
+
+    Some(vendor_id.trim().to_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn usb_vendor_id(_: &str) -> Option<String> {
+    None
+}
+
+// Headsets whose OpenXR runtime is part of the system, by the model printed by `adb devices -l`
+fn has_system_openxr_runtime(model: &str) -> bool {
+    ["Quest", "Oculus_Quest", "Meta_Quest"]
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+}
+
+fn is_network_serial(serial: &str) -> bool {
+    serial.parse::<SocketAddr>().is_ok()
+}
+
+// Parameters of a `setup` and what its phases found so far, passed from one phase to the next
+struct SetupTick<'a> {
+    control_port: u16,
+    stream_port: u16,
+    client_type: &'a ClientFlavor,
+    allow_background_client: bool,
+    keep_display_awake: bool,
+    client_autolaunch: Option<WiredClientAutoLaunchConfig>,
+    client_not_responding_since: Option<Instant>,
+    // Set by the device lookup
+    device: Option<Device>,
+    device_serial: String,
+    device_state: Option<ConnectionState>,
+    // Set once the client is found
+    process_name: String,
+    client_process_id: Option<usize>,
+    // Set once the launch delays expired
+    launch_step: Option<LaunchStep>,
+}
+
+// Outcome of a setup phase
+enum SetupTransition {
+    // The phase is complete, the setup goes on with this one
+    Next(WiredSetupState),
+    // The phase is not complete, the setup returns this status
+    Stop(WiredConnectionStatus),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum LaunchStep {
+    WaitForDevice(Duration),
+    WaitForClient,
+    GiveUp,
+    Launch,
+}
+
+// Decides what to do with a client that is not running. All delays are measured against the
+// same `now`, so the outcome only depends on how much time passed and not on how often `setup`
+// is polled: a late poll moves on to the next step instead of missing it.
+fn launch_step(
+    config: &WiredClientAutoLaunchConfig,
+    device_found_time: Instant,
+    launch_time: Option<Instant>,
+    launch_attempts: u32,
+    now: Instant,
+) -> LaunchStep {
+    let pre_launch_delay = launch_delay(config.pre_launch_delay);
+    let device_found_elapsed = now.saturating_duration_since(device_found_time);
+    if device_found_elapsed < pre_launch_delay {
+        return LaunchStep::WaitForDevice(pre_launch_delay - device_found_elapsed);
+    }
+
+    // The launch can silently have no effect, for example if monkey fails. Retry with an
+    // exponential backoff until the client process appears.
+    if let Some(launch_time) = launch_time {
+        let retry_delay = launch_retry_delay(config.post_launch_delay, launch_attempts);
+        if now.saturating_duration_since(launch_time) < retry_delay {
+            return LaunchStep::WaitForClient;
+        }
+
+        if launch_attempts >= config.max_launch_attempts.max(1) {
+            return LaunchStep::GiveUp;
+        }
+    }
+
+    LaunchStep::Launch
+}
+
+fn launch_step_setup_state(step: &LaunchStep) -> WiredSetupState {
+    match step {
+        LaunchStep::WaitForDevice(_) => WiredSetupState::AwaitingLaunchDelay,
+        LaunchStep::WaitForClient | LaunchStep::Launch => WiredSetupState::Launching,
+        LaunchStep::GiveUp => WiredSetupState::LaunchFailed,
+    }
+}
+
+fn launch_delay(seconds: u32) -> Duration {
+    Duration::from_secs(seconds.into()).min(MAX_LAUNCH_DELAY)
+}
+
+fn launch_retry_delay(post_launch_delay: u32, launch_attempts: u32) -> Duration {
+    let base_delay = launch_delay(post_launch_delay).max(MIN_LAUNCH_RETRY_DELAY);
+
+    base_delay
+        .saturating_mul(2_u32.saturating_pow(launch_attempts.saturating_sub(1)))
+        .min(MAX_LAUNCH_DELAY)
+}
+
+fn remaining_secs(remaining: Duration) -> u64 {
+    remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ImportanceLevel;
+    use alvr_session::settings_schema::Switch;
+
+    #[test]
+    fn test_launch_step_with_sparse_polling() {
+        let config = WiredClientAutoLaunchConfig {
+            pre_launch_delay: 5,
+            ..test_autolaunch_config()
+        };
+        let hour = Duration::from_secs(3600);
+        let device_found_time = Instant::now();
+
+        assert_eq!(
+            launch_step(&config, device_found_time, None, 0, device_found_time),
+            LaunchStep::WaitForDevice(Duration::from_secs(5))
+        );
+
+        // Each poll comes long after every delay expired, so every one of them advances by a step
+        let mut launch_time = None;
+        let mut launch_attempts = 0;
+        for poll in 1..=3 {
+            let now = device_found_time + hour * poll;
+            assert_eq!(
+                launch_step(
+                    &config,
+                    device_found_time,
+                    launch_time,
+                    launch_attempts,
+                    now
+                ),
+                LaunchStep::Launch
+            );
+            launch_time = Some(now);
+            launch_attempts += 1;
+
+            assert_eq!(
+                launch_step(
+                    &config,
+                    device_found_time,
+                    launch_time,
+                    launch_attempts,
+                    now + Duration::from_secs(1)
+                ),
+                LaunchStep::WaitForClient
+            );
+        }
+
+        assert_eq!(
+            launch_step(
+                &config,
+                device_found_time,
+                launch_time,
+                launch_attempts,
+                device_found_time + hour * 4
+            ),
+            LaunchStep::GiveUp
+        );
+    }
+
+    #[test]
+    fn test_launch_failure_status() {
+        let status = launch_failure_status(
+            Some(WiredMessage::LaunchPermissionDenied),
+            WiredStatusKind::Launching,
+            WiredMessage::StartingClient,
+        );
+        assert_eq!(
+            status,
+            WiredConnectionStatus::not_ready(
+                WiredStatusKind::LaunchFailed,
+                WiredMessage::LaunchPermissionDenied
+            )
+        );
+        assert_eq!(
+            status.to_string(),
+            "ALVR client activity cannot be started, check the wired client type"
+        );
+
+        assert_eq!(
+            launch_failure_status(
+                None,
+                WiredStatusKind::Launching,
+                WiredMessage::StartingClient
+            ),
+            WiredConnectionStatus::not_ready(
+                WiredStatusKind::Launching,
+                WiredMessage::StartingClient
+            )
+        );
+    }
+
+    #[test]
+    fn test_launch_setup_states() {
+        let config = WiredClientAutoLaunchConfig {
+            pre_launch_delay: 5,
+            max_launch_attempts: 1,
+            ..test_autolaunch_config()
+        };
+        let device_found_time = Instant::now();
+        let launch_time = device_found_time + Duration::from_secs(5);
+
+        // (seconds since the device was found, launch issued, expected state)
+        let transitions = [
+            (0, false, WiredSetupState::AwaitingLaunchDelay),
+            (4, false, WiredSetupState::AwaitingLaunchDelay),
+            (5, false, WiredSetupState::Launching),
+            (6, true, WiredSetupState::Launching),
+            (15, true, WiredSetupState::LaunchFailed),
+        ];
+        for (secs, launched, state) in transitions {
+            let step = launch_step(
+                &config,
+                device_found_time,
+                launched.then_some(launch_time),
+                launched.into(),
+                device_found_time + Duration::from_secs(secs),
+            );
+            assert_eq!(launch_step_setup_state(&step), state, "after {secs}s");
+        }
+
+        assert!(WiredSetupState::NoDevice < WiredSetupState::Launching);
+        assert!(WiredSetupState::AwaitingResume < WiredSetupState::Ready);
+    }
+
+    const MOCK_SERIAL: &str = "1WMHH000000000";
+
+    // Launches without delay and stops retrying after 3 attempts. Tests override the fields they
+    // depend on.
+    fn test_autolaunch_config() -> WiredClientAutoLaunchConfig {
+        WiredClientAutoLaunchConfig {
+            boot_delay: 0,
+            pre_launch_delay: 0,
+            post_launch_delay: 10,
+            max_launch_attempts: 3,
+            unresponsive_restart_delay: Switch::Disabled,
+            stop_client_on_disconnect: false,
+            restart_outdated_client: false,
+            stop_competing_apps: Switch::Disabled,
+            foreground_paused_client: Switch::Disabled,
+            launch_display: Switch::Disabled,
+        }
+    }
+
+    // Connection pinned to the mocked device. Pinning also keeps it from killing a real server.
+    fn mock_connection(mock: &Arc<MockTransport>) -> WiredConnection {
+        let mut connection = WiredConnection::with_adb_path(
+            "adb".to_owned(),
+            AdbSource::UserProvided,
+            Some(MOCK_SERIAL.to_owned()),
+        );
+        connection.set_transport(Arc::clone(mock) as Arc<dyn AdbTransport>);
+
+        connection
+    }
+
+    // Authorized and booted device with the client installed, but not running
+    fn mock_ready_device() -> Arc<MockTransport> {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(
+            &["devices"],
+            &format!("List of devices attached\n{MOCK_SERIAL} device usb:1-1 model:Quest_3\n"),
+        );
+        mock.respond(&["get-state"], "device\n");
+        mock.respond(&["getprop", "sys.boot_completed"], "1\n");
+        mock.respond(&["pm", "list", "package"], "package:com.example.client\n");
+
+        mock
+    }
+
+    fn mock_setup(
+        connection: &mut WiredConnection,
+        client_autolaunch: Option<WiredClientAutoLaunchConfig>,
+    ) -> WiredConnectionStatus {
+        connection
+            .run_setup(
+                9943,
+                9944,
+                &ClientFlavor::Custom("com.example.client".to_owned()),
+                false,
+                false,
+                client_autolaunch,
+            )
+            .unwrap()
+    }
+
+    fn status_kind(status: &WiredConnectionStatus) -> Option<WiredStatusKind> {
+        match status {
+            WiredConnectionStatus::Ready(_) => None,
+            WiredConnectionStatus::NotReady { kind, .. } => Some(*kind),
+        }
+    }
+
+    #[test]
+    fn test_setup_no_device() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(
+            &["devices"],
+            "List of devices attached\nOTHER000000000 device usb:1-2\n\n",
+        );
+        let mut connection = mock_connection(&mock);
+
+        let status = mock_setup(&mut connection, None);
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::NoDevice));
+        assert_eq!(connection.setup_state(), WiredSetupState::NoDevice);
+        assert!(!mock.was_called(&["forward"]));
+    }
+
+    #[test]
+    fn test_setup_unauthorized() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(
+            &["devices"],
+            &format!("List of devices attached\n{MOCK_SERIAL} unauthorized usb:1-1\n"),
+        );
+        let mut connection = mock_connection(&mock);
+
+        let status = mock_setup(&mut connection, None);
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::Unauthorized));
+        assert_eq!(
+            connection.setup_state(),
+            WiredSetupState::DeviceUnauthorized
+        );
+        assert!(!mock.was_called(&["forward"]));
+    }
+
+    #[test]
+    fn test_setup_paused_client() {
+        let mock = mock_ready_device();
+        mock.respond(&["pidof"], "1234\n");
+        mock.respond(
+            &["dumpsys", "activity", "activities"],
+            "  mResumedActivity: ActivityRecord{1a2b u0 com.oculus.vrshell/.MainActivity t1}\n",
+        );
+        mock.respond(&["dumpsys", "power"], "  mWakefulness=Awake\n");
+        let mut connection = mock_connection(&mock);
+
+        let status = mock_setup(&mut connection, None);
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::ClientPaused));
+        assert_eq!(connection.setup_state(), WiredSetupState::AwaitingResume);
+        assert!(mock.was_called(&["forward", "tcp:9943", "tcp:9943"]));
+        assert!(mock.was_called(&["forward", "tcp:9944", "tcp:9944"]));
+
+        // The headset went to sleep
+        mock.respond(&["dumpsys", "power"], "  mWakefulness=Asleep\n");
+        let status = mock_setup(&mut connection, None);
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::HeadsetAsleep));
+    }
+
+    #[test]
+    fn test_setup_autolaunch_delay() {
+        let config = WiredClientAutoLaunchConfig {
+            pre_launch_delay: 60,
+            ..test_autolaunch_config()
+        };
+        let mock = mock_ready_device();
+        let mut connection = mock_connection(&mock);
+
+        let status = mock_setup(&mut connection, Some(config.clone()));
+
+        assert!(matches!(
+            status_kind(&status),
+            Some(WiredStatusKind::AwaitingLaunchDelay {
+                remaining_secs: 59..=60
+            })
+        ));
+        assert_eq!(
+            connection.setup_state(),
+            WiredSetupState::AwaitingLaunchDelay
+        );
+
+        // Without auto-launch the client is left stopped
+        let status = mock_setup(&mut connection, None);
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::ClientStopped));
+        assert_eq!(connection.setup_state(), WiredSetupState::ClientStopped);
+        assert!(!mock.was_called(&["monkey"]));
+        assert!(!mock.was_called(&["am", "start"]));
+    }
+
+    #[test]
+    fn test_setup_transitions() {
+        let boot_delay_config = WiredClientAutoLaunchConfig {
+            boot_delay: 60,
+            ..test_autolaunch_config()
+        };
+        fn running_client(mock: &MockTransport, resumed_activity: &str) {
+            mock.respond(&["pidof"], "1234\n");
+            mock.respond(
+                &["dumpsys", "activity", "activities"],
+                &format!("  mResumedActivity: ActivityRecord{{1a2b u0 {resumed_activity} t1}}\n"),
+            );
+            mock.respond(&["dumpsys", "power"], "  mWakefulness=Awake\n");
+        }
+
+        // (device, auto-launch, state the setup stops at, status)
+        let transitions: [(fn(&MockTransport), _, _, _); 9] = [
+            (
+                |mock| mock.respond(&["devices"], "List of devices attached\n"),
+                None,
+                WiredSetupState::NoDevice,
+                Some(WiredStatusKind::NoDevice),
+            ),
+            (
+                |mock| {
+                    mock.respond(
+                        &["devices"],
+                        &format!("List of devices attached\n{MOCK_SERIAL} unauthorized usb:1-1\n"),
+                    )
+                },
+                None,
+                WiredSetupState::DeviceUnauthorized,
+                Some(WiredStatusKind::Unauthorized),
+            ),
+            (
+                |mock| mock.respond(&["getprop", "sys.boot_completed"], "0\n"),
+                None,
+                WiredSetupState::Booting,
+                Some(WiredStatusKind::Booting),
+            ),
+            (
+                |mock| mock.respond(&["pm", "list", "package"], ""),
+                None,
+                WiredSetupState::VerifyingClient,
+                Some(WiredStatusKind::ClientNotInstalled),
+            ),
+            (
+                |_| (),
+                None,
+                WiredSetupState::ClientStopped,
+                Some(WiredStatusKind::ClientStopped),
+            ),
+            (
+                |mock| mock.respond(&["cat", "/proc/uptime"], "15.00 40.00\n"),
+                Some(boot_delay_config),
+                WiredSetupState::AwaitingLaunchDelay,
+                Some(WiredStatusKind::AwaitingLaunchDelay { remaining_secs: 45 }),
+            ),
+            (
+                |_| (),
+                Some(test_autolaunch_config()),
+                WiredSetupState::Launching,
+                Some(WiredStatusKind::Launching),
+            ),
+            (
+                |mock| running_client(mock, "com.oculus.vrshell/.MainActivity"),
+                None,
+                WiredSetupState::AwaitingResume,
+                Some(WiredStatusKind::ClientPaused),
+            ),
+            (
+                |mock| running_client(mock, "com.example.client/.MainActivity"),
+                None,
+                WiredSetupState::Ready,
+                None,
+            ),
+        ];
+        for (prepare_device, autolaunch, state, kind) in transitions {
+            let mock = mock_ready_device();
+            prepare_device(&mock);
+            let mut connection = mock_connection(&mock);
+
+            let status = mock_setup(&mut connection, autolaunch);
+
+            assert_eq!(connection.setup_state(), state);
+            assert_eq!(status_kind(&status), kind, "in {state:?}");
+        }
+
+        // The launch did not bring up the client before the retry delay expired
+        let config = WiredClientAutoLaunchConfig {
+            post_launch_delay: 0,
+            max_launch_attempts: 1,
+            ..test_autolaunch_config()
+        };
+        let mock = mock_ready_device();
+        let mut connection = mock_connection(&mock);
+        mock_setup(&mut connection, Some(config.clone()));
+        connection.launch_time = connection
+            .launch_time
+            .map(|launch_time| launch_time - MIN_LAUNCH_RETRY_DELAY);
+
+        let status = mock_setup(&mut connection, Some(config));
+
+        assert_eq!(connection.setup_state(), WiredSetupState::LaunchFailed);
+        assert_eq!(
+            status_kind(&status),
+            Some(WiredStatusKind::LaunchGaveUp { attempts: 1 })
+        );
+    }
+
+    #[test]
+    fn test_restore_timing_state() {
+        let config = WiredClientAutoLaunchConfig {
+            pre_launch_delay: 15,
+            ..test_autolaunch_config()
+        };
+        let mock = mock_ready_device();
+        let mut connection = mock_connection(&mock);
+        mock_setup(&mut connection, Some(config.clone()));
+        let state = connection.timing_state().unwrap();
+        assert_eq!(state.device_serial, MOCK_SERIAL);
+        drop(connection);
+
+        // State of another headset
+        let mut connection = mock_connection(&mock);
+        connection.restore_timing_state(WiredTimingState {
+            device_serial: "2G0YC000000000".to_owned(),
+            since_device_found: Duration::from_secs(20),
+            ..state.clone()
+        });
+        let status = mock_setup(&mut connection, Some(config.clone()));
+
+        assert!(matches!(
+            status_kind(&status),
+            Some(WiredStatusKind::AwaitingLaunchDelay { .. })
+        ));
+        drop(connection);
+
+        // The old connection already waited for the delay
+        let mut connection = mock_connection(&mock);
+        connection.restore_timing_state(WiredTimingState {
+            since_device_found: Duration::from_secs(20),
+            ..state
+        });
+        let status = mock_setup(&mut connection, Some(config));
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::Launching));
+        assert_eq!(connection.setup_state(), WiredSetupState::Launching);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mock = mock_ready_device();
+        mock.respond(&["pidof"], "1234\n");
+        mock.respond(&["dumpsys", "package"], "    versionName=20.11.0\n");
+        mock.respond(
+            &["dumpsys", "activity", "processes"],
+            "    Proc # 9: cch+2  B/ /CE   trm: 0 1234:com.example.client/u0a124 (cch-empty)\n",
+        );
+        let mut connection = mock_connection(&mock);
+
+        mock_setup(&mut connection, None);
+        let calls = mock.calls().len();
+        let diagnostics = connection.diagnostics();
+
+        assert_eq!(mock.calls().len(), calls);
+        assert_eq!(diagnostics.device_serial.as_deref(), Some(MOCK_SERIAL));
+        assert_eq!(diagnostics.device_model.as_deref(), Some("Quest_3"));
+        assert_eq!(diagnostics.device_state, Some(ConnectionState::Device));
+        assert_eq!(diagnostics.forwarded_ports, [9943, 9944]);
+        assert_eq!(
+            diagnostics.client_package.as_deref(),
+            Some("com.example.client")
+        );
+        assert_eq!(diagnostics.client_version.as_deref(), Some("20.11.0"));
+        assert!(diagnostics.client_running);
+        // The client is paused, nothing is resumed
+        assert_eq!(
+            diagnostics.client_importance,
+            Some(ProcessImportance {
+                level: ImportanceLevel::Cached,
+                reason: Some("cch-empty".to_owned()),
+            })
+        );
+        assert_eq!(diagnostics.setup_state, connection.setup_state());
+        assert_eq!(diagnostics.last_error, None);
+
+        // The device was unplugged
+        mock.respond(&["devices"], "List of devices attached\n");
+        connection.set_device_lost_grace(Duration::ZERO);
+        connection.force_refresh();
+        mock_setup(&mut connection, None);
+        let diagnostics = connection.diagnostics();
+
+        assert_eq!(diagnostics.device_serial, None);
+        assert_eq!(diagnostics.device_state, None);
+        assert_eq!(diagnostics.client_importance, None);
+        assert_eq!(diagnostics.setup_state, WiredSetupState::NoDevice);
+    }
+
+    #[test]
+    fn test_select_device() {
+        let other_serial = "2G0000000000";
+        let mock = mock_ready_device();
+        mock.respond(
+            &["devices"],
+            &format!(
+                "List of devices attached\n{MOCK_SERIAL} device usb:1-1 model:Quest_3\n{other_serial} device usb:1-2 model:Quest_3\n"
+            ),
+        );
+        let mut connection = mock_connection(&mock);
+        assert!(connection.select_device(other_serial).is_err());
+        connection.device_watcher.device_filter = None;
+
+        mock_setup(&mut connection, None);
+        assert_eq!(connection.device_serial.as_deref(), Some(MOCK_SERIAL));
+        assert_eq!(connection.pinned_device_serial(), None);
+
+        // Kept even though adb lists the other device first
+        connection.select_device(other_serial).unwrap();
+        mock_setup(&mut connection, None);
+        assert_eq!(connection.device_serial.as_deref(), Some(other_serial));
+        assert_eq!(
+            connection.diagnostics().pinned_device_serial.as_deref(),
+            Some(other_serial)
+        );
+
+        // Selected again once it is unplugged
+        mock.respond(
+            &["devices"],
+            &format!("List of devices attached\n{MOCK_SERIAL} device usb:1-1 model:Quest_3\n"),
+        );
+        connection.set_device_lost_grace(Duration::ZERO);
+        connection.force_refresh();
+        let status = mock_setup(&mut connection, None);
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::NoDevice));
+        assert_eq!(connection.pinned_device_serial(), None);
+        mock_setup(&mut connection, None);
+        assert_eq!(connection.device_serial.as_deref(), Some(MOCK_SERIAL));
+    }
+
+    #[test]
+    fn test_setup_prefers_usb_transport() {
+        let network_serial = "192.168.1.20:5555";
+        let mock = mock_ready_device();
+        mock.respond(
+            &["devices"],
+            &format!(
+                "List of devices attached\n{network_serial} device model:Quest_3\n{MOCK_SERIAL} device usb:1-1 model:Quest_3\n"
+            ),
+        );
+        mock.respond(&["settings", "get", "secure", "android_id"], "a1b2c3d4\n");
+        let mut connection = mock_connection(&mock);
+        connection.device_watcher.device_filter = Some(network_serial.to_owned());
+
+        mock_setup(&mut connection, None);
+        let diagnostics = connection.diagnostics();
+
+        assert_eq!(diagnostics.device_serial.as_deref(), Some(MOCK_SERIAL));
+        assert_eq!(
+            diagnostics.stream_device_serial.as_deref(),
+            Some(network_serial)
+        );
+        assert!(mock.was_called(&["-s", MOCK_SERIAL, "shell", "pidof"]));
+        assert!(mock.was_called(&["-s", network_serial, "forward"]));
+        assert!(!mock.was_called(&["-s", MOCK_SERIAL, "forward"]));
+
+        // Another headset is connected with the cable
+        mock.respond(
+            &[
+                "-s",
+                MOCK_SERIAL,
+                "shell",
+                "settings",
+                "get",
+                "secure",
+                "android_id",
+            ],
+            "e5f6a7b8\n",
+        );
+        connection.android_ids.clear();
+        connection.force_refresh();
+        mock_setup(&mut connection, None);
+        let diagnostics = connection.diagnostics();
+
+        assert_eq!(diagnostics.device_serial.as_deref(), Some(network_serial));
+        assert_eq!(diagnostics.stream_device_serial, None);
+    }
+
+    #[test]
+    fn test_setup_disabled_client() {
+        let mock = mock_ready_device();
+        mock.respond(
+            &["pm", "list", "packages", "-d"],
+            "package:com.example.client\n",
+        );
+        let mut connection = mock_connection(&mock);
+
+        let status = mock_setup(&mut connection, None);
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::ClientDisabled));
+        assert_eq!(connection.setup_state(), WiredSetupState::ClientStopped);
+
+        mock.respond(&["pm", "list", "packages", "-d"], "");
+        let status = mock_setup(&mut connection, None);
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::ClientStopped));
+    }
+
+    #[test]
+    fn test_setup_grants_permissions() {
+        let mock = mock_ready_device();
+        mock.respond(
+            &["dumpsys", "package", "com.example.client"],
+            "      runtime permissions:\n        android.permission.RECORD_AUDIO: granted=false\n        android.permission.POST_NOTIFICATIONS: granted=true\n",
+        );
+        let mut connection = mock_connection(&mock);
+        connection.client_permissions = vec![
+            "android.permission.RECORD_AUDIO".to_owned(),
+            "android.permission.POST_NOTIFICATIONS".to_owned(),
+        ];
+
+        mock_setup(&mut connection, None);
+
+        assert!(mock.was_called(&[
+            "pm",
+            "grant",
+            "com.example.client",
+            "android.permission.RECORD_AUDIO"
+        ]));
+        assert!(!mock.was_called(&["android.permission.POST_NOTIFICATIONS"]));
+    }
+
+    #[test]
+    fn test_setup_pre_launch_shell() {
+        let config = test_autolaunch_config();
+        let mock = mock_ready_device();
+        mock.respond_with(
+            &["sh", "-c"],
+            MockResponse {
+                stderr: "setprop: failed to set property\n".to_owned(),
+                exit_code: 1,
+                ..Default::default()
+            },
+        );
+        let mut connection = mock_connection(&mock);
+        let params = WiredSetupParams::new(9943, 9944)
+            .client_type(ClientFlavor::Custom("com.example.client".to_owned()))
+            .autolaunch(Some(config))
+            .pre_launch_shell(vec!["setprop debug.example 1".to_owned()]);
+
+        let status = connection.setup_with_params(&params).unwrap();
+
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::LaunchFailed));
+        assert_eq!(
+            status.to_string(),
+            "Pre-launch command `setprop debug.example 1` failed: setprop: failed to set property"
+        );
+        assert!(mock.was_called(&["sh", "-c", "'setprop debug.example 1'"]));
+        assert!(!mock.was_called(&["monkey"]));
+        assert!(!mock.was_called(&["am", "start"]));
+    }
+
+    #[test]
+    fn test_setup_device_lost_grace() {
+        let config = WiredClientAutoLaunchConfig {
+            pre_launch_delay: 60,
+            ..test_autolaunch_config()
+        };
+        let devices = format!("List of devices attached\n{MOCK_SERIAL} device usb:1-1\n");
+        let mock = mock_ready_device();
+        let mut connection = mock_connection(&mock);
+        connection.set_device_lost_grace(Duration::from_secs(3600));
+
+        mock_setup(&mut connection, Some(config.clone()));
+        let device_found_time = connection.device_found_time;
+
+        // The device vanishes during the grace period, the launch delay keeps running. The device
+        // list is cached, the failed commands would clear it.
+        mock.respond(&["devices"], "List of devices attached\n\n");
+        connection.force_refresh();
+        let status = mock_setup(&mut connection, Some(config.clone()));
+        assert_eq!(
+            status_kind(&status),
+            Some(WiredStatusKind::DeviceReconnecting)
+        );
+        assert_eq!(
+            connection.setup_state(),
+            WiredSetupState::AwaitingLaunchDelay
+        );
+
+        mock.respond(&["devices"], &devices);
+        let status = mock_setup(&mut connection, Some(config.clone()));
+        assert!(matches!(
+            status_kind(&status),
+            Some(WiredStatusKind::AwaitingLaunchDelay { .. })
+        ));
+        assert_eq!(connection.device_found_time, device_found_time);
+
+        // Once the grace period expired the device is lost, the delay starts over when it is back
+        connection.set_device_lost_grace(Duration::ZERO);
+        mock.respond(&["devices"], "List of devices attached\n\n");
+        connection.force_refresh();
+        let status = mock_setup(&mut connection, Some(config.clone()));
+        assert_eq!(status_kind(&status), Some(WiredStatusKind::NoDevice));
+
+        mock.respond(&["devices"], &devices);
+        mock_setup(&mut connection, Some(config));
+        assert!(connection.device_found_time > device_found_time);
+    }
+
+    #[test]
+    fn test_setup_min_tick_interval() {
+        let mock = mock_ready_device();
+        let mut connection = mock_connection(&mock);
+        connection.set_min_tick_interval(Duration::from_secs(3600));
+
+        let status = mock_setup(&mut connection, None);
+        let calls = mock.calls().len();
+        assert_eq!(mock_setup(&mut connection, None), status);
+        assert_eq!(mock.calls().len(), calls);
+
+        connection.set_min_tick_interval(Duration::ZERO);
+        mock_setup(&mut connection, None);
+        assert!(mock.calls().len() > calls);
+    }
+
+    #[test]
+    fn test_check_schedule() {
+        let mut schedule = CheckSchedule::default();
+        let interval = Duration::from_secs(3600);
+
+        assert!(schedule.run_if_due(ScheduledCheck::ClientOutdated, interval));
+        assert!(!schedule.run_if_due(ScheduledCheck::ClientOutdated, interval));
+        assert!(schedule.run_if_due(ScheduledCheck::ClientOutdated, Duration::ZERO));
+        assert!(schedule.run_if_due(ScheduledCheck::ClientNotResponding, interval));
+
+        schedule.clear();
+        assert!(schedule.run_if_due(ScheduledCheck::ClientOutdated, interval));
+    }
+
+    #[test]
+    fn test_setup_all() {
+        let mock = mock_ready_device();
+        mock.respond(
+            &["devices"],
+            &format!(
+                "List of devices attached\n{MOCK_SERIAL} device usb:1-1 model:Quest_3\n2G0000000000 device usb:1-2 model:Quest_2\n"
+            ),
+        );
+        let mut connection = mock_connection(&mock);
+        connection.set_setup_params(WiredSetupParams {
+            client_type: ClientFlavor::Custom("com.example.client".to_owned()),
+            ..Default::default()
+        });
+
+        let statuses = connection.setup_all().unwrap();
+
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(
+            status_kind(&statuses[MOCK_SERIAL]),
+            Some(WiredStatusKind::ClientStopped)
+        );
+        assert_eq!(
+            status_kind(&statuses["2G0000000000"]),
+            Some(WiredStatusKind::ClientStopped)
+        );
+        // Only the primary device gets the ports
+        assert!(mock.was_called(&["-s", MOCK_SERIAL, "forward"]));
+        assert!(!mock.was_called(&["-s", "2G0000000000", "forward"]));
+        assert!(mock.was_called(&["-s", "2G0000000000", "shell", "pidof"]));
+    }
+
+    #[test]
+    fn test_status_is_same_state() {
+        let delay = |remaining_secs| {
+            WiredConnectionStatus::not_ready(
+                WiredStatusKind::AwaitingLaunchDelay { remaining_secs },
+                WiredMessage::AwaitingLaunchDelay {
+                    seconds_remaining: remaining_secs,
+                },
+            )
+        };
+
+        assert!(delay(5).is_same_state(&delay(4)));
+        assert!(!delay(5).is_same_state(&WiredConnectionStatus::not_ready(
+            WiredStatusKind::Launching,
+            WiredMessage::StartingClient
+        )));
+        assert!(
+            !delay(5).is_same_state(&WiredConnectionStatus::Ready(WiredSetupTimings::default()))
+        );
+        assert!(
+            WiredConnectionStatus::Ready(WiredSetupTimings::default())
+                .is_same_state(&WiredConnectionStatus::Ready(WiredSetupTimings::default()))
+        );
+    }
+
+    #[test]
+    fn test_no_permissions_message() {
+        let device = parse::parse_device(
+            "(no serial number)     no permissions (missing udev rules? user is in the plugdev group); see [http://developer.android.com/tools/device.html] usb:255-255",
+        )
+        .unwrap();
+        assert_eq!(device.serial, None);
+        assert!(
+            no_permissions_message(&device)
+                .to_string()
+                .contains("udev rules")
+        );
+    }
+
+    #[test]
+    fn test_get_activity_name() {
+        let custom = ClientFlavor::Custom("my.client/.VrActivity".to_owned());
+        assert_eq!(get_activity_name(&custom, "my.client"), Some(".VrActivity"));
+        assert_eq!(
+            get_activity_name(&custom, PACKAGE_NAME_STORE),
+            Some(ACTIVITY_NAME_STORE)
+        );
+        assert_eq!(
+            get_activity_name(&ClientFlavor::Custom("my.client".to_owned()), "my.client"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_start_local_secs() {
+        let clock = DeviceClock {
+            unix_secs: 1_714_567_890,
+            utc_offset_secs: 7200,
+        };
+
+        assert_eq!(
+            process_start_local_secs(clock, Duration::from_secs(1000), Duration::from_secs(400)),
+            1_714_567_890 + 7200 - 600
+        );
+    }
+
+    #[test]
+    fn test_is_process_outdated() {
+        let process_start = 1_714_566_896;
+
+        // Updated before the process started
+        assert!(!is_process_outdated(process_start, process_start - 3600));
+        // Process restarted by the update itself, within the resolution of lastUpdateTime
+        assert!(!is_process_outdated(process_start, process_start));
+        assert!(!is_process_outdated(
+            process_start,
+            process_start + OUTDATED_PROCESS_TOLERANCE_SECS
+        ));
+        // Updated while the process kept running
+        assert!(is_process_outdated(
+            process_start,
+            process_start + OUTDATED_PROCESS_TOLERANCE_SECS + 1
+        ));
+        assert!(is_process_outdated(process_start, process_start + 3600));
+    }
 }
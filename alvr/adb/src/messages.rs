@@ -0,0 +1,440 @@
+use crate::WiredOperation;
+use crate::parse::{ConnectionState, CrashKind};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// Text of a status that is not ready, as a stable id and typed parameters so that it can be
+/// translated. It serializes as `{ "id": "awaiting_launch_delay", "params": { "seconds_remaining":
+/// 7 } }`, without `params` for the messages that have none. `Display` renders the English text.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "id", content = "params", rename_all = "snake_case")]
+pub enum WiredMessage {
+    NoDevice,
+    Reconnecting {
+        serial: String,
+        attempt: u32,
+        max_attempts: u32,
+    },
+    DeviceReconnecting,
+    DeviceManagedElsewhere,
+    // The vendor id is only known on Linux
+    NoPermissions {
+        vendor_id: Option<String>,
+    },
+    Unauthorized,
+    Authorizing,
+    // Listed as authorized, but the first command doesn't go through yet
+    FinishingAuthorization,
+    Connecting,
+    Offline,
+    DeviceNotReady {
+        state: ConnectionState,
+    },
+    DeviceStateUnknown,
+    Booting,
+    ClientNotInstalled,
+    ClientStopped,
+    ClientDisabled,
+    AwaitingBoot {
+        seconds_remaining: u64,
+    },
+    AwaitingLaunchDelay {
+        seconds_remaining: u64,
+    },
+    HeadsetAsleepBeforeLaunch,
+    HeadsetLocked,
+    LockScreenNotDismissed,
+    StartingClient,
+    PreLaunchCommandFailed {
+        command_line: String,
+        stderr: String,
+    },
+    LaunchPermissionDenied,
+    ActivityNotFound,
+    PackageNotFound,
+    UserRestricted,
+    // The error is the untranslated output of `am`
+    LaunchFailed {
+        error: String,
+    },
+    LaunchGaveUp {
+        attempts: u32,
+    },
+    ClientOutdated,
+    RestartingOutdatedClient,
+    ClientPaused,
+    ClientInBackground,
+    HeadsetAsleep,
+    ClientNotResponding,
+    RestartingUnresponsiveClient,
+    DownloadingAdb {
+        percent: usize,
+    },
+    // The server didn't tell the size
+    DownloadingAdbSize {
+        kilobytes: usize,
+    },
+    AdbMissing {
+        error: String,
+    },
+    Busy {
+        operation: Option<WiredOperation>,
+    },
+    // Another message, followed by the crash of the last run of the client. The summary is the
+    // untranslated crash message.
+    LastRunCrashed {
+        message: Box<WiredMessage>,
+        crash: CrashKind,
+        summary: String,
+    },
+}
+
+impl WiredMessage {
+    /// Key of the message in the translations, the same as the serialized `id`
+    pub fn id(&self) -> &'static str {
+        match self {
+            WiredMessage::NoDevice => "no_device",
+            WiredMessage::Reconnecting { .. } => "reconnecting",
+            WiredMessage::DeviceReconnecting => "device_reconnecting",
+            WiredMessage::DeviceManagedElsewhere => "device_managed_elsewhere",
+            WiredMessage::NoPermissions { .. } => "no_permissions",
+            WiredMessage::Unauthorized => "unauthorized",
+            WiredMessage::Authorizing => "authorizing",
+            WiredMessage::FinishingAuthorization => "finishing_authorization",
+            WiredMessage::Connecting => "connecting",
+            WiredMessage::Offline => "offline",
+            WiredMessage::DeviceNotReady { .. } => "device_not_ready",
+            WiredMessage::DeviceStateUnknown => "device_state_unknown",
+            WiredMessage::Booting => "booting",
+            WiredMessage::ClientNotInstalled => "client_not_installed",
+            WiredMessage::ClientStopped => "client_stopped",
+            WiredMessage::ClientDisabled => "client_disabled",
+            WiredMessage::AwaitingBoot { .. } => "awaiting_boot",
+            WiredMessage::AwaitingLaunchDelay { .. } => "awaiting_launch_delay",
+            WiredMessage::HeadsetAsleepBeforeLaunch => "headset_asleep_before_launch",
+            WiredMessage::HeadsetLocked => "headset_locked",
+            WiredMessage::LockScreenNotDismissed => "lock_screen_not_dismissed",
+            WiredMessage::StartingClient => "starting_client",
+            WiredMessage::PreLaunchCommandFailed { .. } => "pre_launch_command_failed",
+            WiredMessage::LaunchPermissionDenied => "launch_permission_denied",
+            WiredMessage::ActivityNotFound => "activity_not_found",
+            WiredMessage::PackageNotFound => "package_not_found",
+            WiredMessage::UserRestricted => "user_restricted",
+            WiredMessage::LaunchFailed { .. } => "launch_failed",
+            WiredMessage::LaunchGaveUp { .. } => "launch_gave_up",
+            WiredMessage::ClientOutdated => "client_outdated",
+            WiredMessage::RestartingOutdatedClient => "restarting_outdated_client",
+            WiredMessage::ClientPaused => "client_paused",
+            WiredMessage::ClientInBackground => "client_in_background",
+            WiredMessage::HeadsetAsleep => "headset_asleep",
+            WiredMessage::ClientNotResponding => "client_not_responding",
+            WiredMessage::RestartingUnresponsiveClient => "restarting_unresponsive_client",
+            WiredMessage::DownloadingAdb { .. } => "downloading_adb",
+            WiredMessage::DownloadingAdbSize { .. } => "downloading_adb_size",
+            WiredMessage::AdbMissing { .. } => "adb_missing",
+            WiredMessage::Busy { .. } => "busy",
+            WiredMessage::LastRunCrashed { .. } => "last_run_crashed",
+        }
+    }
+}
+
+impl Display for WiredMessage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WiredMessage::NoDevice => write!(f, "No wired devices found"),
+            WiredMessage::Reconnecting {
+                serial,
+                attempt,
+                max_attempts,
+            } => write!(
+                f,
+                "Reconnecting to {serial} (attempt {attempt}/{max_attempts})"
+            ),
+            WiredMessage::DeviceReconnecting => write!(f, "Headset reconnecting"),
+            WiredMessage::DeviceManagedElsewhere => {
+                write!(f, "Device managed by another ALVR instance")
+            }
+            WiredMessage::NoPermissions {
+                vendor_id: Some(vendor_id),
+            } => write!(
+                f,
+                "No permissions to access the device, add the udev rule \
+                SUBSYSTEM==\"usb\", ATTR{{idVendor}}==\"{vendor_id}\", MODE=\"0666\" \
+                to /etc/udev/rules.d/51-android.rules and reconnect the headset"
+            ),
+            WiredMessage::NoPermissions { vendor_id: None } => write!(
+                f,
+                "No permissions to access the device, install the udev rules for Android \
+                devices and reconnect the headset"
+            ),
+            WiredMessage::Unauthorized => {
+                write!(
+                    f,
+                    "Accept the \"Allow USB debugging?\" prompt in the headset"
+                )
+            }
+            WiredMessage::Authorizing => write!(f, "Waiting for USB debugging authorization"),
+            WiredMessage::FinishingAuthorization => {
+                write!(f, "Waiting for the headset to finish authorizing")
+            }
+            WiredMessage::Connecting => write!(f, "Connecting to the device"),
+            WiredMessage::Offline => write!(f, "Device is offline, try reconnecting it"),
+            WiredMessage::DeviceNotReady { state } => write!(f, "Device is not ready ({state:?})"),
+            WiredMessage::DeviceStateUnknown => write!(f, "Device is in an unknown state"),
+            WiredMessage::Booting => write!(f, "Headset is booting"),
+            WiredMessage::ClientNotInstalled => write!(f, "No suitable ALVR client is installed"),
+            WiredMessage::ClientStopped => write!(f, "ALVR client is not running"),
+            WiredMessage::ClientDisabled => write!(
+                f,
+                "ALVR client is disabled, enable it in the headset settings"
+            ),
+            WiredMessage::AwaitingBoot { seconds_remaining } => {
+                write!(f, "Waiting for device boot ({seconds_remaining}s)")
+            }
+            WiredMessage::AwaitingLaunchDelay { seconds_remaining } => {
+                write!(f, "Launching ALVR client in {seconds_remaining}s")
+            }
+            WiredMessage::HeadsetAsleepBeforeLaunch => {
+                write!(f, "Headset is asleep, put it on to launch the ALVR client")
+            }
+            WiredMessage::HeadsetLocked => {
+                write!(f, "Headset is locked, unlock it to launch the ALVR client")
+            }
+            WiredMessage::LockScreenNotDismissed => write!(
+                f,
+                "Failed to dismiss the lock screen, unlock the headset to launch the ALVR client"
+            ),
+            WiredMessage::StartingClient => write!(f, "Starting ALVR client"),
+            WiredMessage::PreLaunchCommandFailed {
+                command_line,
+                stderr,
+            } => write!(f, "Pre-launch command `{command_line}` failed: {stderr}"),
+            WiredMessage::LaunchPermissionDenied => write!(
+                f,
+                "ALVR client activity cannot be started, check the wired client type"
+            ),
+            WiredMessage::ActivityNotFound => write!(
+                f,
+                "ALVR client activity does not exist, reinstall the client"
+            ),
+            WiredMessage::PackageNotFound => {
+                write!(f, "ALVR client cannot be launched, reinstall the client")
+            }
+            WiredMessage::UserRestricted => write!(
+                f,
+                "ALVR client cannot be started for this headset account, switch to the main account"
+            ),
+            WiredMessage::LaunchFailed { error } => {
+                write!(f, "Failed to start ALVR client: {error}")
+            }
+            WiredMessage::LaunchGaveUp { attempts } => {
+                write!(f, "Failed to start ALVR client after {attempts} attempts")
+            }
+            WiredMessage::ClientOutdated => {
+                write!(f, "ALVR client is running an outdated build, restart it")
+            }
+            WiredMessage::RestartingOutdatedClient => write!(f, "Restarting outdated ALVR client"),
+            WiredMessage::ClientPaused => write!(f, "ALVR client is paused"),
+            WiredMessage::ClientInBackground => write!(
+                f,
+                "ALVR client is in the background, return to it from the headset"
+            ),
+            WiredMessage::HeadsetAsleep => write!(f, "Headset is asleep or not worn"),
+            WiredMessage::ClientNotResponding => write!(f, "ALVR client is not responding"),
+            WiredMessage::RestartingUnresponsiveClient => {
+                write!(f, "Restarting unresponsive ALVR client")
+            }
+            WiredMessage::DownloadingAdb { percent } => write!(f, "Downloading ADB ({percent}%)"),
+            WiredMessage::DownloadingAdbSize { kilobytes } => {
+                write!(f, "Downloading ADB ({kilobytes} KB)")
+            }
+            WiredMessage::AdbMissing { error } => {
+                write!(f, "ADB is missing and installing it again failed: {error}")
+            }
+            WiredMessage::Busy {
+                operation: Some(operation),
+            } => write!(f, "Busy: {operation}"),
+            WiredMessage::Busy { operation: None } => write!(f, "Busy"),
+            WiredMessage::LastRunCrashed {
+                message,
+                crash,
+                summary,
+            } => {
+                let crash = match crash {
+                    CrashKind::Crash => "crashed",
+                    CrashKind::NativeCrash => "crashed in native code",
+                    CrashKind::Anr => "stopped responding",
+                };
+
+                write!(f, "{message} (last run {crash}: {summary})")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // One message of each kind. A message missing here fails to compile in `variant_index`.
+    fn all_messages() -> Vec<WiredMessage> {
+        vec![
+            WiredMessage::NoDevice,
+            WiredMessage::Reconnecting {
+                serial: "192.168.1.20:5555".to_owned(),
+                attempt: 1,
+                max_attempts: 5,
+            },
+            WiredMessage::DeviceReconnecting,
+            WiredMessage::DeviceManagedElsewhere,
+            WiredMessage::NoPermissions { vendor_id: None },
+            WiredMessage::Unauthorized,
+            WiredMessage::Authorizing,
+            WiredMessage::FinishingAuthorization,
+            WiredMessage::Connecting,
+            WiredMessage::Offline,
+            WiredMessage::DeviceNotReady {
+                state: ConnectionState::Recovery,
+            },
+            WiredMessage::DeviceStateUnknown,
+            WiredMessage::Booting,
+            WiredMessage::ClientNotInstalled,
+            WiredMessage::ClientStopped,
+            WiredMessage::ClientDisabled,
+            WiredMessage::AwaitingBoot {
+                seconds_remaining: 7,
+            },
+            WiredMessage::AwaitingLaunchDelay {
+                seconds_remaining: 7,
+            },
+            WiredMessage::HeadsetAsleepBeforeLaunch,
+            WiredMessage::HeadsetLocked,
+            WiredMessage::LockScreenNotDismissed,
+            WiredMessage::StartingClient,
+            WiredMessage::PreLaunchCommandFailed {
+                command_line: "false".to_owned(),
+                stderr: String::new(),
+            },
+            WiredMessage::LaunchPermissionDenied,
+            WiredMessage::ActivityNotFound,
+            WiredMessage::PackageNotFound,
+            WiredMessage::UserRestricted,
+            WiredMessage::LaunchFailed {
+                error: "Error type 3".to_owned(),
+            },
+            WiredMessage::LaunchGaveUp { attempts: 3 },
+            WiredMessage::ClientOutdated,
+            WiredMessage::RestartingOutdatedClient,
+            WiredMessage::ClientPaused,
+            WiredMessage::ClientInBackground,
+            WiredMessage::HeadsetAsleep,
+            WiredMessage::ClientNotResponding,
+            WiredMessage::RestartingUnresponsiveClient,
+            WiredMessage::DownloadingAdb { percent: 25 },
+            WiredMessage::DownloadingAdbSize { kilobytes: 1024 },
+            WiredMessage::AdbMissing {
+                error: "No internet".to_owned(),
+            },
+            WiredMessage::Busy {
+                operation: Some(WiredOperation::InstallingClient),
+            },
+            WiredMessage::LastRunCrashed {
+                message: Box::new(WiredMessage::ClientStopped),
+                crash: CrashKind::Anr,
+                summary: "Input dispatching timed out".to_owned(),
+            },
+        ]
+    }
+
+    fn variant_index(message: &WiredMessage) -> usize {
+        match message {
+            WiredMessage::NoDevice => 0,
+            WiredMessage::Reconnecting { .. } => 1,
+            WiredMessage::DeviceReconnecting => 2,
+            WiredMessage::DeviceManagedElsewhere => 3,
+            WiredMessage::NoPermissions { .. } => 4,
+            WiredMessage::Unauthorized => 5,
+            WiredMessage::Authorizing => 6,
+            WiredMessage::FinishingAuthorization => 7,
+            WiredMessage::Connecting => 8,
+            WiredMessage::Offline => 9,
+            WiredMessage::DeviceNotReady { .. } => 10,
+            WiredMessage::DeviceStateUnknown => 11,
+            WiredMessage::Booting => 12,
+            WiredMessage::ClientNotInstalled => 13,
+            WiredMessage::ClientStopped => 14,
+            WiredMessage::ClientDisabled => 15,
+            WiredMessage::AwaitingBoot { .. } => 16,
+            WiredMessage::AwaitingLaunchDelay { .. } => 17,
+            WiredMessage::HeadsetAsleepBeforeLaunch => 18,
+            WiredMessage::HeadsetLocked => 19,
+            WiredMessage::LockScreenNotDismissed => 20,
+            WiredMessage::StartingClient => 21,
+            WiredMessage::PreLaunchCommandFailed { .. } => 22,
+            WiredMessage::LaunchPermissionDenied => 23,
+            WiredMessage::ActivityNotFound => 24,
+            WiredMessage::PackageNotFound => 25,
+            WiredMessage::UserRestricted => 26,
+            WiredMessage::LaunchFailed { .. } => 27,
+            WiredMessage::LaunchGaveUp { .. } => 28,
+            WiredMessage::ClientOutdated => 29,
+            WiredMessage::RestartingOutdatedClient => 30,
+            WiredMessage::ClientPaused => 31,
+            WiredMessage::ClientInBackground => 32,
+            WiredMessage::HeadsetAsleep => 33,
+            WiredMessage::ClientNotResponding => 34,
+            WiredMessage::RestartingUnresponsiveClient => 35,
+            WiredMessage::DownloadingAdb { .. } => 36,
+            WiredMessage::DownloadingAdbSize { .. } => 37,
+            WiredMessage::AdbMissing { .. } => 38,
+            WiredMessage::Busy { .. } => 39,
+            WiredMessage::LastRunCrashed { .. } => 40,
+        }
+    }
+
+    #[test]
+    fn test_message_ids() {
+        let messages = all_messages();
+        let indices = messages.iter().map(variant_index).collect::<HashSet<_>>();
+        assert_eq!(indices, (0..messages.len()).collect());
+
+        let mut ids = HashSet::new();
+        for message in &messages {
+            let id = message.id();
+            assert!(ids.insert(id), "{id}");
+            assert!(!message.to_string().is_empty());
+
+            // Serde renames the variants to snake case, the ids must match
+            let debug = format!("{message:?}");
+            let variant = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap();
+            let mut snake_case = String::new();
+            for (i, c) in variant.chars().enumerate() {
+                if c.is_uppercase() && i > 0 {
+                    snake_case.push('_');
+                }
+                snake_case.push(c.to_ascii_lowercase());
+            }
+            assert_eq!(id, snake_case);
+        }
+    }
+
+    #[test]
+    fn test_message_display() {
+        let message = WiredMessage::AwaitingLaunchDelay {
+            seconds_remaining: 7,
+        };
+        assert_eq!(message.id(), "awaiting_launch_delay");
+        assert_eq!(message.to_string(), "Launching ALVR client in 7s");
+
+        let message = WiredMessage::LastRunCrashed {
+            message: Box::new(WiredMessage::ClientStopped),
+            crash: CrashKind::Anr,
+            summary: "Input dispatching timed out".to_owned(),
+        };
+        assert_eq!(
+            message.to_string(),
+            "ALVR client is not running (last run stopped responding: Input dispatching timed out)"
+        );
+    }
+}
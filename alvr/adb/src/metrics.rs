@@ -0,0 +1,184 @@
+use crate::WiredConnection;
+use alvr_common::debug;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Calls kept until `take_metrics`, the oldest ones are dropped first
+const MAX_CALL_METRICS: usize = 512;
+
+/// Duration of an adb call made by the setup, see `WiredConnection::take_metrics`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdbCallMetric {
+    // adb command, with the program of shell commands and the service of dumpsys, e.g. "devices",
+    // "shell pidof" or "shell dumpsys activity"
+    pub command: String,
+    pub device_serial: Option<String>,
+    pub duration: Duration,
+}
+
+pub(crate) struct CallMetrics {
+    calls: VecDeque<AdbCallMetric>,
+    // Longer calls are logged at debug level
+    slow_call_threshold: Option<Duration>,
+}
+
+impl CallMetrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            calls: VecDeque::new(),
+            slow_call_threshold: None,
+        }
+    }
+}
+
+thread_local! {
+    static METRICS: RefCell<Option<Arc<Mutex<CallMetrics>>>> = const { RefCell::new(None) };
+}
+
+// Records the adb calls made by `f` on this thread into `metrics`
+pub(crate) fn record_metrics<T>(metrics: Arc<Mutex<CallMetrics>>, f: impl FnOnce() -> T) -> T {
+    let previous = METRICS.with(|current| current.replace(Some(metrics)));
+    let _restore = RestoreMetrics(previous);
+
+    f()
+}
+
+struct RestoreMetrics(Option<Arc<Mutex<CallMetrics>>>);
+
+impl Drop for RestoreMetrics {
+    fn drop(&mut self) {
+        METRICS.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+pub(crate) fn record_call(args: &[String], duration: Duration) {
+    let Some(metrics) = METRICS.with(|current| current.borrow().clone()) else {
+        return;
+    };
+
+    let (device_serial, args) = match args {
+        [flag, serial, args @ ..] if flag == "-s" => (Some(serial.clone()), args),
+        _ => (None, args),
+    };
+    let metric = AdbCallMetric {
+        command: command_name(args),
+        device_serial,
+        duration,
+    };
+
+    let mut metrics = metrics.lock().unwrap();
+    if metrics
+        .slow_call_threshold
+        .is_some_and(|threshold| duration >= threshold)
+    {
+        debug!(
+            "wired_connection: adb {} took {}ms",
+            metric.command,
+            duration.as_millis()
+        );
+    }
+    if metrics.calls.len() == MAX_CALL_METRICS {
+        metrics.calls.pop_front();
+    }
+    metrics.calls.push_back(metric);
+}
+
+fn command_name(args: &[String]) -> String {
+    let words = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let len = match words.as_slice() {
+        ["shell", "dumpsys" | "cmd" | "am" | "pm" | "settings", ..] => 3,
+        ["shell", ..] => 2,
+        _ => 1,
+    };
+
+    words[..len.min(words.len())].join(" ")
+}
+
+impl WiredConnection {
+    /// Returns and clears the adb calls made by `setup` and `teardown` since the last call, up to
+    /// the last 512, including the ones of the other devices of `setup_all`. Meant to find which
+    /// commands slow down the setup.
+    pub fn take_metrics(&mut self) -> Vec<AdbCallMetric> {
+        let mut calls = Vec::from(mem::take(&mut self.metrics.lock().unwrap().calls));
+        for connection in self.other_devices.values_mut() {
+            calls.extend(connection.take_metrics());
+        }
+
+        calls
+    }
+
+    /// Logs the adb calls of the setup that take at least `threshold`, at debug level. Disabled by
+    /// default.
+    pub fn set_slow_call_threshold(&mut self, threshold: Option<Duration>) {
+        self.metrics.lock().unwrap().slow_call_threshold = threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| (*arg).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_record_call() {
+        let metrics = Arc::new(Mutex::new(CallMetrics::new()));
+
+        // Not recorded outside of `record_metrics`
+        record_call(&args(&["devices", "-l"]), Duration::from_millis(1));
+        record_metrics(Arc::clone(&metrics), || {
+            record_call(&args(&["devices", "-l"]), Duration::from_millis(2));
+            record_call(
+                &args(&[
+                    "-s",
+                    "1WMHH000000000",
+                    "shell",
+                    "dumpsys",
+                    "activity",
+                    "activities",
+                ]),
+                Duration::from_millis(300),
+            );
+            record_call(
+                &args(&["-s", "1WMHH000000000", "shell", "pidof", "alvr.client"]),
+                Duration::from_millis(20),
+            );
+        });
+
+        let calls = Vec::from(mem::take(&mut metrics.lock().unwrap().calls));
+        assert_eq!(
+            calls,
+            [
+                AdbCallMetric {
+                    command: "devices".to_owned(),
+                    device_serial: None,
+                    duration: Duration::from_millis(2),
+                },
+                AdbCallMetric {
+                    command: "shell dumpsys activity".to_owned(),
+                    device_serial: Some("1WMHH000000000".to_owned()),
+                    duration: Duration::from_millis(300),
+                },
+                AdbCallMetric {
+                    command: "shell pidof".to_owned(),
+                    device_serial: Some("1WMHH000000000".to_owned()),
+                    duration: Duration::from_millis(20),
+                },
+            ]
+        );
+
+        // Only the last calls are kept
+        record_metrics(Arc::clone(&metrics), || {
+            for _ in 0..MAX_CALL_METRICS + 10 {
+                record_call(&args(&["devices"]), Duration::ZERO);
+            }
+        });
+        assert_eq!(metrics.lock().unwrap().calls.len(), MAX_CALL_METRICS);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    AdbError, AdbResult, WiredConnection, WiredConnectionStatus, as_adb_error, commands, transport,
+    with_transport,
+};
+use alvr_common::{dbg_connection, warn};
+use std::collections::HashMap;
+use std::panic;
+use std::{mem, thread};
+
+impl WiredConnection {
+    /// Runs the setup of every wired device at the same time, with the parameters used by `tick`,
+    /// and returns the statuses by serial. The device picked by `tick` is the primary one, whose
+    /// status `setup` keeps returning. A local port can only be forwarded to one device, so only the
+    /// primary device gets the ports. The clients of the other devices are still launched and
+    /// monitored. A failed setup of another device is logged and leaves it out of the statuses,
+    /// only a failure of the primary device fails the call.
+    pub fn setup_all(&mut self) -> AdbResult<HashMap<String, WiredConnectionStatus>> {
+        // The transport is per thread, the setup threads must be given it
+        let transport = self.transport.clone().or_else(transport::current_transport);
+
+        let list_devices = || commands::list_devices(&self.adb_path);
+        let devices = match transport.clone() {
+            Some(transport) => with_transport(transport, list_devices),
+            None => list_devices(),
+        }?;
+        let primary_serial = self
+            .device_watcher
+            .select_device(&devices)
+            .and_then(|d| d.serial.clone());
+        let other_serials = devices
+            .into_iter()
+            .filter_map(|d| d.serial)
+            .filter(|s| !s.starts_with("127.0.0.1") && Some(s) != primary_serial.as_ref())
+            .collect::<Vec<_>>();
+
+        // Dropping the connection of an unplugged device restores what is left of it
+        self.other_devices
+            .retain(|serial, _| other_serials.contains(serial));
+        for serial in other_serials {
+            self.other_devices
+                .entry(serial)
+                .or_insert_with_key(|serial| {
+                    dbg_connection!("wired_connection: Adding device {serial}");
+                    let mut connection = WiredConnection::with_adb_path(
+                        self.adb_path.clone(),
+                        self.adb_source,
+                        Some(serial.clone()),
+                    );
+                    connection.adb_version = self.adb_version.clone();
+                    connection.forward_ports = false;
+                    connection.cancel_handle = self.cancel_handle.clone();
+                    connection.lock_dir = self.lock_dir.clone();
+
+                    connection
+                });
+        }
+
+        let mut other_devices = mem::take(&mut self.other_devices);
+        let (primary_res, other_results) = thread::scope(|scope| {
+            let handles = other_devices
+                .iter_mut()
+                .map(|(serial, connection)| {
+                    connection.set_setup_params(self.setup_params.clone());
+                    let transport = transport.clone();
+                    let handle = scope.spawn(move || match transport {
+                        Some(transport) => with_transport(transport, || connection.tick()),
+                        None => connection.tick(),
+                    });
+
+                    (serial.clone(), handle)
+                })
+                .collect::<Vec<_>>();
+
+            let primary_res = self.tick();
+            let other_results = handles
+                .into_iter()
+                .map(|(serial, handle)| {
+                    let res = handle
+                        .join()
+                        .unwrap_or_else(|payload| panic::resume_unwind(payload));
+
+                    (serial, res)
+                })
+                .collect::<Vec<_>>();
+
+            (primary_res, other_results)
+        });
+        self.other_devices = other_devices;
+
+        let mut statuses = HashMap::new();
+        for (serial, res) in other_results {
+            match res {
+                Ok(status) => {
+                    statuses.insert(serial, status);
+                }
+                Err(e) if matches!(as_adb_error(&e), Some(AdbError::Cancelled)) => (),
+                Err(e) => warn!("wired_connection: Setup of device {serial} failed with {e:#}"),
+            }
+        }
+        let primary_status = primary_res?;
+        if let Some(serial) = &self.device_serial {
+            statuses.insert(serial.clone(), primary_status);
+        }
+
+        Ok(statuses)
+    }
+}
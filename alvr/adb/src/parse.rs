@@ -1,9 +1,12 @@
-// https://cs.android.com/android/platform/superproject/main/+/7dbe542b9a93fb3cee6c528e16e2d02a26da7cc0:packages/modules/adb/transport.cpp;l=1409
-// The serial number is printed with a "%-22s" format, meaning that it's a left-aligned space-padded string of 22 characters.
-const SERIAL_NUMBER_COLUMN_LENGTH: usize = 22;
+use crate::AdbError;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
 
 // https://cs.android.com/android/platform/superproject/main/+/7dbe542b9a93fb3cee6c528e16e2d02a26da7cc0:packages/modules/adb/adb.h;l=104-122
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConnectionState {
     Authorizing,
     Bootloader,
@@ -36,90 +39,219 @@ pub fn parse_connection_state(value: &str) -> Option<ConnectionState> {
     }
 }
 
-fn parse_pair(pair: &str) -> Option<String> {
-    let mut slice = pair.split(':');
-    let _key = slice.next();
+// `adb version` prints "Android Debug Bridge version 1.0.41", then "Version 35.0.0-11411520" with the
+// platform-tools version and the build number since platform-tools 28
+pub fn parse_adb_version(text: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let version = line.strip_prefix("Version ")?.trim();
 
-    slice.next().map(|value| value.to_string())
+        Some(version.split('-').next().unwrap_or(version).to_owned())
+    })
 }
 
-// https://cs.android.com/android/platform/superproject/main/+/7dbe542b9a93fb3cee6c528e16e2d02a26da7cc0:packages/modules/adb/adb.h;l=95-100
-#[derive(Debug)]
-pub enum TransportType {
-    Usb,
-    Local,
-    Any,
-    Host,
+// dumpsys prints "Permission Denial: can't dump ActivityManager from from pid=1234, uid=2000 due to
+// missing android.permission.DUMP permission" if the shell user may not dump the service, and
+// "Can't find service: package" if the service is hidden from it
+pub fn is_dumpsys_restricted(text: &str) -> bool {
+    let text = text.trim_start();
+
+    (text.starts_with("Permission Denial") && text.contains("can't dump"))
+        || text.starts_with("Can't find service")
 }
 
-pub fn parse_transport_type(pair: &str) -> Option<TransportType> {
-    let mut slice = pair.split(':');
-    let _key = slice.next();
+// adb reports its own failures on stderr, e.g. "error: device 'abc' not found", as opposed to the
+// failures of the command run on the device. Returns None if adb itself did not fail.
+pub fn parse_adb_error(stderr: &str) -> Option<AdbError> {
+    let has_line = |prefixes: &[&str]| {
+        stderr.lines().any(|line| {
+            prefixes
+                .iter()
+                .any(|prefix| line.trim().starts_with(prefix))
+        })
+    };
+    let message = stderr.trim().to_owned();
 
-    if let Ok(value) = slice.next()?.parse::<u8>() {
-        match value {
-            0 => Some(TransportType::Usb),
-            1 => Some(TransportType::Local),
-            2 => Some(TransportType::Any),
-            3 => Some(TransportType::Host),
-            _ => None,
-        }
+    if has_line(&[
+        "error: cannot connect to daemon",
+        "* failed to start daemon",
+        "adb: failed to check server version",
+        "error: protocol fault",
+    ]) {
+        Some(AdbError::ServerUnreachable(message))
+    } else if has_line(&[
+        "error: device unauthorized",
+        "error: device still authorizing",
+        "error: insufficient permissions for device",
+    ]) {
+        Some(AdbError::DeviceUnauthorized(message))
+    } else if has_line(&[
+        "error: device '",
+        "error: no devices/emulators found",
+        "error: device offline",
+        "error: closed",
+    ]) {
+        Some(AdbError::DeviceNotFound(message))
     } else {
         None
     }
 }
 
+// `adb start-server` fails with "cannot bind to 127.0.0.1:5037: Address already in use" when another
+// process holds the port without answering as an adb server. Windows prints "Only one usage of each
+// socket address ..." instead.
+pub fn is_server_port_in_use(text: &str) -> bool {
+    [
+        "cannot bind",
+        "Address already in use",
+        "Only one usage of each socket address",
+    ]
+    .iter()
+    .any(|message| text.contains(message))
+}
+
+// `netstat -ano -p TCP` on Windows prints "  TCP    127.0.0.1:5037    0.0.0.0:0    LISTENING    1234"
+pub fn parse_netstat_listening_pids(text: &str, port: u16) -> Vec<u32> {
+    let mut pids = text
+        .lines()
+        .filter_map(|line| {
+            let columns = line.split_whitespace().collect::<Vec<_>>();
+            let [_, local_address, _, "LISTENING", pid] = columns[..] else {
+                return None;
+            };
+            let (_, local_port) = local_address.rsplit_once(':')?;
+
+            (local_port.parse::<u16>().ok()? == port)
+                .then(|| pid.parse().ok())
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+    pids.sort_unstable();
+    pids.dedup();
+
+    pids
+}
+
+// `tasklist /FI "PID eq <pid>" /FO CSV /NH` prints `"adb.exe","1234","Console","1","8,180 K"`, or
+// an "INFO:" line if there is no such process
+pub fn parse_tasklist_image_name(text: &str) -> Option<String> {
+    let name = text.trim().strip_prefix('"')?.split('"').next()?;
+
+    Some(name.to_owned())
+}
+
 // https://cs.android.com/android/platform/superproject/main/+/7dbe542b9a93fb3cee6c528e16e2d02a26da7cc0:packages/modules/adb/transport.cpp;l=1398
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Device {
-    pub connection_state: Option<ConnectionState>,
-    pub device: Option<String>,
-    pub model: Option<String>,
-    pub product: Option<String>,
     pub serial: Option<String>,
-    pub transport_type: Option<TransportType>,
+    pub state: Option<ConnectionState>,
+    // Entries printed by `adb devices -l`, e.g. "usb" -> "1-1", "model" -> "Quest_3"
+    pub attributes: HashMap<String, String>,
 }
 
+impl Device {
+    pub fn product(&self) -> Option<&str> {
+        self.attributes.get("product").map(String::as_str)
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.attributes.get("model").map(String::as_str)
+    }
+
+    pub fn device(&self) -> Option<&str> {
+        self.attributes.get("device").map(String::as_str)
+    }
+
+    pub fn transport_id(&self) -> Option<u64> {
+        self.attributes.get("transport_id")?.parse().ok()
+    }
+}
+
+// Parses the output of `adb devices` or `adb devices -l`, skipping the header and the messages
+// printed while the daemon starts
+pub fn parse_devices(output: &str) -> Vec<Device> {
+    output
+        .lines()
+        .filter(|l| {
+            let line = l.trim();
+            !line.is_empty() && !line.starts_with("List of devices") && !line.starts_with('*')
+        })
+        .filter_map(parse_device)
+        .collect()
+}
+
+// The short form separates the serial number and the state with a tab. The long form prints the
+// serial number with a "%-22s" format (left-aligned and space-padded, but longer serials are not
+// truncated), followed by the state and the attributes.
 pub fn parse_device(line: &str) -> Option<Device> {
-    if line.len() < SERIAL_NUMBER_COLUMN_LENGTH {
+    const NO_SERIAL_NUMBER: &str = "(no serial number)";
+
+    let (serial, remaining) = if let Some(remaining) = line.strip_prefix(NO_SERIAL_NUMBER) {
+        (None, remaining)
+    } else {
+        let (serial, remaining) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        (Some(serial.to_owned()), remaining)
+    };
+    let mut remaining = remaining.trim();
+    if serial.as_ref().is_some_and(|s| s.is_empty()) || remaining.is_empty() {
         return None;
     }
-    let (left, right) = line.split_at(SERIAL_NUMBER_COLUMN_LENGTH);
-    let serial = (!left.contains("(no serial number)")).then(|| left.trim().to_owned());
-    let mut remaining = right.trim();
 
-    let connection_state = if remaining.starts_with("no permissions") {
+    let state = if remaining.starts_with("no permissions") {
         // Since the current user's name can be printed in the error message,
         // we are gambling that there's not a "]" in it.
-        if let Some((_, right)) = remaining.split_once(']') {
-            remaining = right;
-            Some(ConnectionState::NoPermissions)
-        } else {
-            None
-        }
-    } else if let Some((left, right)) = remaining.split_once(' ') {
-        remaining = right;
-        parse_connection_state(left)
+        remaining = remaining.split_once(']').map_or("", |(_, right)| right);
+        Some(ConnectionState::NoPermissions)
     } else {
-        None
+        let (state, right) = remaining
+            .split_once(char::is_whitespace)
+            .unwrap_or((remaining, ""));
+        remaining = right;
+        parse_connection_state(state)
     };
 
-    let mut slices = remaining.split_whitespace();
-    let product = slices.next().and_then(parse_pair);
-    let model = slices.next().and_then(parse_pair);
-    let device = slices.next().and_then(parse_pair);
-    let transport_type = slices.next().and_then(parse_transport_type);
+    let attributes = remaining
+        .split_whitespace()
+        .filter_map(|pair| pair.split_once(':'))
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect();
 
     Some(Device {
-        connection_state,
-        device,
-        model,
-        product,
         serial,
-        transport_type,
+        state,
+        attributes,
     })
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportType {
+    Usb,
+    Emulator,
+    Tcp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transport {
+    pub transport_type: TransportType,
+    pub device: Device,
+}
+
+// Emulators are named "emulator-<console port>". Network devices are named by their address, or by
+// their mDNS service name when they were connected with wireless debugging. USB devices have a "usb"
+// attribute in the long form, any other device is assumed to be connected through USB.
+pub fn parse_transport_type(device: &Device) -> TransportType {
+    let serial = device.serial.as_deref().unwrap_or_default();
+
+    if device.attributes.contains_key("usb") {
+        TransportType::Usb
+    } else if serial.starts_with("emulator-") {
+        TransportType::Emulator
+    } else if serial.parse::<SocketAddr>().is_ok() || serial.ends_with("._tcp") {
+        TransportType::Tcp
+    } else {
+        TransportType::Usb
+    }
+}
+
 #[derive(Debug)]
 pub struct ForwardedPorts {
     pub local: u16,
@@ -154,3 +286,2096 @@ fn parse_port(value: &str) -> Option<u16> {
 
     maybe_port.and_then(|p| p.parse::<u16>().ok())
 }
+
+// https://cs.android.com/android/platform/superproject/main/+/main:system/logging/liblog/logprint.cpp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogcatPriority {
+    Verbose,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+    Silent,
+}
+
+impl LogcatPriority {
+    pub fn as_char(self) -> char {
+        match self {
+            LogcatPriority::Verbose => 'V',
+            LogcatPriority::Debug => 'D',
+            LogcatPriority::Info => 'I',
+            LogcatPriority::Warn => 'W',
+            LogcatPriority::Error => 'E',
+            LogcatPriority::Fatal => 'F',
+            LogcatPriority::Silent => 'S',
+        }
+    }
+}
+
+pub fn parse_logcat_priority(value: &str) -> Option<LogcatPriority> {
+    match value {
+        "V" => Some(LogcatPriority::Verbose),
+        "D" => Some(LogcatPriority::Debug),
+        "I" => Some(LogcatPriority::Info),
+        "W" => Some(LogcatPriority::Warn),
+        "E" => Some(LogcatPriority::Error),
+        "F" => Some(LogcatPriority::Fatal),
+        "S" => Some(LogcatPriority::Silent),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogcatLine {
+    pub timestamp: String,
+    pub pid: u32,
+    pub tid: u32,
+    pub priority: LogcatPriority,
+    pub tag: String,
+    pub message: String,
+}
+
+// Parses a line printed with `logcat -v threadtime`, e.g.
+// "10-15 12:34:56.789  1234  5678 I ActivityManager: Start proc".
+// Multiline messages are printed as one line per message line, each with its own header.
+pub fn parse_logcat_line(line: &str) -> Option<LogcatLine> {
+    let mut remaining = line.trim_start();
+
+    let mut next_field = || {
+        let (field, right) = remaining.split_once(char::is_whitespace)?;
+        remaining = right.trim_start();
+        Some(field)
+    };
+    let date = next_field()?;
+    let time = next_field()?;
+    let pid = next_field()?.parse().ok()?;
+    let tid = next_field()?.parse().ok()?;
+    let priority = parse_logcat_priority(next_field()?)?;
+
+    let (tag, message) = if let Some((tag, message)) = remaining.split_once(": ") {
+        (tag, message)
+    } else {
+        (remaining.strip_suffix(':')?, "")
+    };
+
+    Some(LogcatLine {
+        timestamp: format!("{date} {time}"),
+        pid,
+        tid,
+        priority,
+        tag: tag.trim_end().to_owned(),
+        message: message.to_owned(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityState {
+    Resumed,
+    // Not resumed but still visible, e.g. in a multi-window panel while another app has focus
+    Visible,
+    Paused,
+    Unknown,
+}
+
+// Records look like "ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}"
+fn parse_activity_record_package(record: &str) -> Option<&str> {
+    let (_, record) = record.split_once("ActivityRecord{")?;
+
+    record
+        .split_whitespace()
+        .find_map(|entry| entry.split_once('/'))
+        .map(|(package, _)| package)
+}
+
+// Tasks look like "* Task{5e6f7a8 #34 type=standard A=10123:alvr.client U=0 visible=true}", where
+// the affinity (A=) or the intent (I=) identifies the package
+fn is_task_of_package(task: &str, package: &str) -> bool {
+    task.split_whitespace().any(|entry| {
+        if let Some(affinity) = entry.strip_prefix("A=") {
+            affinity.rsplit(':').next() == Some(package)
+        } else if let Some(intent) = entry.strip_prefix("I=") {
+            intent.split('/').next() == Some(package)
+        } else {
+            false
+        }
+    })
+}
+
+// `dumpsys activity activities` prints the resumed activity of every task display area as
+// "ResumedActivity:", of every task (or stack before Android 12) as "mResumedActivity:" and the
+// focused one as "topResumedActivity=". Android 9 and older only print the focused activity as
+// "mFocusedActivity:". Activities on secondary displays and in multi-window panels are included.
+// Visibility is read from the task header (Android 12+) or from the activity record entries.
+pub fn parse_activity_state(text: &str, package: &str) -> ActivityState {
+    const RESUMED_ACTIVITY_PREFIXES: [&str; 4] = [
+        "ResumedActivity:",
+        "mResumedActivity:",
+        "topResumedActivity=",
+        "mFocusedActivity:",
+    ];
+
+    let mut found_resumed_activities = false;
+    let mut visible = false;
+    let mut record_indentation = None;
+    for line in text.lines() {
+        let trimmed_line = line.trim_start();
+        let indentation = line.len() - trimmed_line.len();
+        if record_indentation.is_some_and(|record_indentation| indentation <= record_indentation) {
+            record_indentation = None;
+        }
+
+        if RESUMED_ACTIVITY_PREFIXES
+            .iter()
+            .any(|prefix| trimmed_line.starts_with(prefix))
+        {
+            found_resumed_activities = true;
+            if parse_activity_record_package(trimmed_line) == Some(package) {
+                return ActivityState::Resumed;
+            }
+        } else if trimmed_line.starts_with("* Task{") {
+            if is_task_of_package(trimmed_line, package)
+                && trimmed_line.split_whitespace().any(|e| e == "visible=true")
+            {
+                visible = true;
+            }
+        } else if trimmed_line.starts_with("* Hist #") {
+            if parse_activity_record_package(trimmed_line) == Some(package) {
+                record_indentation = Some(indentation);
+            }
+        } else if record_indentation.is_some()
+            && trimmed_line
+                .split_whitespace()
+                .any(|e| e == "visible=true" || e == "nowVisible=true")
+        {
+            visible = true;
+        }
+    }
+
+    if visible {
+        ActivityState::Visible
+    } else if found_resumed_activities {
+        ActivityState::Paused
+    } else {
+        ActivityState::Unknown
+    }
+}
+
+// Parses the "mResumed=true mStopped=false mFinished=false" entry printed by
+// `dumpsys activity <package>`
+pub fn parse_activity_resumed(text: &str) -> Option<bool> {
+    text.lines()
+        .flat_map(str::split_whitespace)
+        .find_map(|entry| entry.strip_prefix("mResumed="))
+        .and_then(|value| match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        })
+}
+
+// Looks for the "crashing=false notResponding=true" entry in the process record of `process_name`
+// printed by `dumpsys activity processes`, e.g.
+// "  *APP* UID 10123 ProcessRecord{8a1b2c3 4321:alvr.client/u0a123}"
+pub fn parse_process_not_responding(text: &str, process_name: &str) -> bool {
+    let record_name = format!(":{process_name}/");
+    let mut record_indentation = None;
+    for line in text.lines() {
+        let indentation = line.len() - line.trim_start().len();
+        if line.trim_start().starts_with('*') && line.contains("ProcessRecord{") {
+            record_indentation = line.contains(&record_name).then_some(indentation);
+        } else if let Some(record_indentation) = record_indentation {
+            if indentation <= record_indentation {
+                return false;
+            } else if line.contains("notResponding=true") {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+// `pm install-create` prints "Success: created install session [1234567]"
+pub fn parse_install_session_id(text: &str) -> Option<u32> {
+    text.trim()
+        .strip_prefix("Success: created install session [")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+// `adb install` prints "Performing Streamed Install" once it starts streaming the APK. Some adb
+// versions follow it with progress lines like "[ 45%] /path/to/app.apk", which are rewritten with
+// carriage returns. Returns the percentage.
+pub fn parse_install_progress(line: &str) -> Option<usize> {
+    let line = line.trim();
+    if line.starts_with("Performing Streamed Install") {
+        return Some(0);
+    }
+    let (percent, _) = line.strip_prefix('[')?.split_once("%]")?;
+
+    percent
+        .trim()
+        .parse()
+        .ok()
+        .map(|percent: usize| percent.min(100))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaunchError {
+    PermissionDenied,
+    ActivityNotFound,
+    PackageNotFound,
+    // The shell cannot start activities for the current user, e.g. a secondary account
+    UserRestricted,
+    Unknown(String),
+}
+
+// Finds the error printed by `am start` or `monkey`. Both exit with a status of 0 on errors and
+// print them on stdout or stderr depending on the Android version. Errors are "Error: ..." lines,
+// exceptions or "** No activities found to run, monkey aborted.".
+pub fn parse_launch_error(text: &str) -> Option<LaunchError> {
+    let line = text.lines().map(str::trim).find(|line| {
+        line.starts_with("Error:") || line.contains("Exception:") || line.starts_with("** ")
+    })?;
+    let error = line.strip_prefix("Error:").unwrap_or(line).trim();
+
+    let error = if error.contains("access user")
+        || error.contains("INTERACT_ACROSS_USERS")
+        || error.contains("background user")
+    {
+        LaunchError::UserRestricted
+    } else if error.contains("SecurityException") || error.contains("do not have permission") {
+        LaunchError::PermissionDenied
+    } else if error.starts_with("Activity class") && error.ends_with("does not exist.") {
+        LaunchError::ActivityNotFound
+    } else if error.contains("No activities found")
+        || error.contains("unable to resolve Intent")
+        || error.contains("Unknown package")
+    {
+        LaunchError::PackageNotFound
+    } else {
+        LaunchError::Unknown(error.to_owned())
+    };
+
+    Some(error)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaunchResult {
+    // None if an existing instance was brought to the front instead, or if the launch was not
+    // waited for
+    Started { total_time_ms: Option<u32> },
+    // The activity is still launching, it did not draw its first frame in time
+    TimedOut,
+    Failed(LaunchError),
+}
+
+// Parses the combined stdout and stderr of `am start -W`, which prints "Status: ok" or
+// "Status: timeout" followed by the launch times
+pub fn parse_launch_result(text: &str) -> LaunchResult {
+    if let Some(error) = parse_launch_error(text) {
+        return LaunchResult::Failed(error);
+    }
+
+    let mut status = None;
+    let mut total_time_ms = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("Status:") {
+            status = Some(value.trim());
+        } else if let Some(value) = line.strip_prefix("TotalTime:") {
+            total_time_ms = value.trim().parse().ok();
+        }
+    }
+
+    match status {
+        Some("ok") => LaunchResult::Started { total_time_ms },
+        Some("timeout") => LaunchResult::TimedOut,
+        Some(status) => {
+            LaunchResult::Failed(LaunchError::Unknown(format!("Launch status is {status}")))
+        }
+        None => LaunchResult::Failed(LaunchError::Unknown("No launch status".to_owned())),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportanceLevel {
+    Persistent,
+    Foreground,
+    Visible,
+    Perceptible,
+    Service,
+    Home,
+    Previous,
+    Cached,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessImportance {
+    pub level: ImportanceLevel,
+    // Why the process has this level, e.g. "top-activity", "fg-service" or "cch-empty"
+    pub reason: Option<String>,
+}
+
+// https://cs.android.com/android/platform/superproject/main/+/main:frameworks/base/services/core/java/com/android/server/am/ProcessList.java
+fn parse_importance_level(value: &str) -> ImportanceLevel {
+    match value.split('+').next().unwrap_or_default() {
+        "ntv" | "sys" | "pers" | "psvc" => ImportanceLevel::Persistent,
+        "fg" | "fore" | "top" => ImportanceLevel::Foreground,
+        "vis" => ImportanceLevel::Visible,
+        "prcp" | "percep" | "prcl" | "prcm" | "prcw" | "bkup" | "hvy" => {
+            ImportanceLevel::Perceptible
+        }
+        "svc" | "svcb" | "svcrst" => ImportanceLevel::Service,
+        "home" => ImportanceLevel::Home,
+        "prev" => ImportanceLevel::Previous,
+        "cch" | "bak" | "empty" => ImportanceLevel::Cached,
+        _ => ImportanceLevel::Unknown,
+    }
+}
+
+// Looks for the entry of `process_name` in the LRU list printed by `dumpsys activity processes`.
+// Android 10 and newer print "Proc #20: fg     T/A/TOP  LCM  t: 0 4321:alvr.client/u0a123 (top-activity)",
+// older versions print "Proc # 3: fore  T/A/TOP  trm: 0 4321:alvr.client/u0a123 (top-activity)".
+// Cached processes have an index, as in "cch+ 5".
+pub fn parse_process_importance(text: &str, process_name: &str) -> Option<ProcessImportance> {
+    let process_pattern = format!(":{process_name}/");
+
+    text.lines().find_map(|line| {
+        let line = line.trim_start();
+        let entry = line
+            .strip_prefix("Proc #")
+            .or_else(|| line.strip_prefix("PERS #"))?;
+        let (_, entry) = entry.split_once(':')?;
+        let (state, process) = entry.split_once(&process_pattern)?;
+
+        let level = parse_importance_level(state.split_whitespace().next()?);
+        let reason = process
+            .split_once('(')
+            .and_then(|(_, reason)| reason.trim_end().strip_suffix(')'))
+            .map(str::to_owned);
+
+        Some(ProcessImportance { level, reason })
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryInfo {
+    pub total_pss_kb: u64,
+    // Memory of the GL/Vulkan buffers and textures, not reported by all GPU drivers
+    pub gl_mtrack_kb: Option<u64>,
+}
+
+// Parses `dumpsys meminfo <package>`. Android 10 and newer print "TOTAL PSS: 234567" in the app
+// summary, older versions only print the "TOTAL" row of the table. Returns None if the process is
+// not running.
+pub fn parse_memory_info(text: &str) -> Option<MemoryInfo> {
+    let first_number = |value: &str| value.split_whitespace().next()?.parse::<u64>().ok();
+
+    let mut total_pss_kb = None;
+    let mut gl_mtrack_kb = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("TOTAL PSS:") {
+            total_pss_kb = first_number(value);
+        } else if let Some(value) = line.strip_prefix("TOTAL ")
+            && total_pss_kb.is_none()
+        {
+            total_pss_kb = first_number(value);
+        } else if let Some(value) = line.strip_prefix("GL mtrack") {
+            gl_mtrack_kb = first_number(value);
+        }
+    }
+
+    Some(MemoryInfo {
+        total_pss_kb: total_pss_kb?,
+        gl_mtrack_kb,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphicsInfo {
+    pub total_frames: u64,
+    pub janky_frames: u64,
+    pub percentile_50_ms: Option<u32>,
+    pub percentile_90_ms: Option<u32>,
+    pub percentile_95_ms: Option<u32>,
+    pub percentile_99_ms: Option<u32>,
+    // Frame time buckets in ms and their frame counts
+    pub histogram: Vec<(u32, u64)>,
+}
+
+// Parses `dumpsys gfxinfo <package>`. The stats are cumulative since the process started. Only
+// frames drawn by the Android UI toolkit are counted, so this returns None for native renderers
+// that never draw through it.
+pub fn parse_graphics_info(text: &str) -> Option<GraphicsInfo> {
+    let parse_ms = |value: &str| value.trim().strip_suffix("ms")?.parse::<u32>().ok();
+
+    let mut total_frames = None;
+    let mut janky_frames = 0;
+    let mut percentiles = [None; 4];
+    let mut histogram = Vec::new();
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key {
+            "Total frames rendered" => total_frames = value.trim().parse::<u64>().ok(),
+            "Janky frames" => {
+                janky_frames = value
+                    .split_whitespace()
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or_default();
+            }
+            "50th percentile" => percentiles[0] = parse_ms(value),
+            "90th percentile" => percentiles[1] = parse_ms(value),
+            "95th percentile" => percentiles[2] = parse_ms(value),
+            "99th percentile" => percentiles[3] = parse_ms(value),
+            "HISTOGRAM" => {
+                histogram = value
+                    .split_whitespace()
+                    .filter_map(|bucket| {
+                        let (time, count) = bucket.split_once('=')?;
+
+                        Some((parse_ms(time)?, count.parse().ok()?))
+                    })
+                    .collect();
+            }
+            _ => (),
+        }
+    }
+
+    let total_frames = total_frames.filter(|frames| *frames > 0)?;
+    let [
+        percentile_50_ms,
+        percentile_90_ms,
+        percentile_95_ms,
+        percentile_99_ms,
+    ] = percentiles;
+
+    Some(GraphicsInfo {
+        total_frames,
+        janky_frames,
+        percentile_50_ms,
+        percentile_90_ms,
+        percentile_95_ms,
+        percentile_99_ms,
+        histogram,
+    })
+}
+
+// `/proc/<pid>/stat` prints the start time of the process in clock ticks since boot as the 22nd
+// field. The second field is the process name in parentheses, which can contain spaces.
+pub fn parse_process_start_ticks(stat: &str) -> Option<u64> {
+    let (_, fields) = stat.rsplit_once(')')?;
+
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+// `dumpsys package <package>` prints "lastUpdateTime=2024-05-01 12:34:56" in the device time zone.
+// Returns the seconds since 1970-01-01 00:00:00 in the same time zone.
+pub fn parse_package_update_time(text: &str) -> Option<i64> {
+    let value = text
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("lastUpdateTime="))?;
+
+    parse_local_date_time(value)
+}
+
+// `dumpsys package <package>` prints "versionName=20.11.0". Packages can be listed in several
+// sections, e.g. for system apps updated by the user, the first one is the installed version.
+pub fn parse_package_version(text: &str) -> Option<String> {
+    text.lines()
+        .find_map(|line| line.trim().strip_prefix("versionName="))
+        .map(|version| version.trim().to_owned())
+        .filter(|version| !version.is_empty())
+}
+
+// `dumpsys package <package>` lists the install and runtime permissions as
+// "android.permission.RECORD_AUDIO: granted=true, flags=[ ... ]"
+pub fn parse_granted_permissions(text: &str) -> HashSet<String> {
+    text.lines()
+        .filter_map(|line| line.trim().split_once(": granted="))
+        .filter(|(_, state)| state.starts_with("true"))
+        .map(|(permission, _)| permission.to_owned())
+        .collect()
+}
+
+fn parse_local_date_time(value: &str) -> Option<i64> {
+    let (date, time) = value.trim().split_once(' ')?;
+
+    let mut date = date.splitn(3, '-').map(|v| v.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    let mut time = time.splitn(3, ':').map(|v| v.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+// https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceClock {
+    pub unix_secs: i64,
+    pub utc_offset_secs: i64,
+}
+
+impl DeviceClock {
+    // Seconds since 1970-01-01 00:00:00 in the device time zone
+    pub fn local_secs(&self) -> i64 {
+        self.unix_secs + self.utc_offset_secs
+    }
+}
+
+// Parses the output of `date "+%s %z"`, e.g. "1714567890 +0200"
+pub fn parse_device_clock(text: &str) -> Option<DeviceClock> {
+    let (unix_secs, utc_offset) = text.trim().split_once(' ')?;
+
+    let sign = match utc_offset.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours = utc_offset.get(1..3)?.parse::<i64>().ok()?;
+    let minutes = utc_offset.get(3..5)?.parse::<i64>().ok()?;
+
+    Some(DeviceClock {
+        unix_secs: unix_secs.parse().ok()?,
+        utc_offset_secs: sign * (hours * 3600 + minutes * 60),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrashKind {
+    Crash,
+    NativeCrash,
+    Anr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    pub kind: CrashKind,
+    // Seconds since 1970-01-01 00:00:00 in the device time zone
+    pub time: i64,
+    pub summary: String,
+}
+
+// Crashes are recorded as "data_app_crash", native crashes as "data_app_native_crash" with the
+// tombstone and ANRs as "data_app_anr"
+pub fn parse_crash_kind(tag: &str) -> Option<CrashKind> {
+    match tag {
+        "data_app_crash" => Some(CrashKind::Crash),
+        "data_app_native_crash" => Some(CrashKind::NativeCrash),
+        "data_app_anr" => Some(CrashKind::Anr),
+        _ => None,
+    }
+}
+
+// Parses the last entry of `process_name` printed by `dumpsys dropbox --print <tag>`. Entries are
+// separated by a line of "=" and start with "2024-05-01 12:34:56 data_app_crash (text, 1234 bytes)",
+// followed by headers like "Process: alvr.client", an empty line and the report.
+pub fn parse_last_crash(text: &str, process_name: &str) -> Option<CrashReport> {
+    const MAX_SUMMARY_LENGTH: usize = 200;
+
+    text.split("========================================")
+        .filter_map(|entry| {
+            let mut lines = entry
+                .lines()
+                .map(str::trim_end)
+                .skip_while(|l| l.is_empty());
+
+            let header = lines.next()?;
+            let (date, header) = header.split_once(' ')?;
+            let (time, header) = header.split_once(' ')?;
+            let kind = parse_crash_kind(header.split_whitespace().next()?)?;
+            let time = parse_local_date_time(&format!("{date} {time}"))?;
+
+            let headers = lines
+                .by_ref()
+                .take_while(|l| !l.is_empty())
+                .collect::<Vec<_>>();
+            if !headers.contains(&format!("Process: {process_name}").as_str()) {
+                return None;
+            }
+
+            let mut report = lines.map(str::trim).filter(|l| !l.is_empty());
+            let summary = match kind {
+                CrashKind::Anr => headers
+                    .iter()
+                    .find_map(|l| l.strip_prefix("Subject: "))
+                    .map(str::to_owned),
+                CrashKind::NativeCrash => {
+                    let report = report.collect::<Vec<_>>();
+                    report
+                        .iter()
+                        .find_map(|l| l.strip_prefix("Abort message: "))
+                        .or_else(|| report.iter().find(|l| l.starts_with("signal ")).copied())
+                        .map(str::to_owned)
+                }
+                CrashKind::Crash => report.next().map(str::to_owned),
+            }
+            .unwrap_or_default();
+
+            Some(CrashReport {
+                kind,
+                time,
+                summary: summary.chars().take(MAX_SUMMARY_LENGTH).collect(),
+            })
+        })
+        .last()
+}
+
+// `dumpsys power` prints "mWakefulness=Awake" since Android 7, which can be Asleep, Dreaming or
+// Dozing otherwise. Older versions print "mScreenOn=true" or "Display Power: state=ON".
+pub fn parse_screen_on(text: &str) -> Option<bool> {
+    text.lines().map(str::trim).find_map(|line| {
+        if let Some(value) = line.strip_prefix("mWakefulness=") {
+            Some(value == "Awake")
+        } else if let Some(value) = line.strip_prefix("mScreenOn=") {
+            Some(value == "true")
+        } else {
+            line.strip_prefix("Display Power: state=")
+                .map(|value| value == "ON")
+        }
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    pub id: u32,
+    pub name: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+// `dumpsys display` prints each display as
+// `DisplayInfo{"Built-in Screen", displayId 0, ..., real 1920 x 1832, ...}`, several times for the
+// base and override info. The id is printed since Android 10, older displays are skipped.
+pub fn parse_displays(text: &str) -> Vec<Display> {
+    let mut displays = Vec::<Display>::new();
+    for line in text.lines() {
+        let Some((_, info)) = line.split_once("DisplayInfo{\"") else {
+            continue;
+        };
+        let Some((name, fields)) = info.split_once('"') else {
+            continue;
+        };
+
+        let mut id = None;
+        let mut size = None;
+        for field in fields.split(", ") {
+            if let Some(value) = field.trim().strip_prefix("displayId ") {
+                id = value.parse::<u32>().ok();
+            } else if let Some(value) = field.trim().strip_prefix("real ") {
+                size = value
+                    .split_once(" x ")
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+            }
+        }
+
+        if let Some(id) = id
+            && !displays.iter().any(|display| display.id == id)
+        {
+            displays.push(Display {
+                id,
+                name: name.to_owned(),
+                width: size.map(|(width, _)| width),
+                height: size.map(|(_, height)| height),
+            });
+        }
+    }
+    displays.sort_by_key(|display| display.id);
+
+    displays
+}
+
+// `settings get global stay_on_while_plugged_in` prints a mask of the BATTERY_PLUGGED_* power
+// sources that keep the screen on, or "null" if the setting was never written
+pub fn parse_stay_on_while_plugged_in(text: &str) -> Option<u32> {
+    match text.trim() {
+        "null" => Some(0),
+        value => value.parse().ok(),
+    }
+}
+
+// `settings get` prints "null" for settings that are not set
+pub fn parse_setting_value(text: &str) -> Option<String> {
+    match text.trim_end_matches(['\r', '\n']) {
+        "null" => None,
+        value => Some(value.to_owned()),
+    }
+}
+
+// `content query` prints each row as "Row: 0 column=value, other=value", or "No result found."
+// Returns the column of the first row.
+pub fn parse_content_query_value(text: &str, column: &str) -> Option<String> {
+    let row = text
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Row: "))?;
+    let (_, fields) = row.split_once(' ')?;
+
+    fields.split(", ").find_map(|field| {
+        field
+            .strip_prefix(column)
+            .and_then(|value| value.strip_prefix('='))
+            .filter(|value| !value.is_empty() && *value != "NULL")
+            .map(str::to_owned)
+    })
+}
+
+// Boolean system properties accept the same values as `android::base::ParseBool`. Unset
+// properties are empty.
+pub fn parse_prop_bool(value: &str) -> Option<bool> {
+    match value.trim() {
+        "1" | "y" | "yes" | "on" | "true" => Some(true),
+        "0" | "n" | "no" | "off" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyguardState {
+    pub showing: bool,
+    // A secure keyguard requires a PIN, pattern or password and cannot be dismissed remotely
+    pub secure: bool,
+}
+
+// `dumpsys window policy` prints the state of the KeyguardServiceDelegate since Android 9. Older
+// versions print "mShowingLockscreen=true" without the secure flag.
+pub fn parse_keyguard_state(text: &str) -> Option<KeyguardState> {
+    let mut lines = text.lines().map(str::trim);
+
+    if lines.any(|l| l.starts_with("KeyguardServiceDelegate")) {
+        let mut showing = None;
+        let mut secure = None;
+        for line in lines.take_while(|l| l.contains('=')) {
+            if let Some(value) = line.strip_prefix("showing=") {
+                showing = Some(value == "true");
+            } else if let Some(value) = line.strip_prefix("secure=") {
+                secure = Some(value == "true");
+            }
+        }
+
+        return Some(KeyguardState {
+            showing: showing?,
+            secure: secure.unwrap_or(false),
+        });
+    }
+
+    text.lines()
+        .map(str::trim)
+        .find_map(|l| {
+            l.split_whitespace()
+                .find_map(|e| e.strip_prefix("mShowingLockscreen="))
+        })
+        .map(|value| KeyguardState {
+            showing: value == "true",
+            secure: false,
+        })
+}
+
+// https://developer.android.com/reference/android/os/BatteryManager#BATTERY_STATUS_CHARGING
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryStatus {
+    Unknown,
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatteryState {
+    pub level_percent: Option<u8>,
+    pub status: BatteryStatus,
+    pub ac_powered: bool,
+    pub usb_powered: bool,
+    pub wireless_powered: bool,
+    pub max_charging_current_ua: Option<u32>,
+}
+
+impl BatteryState {
+    pub fn is_plugged(&self) -> bool {
+        self.ac_powered || self.usb_powered || self.wireless_powered
+    }
+
+    pub fn is_charging(&self) -> bool {
+        matches!(self.status, BatteryStatus::Charging | BatteryStatus::Full)
+    }
+
+    // Some cables enumerate data but deliver negligible power
+    pub fn is_underpowered(&self) -> bool {
+        self.is_plugged() && !self.is_charging()
+    }
+}
+
+pub fn parse_battery_state(text: &str) -> Option<BatteryState> {
+    let entries = text
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect::<HashMap<_, _>>();
+
+    let status = match entries.get("status")?.parse::<u8>().ok()? {
+        2 => BatteryStatus::Charging,
+        3 => BatteryStatus::Discharging,
+        4 => BatteryStatus::NotCharging,
+        5 => BatteryStatus::Full,
+        _ => BatteryStatus::Unknown,
+    };
+    let level = entries.get("level").and_then(|v| v.parse::<u32>().ok());
+    let scale = entries
+        .get("scale")
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|scale| *scale > 0)
+        .unwrap_or(100);
+    let is_powered = |key| entries.get(key) == Some(&"true");
+
+    Some(BatteryState {
+        level_percent: level.and_then(|l| u8::try_from(l * 100 / scale).ok()),
+        status,
+        ac_powered: is_powered("AC powered"),
+        usb_powered: is_powered("USB powered"),
+        wireless_powered: is_powered("Wireless powered"),
+        max_charging_current_ua: entries
+            .get("Max charging current")
+            .and_then(|v| v.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_logcat_line() {
+        let line = parse_logcat_line(
+            "10-15 12:34:56.789  1234  5678 I ActivityManager: Start proc 4321:alvr.client/u0a123",
+        )
+        .unwrap();
+
+        assert_eq!(line.timestamp, "10-15 12:34:56.789");
+        assert_eq!(line.pid, 1234);
+        assert_eq!(line.tid, 5678);
+        assert_eq!(line.priority, LogcatPriority::Info);
+        assert_eq!(line.tag, "ActivityManager");
+        assert_eq!(line.message, "Start proc 4321:alvr.client/u0a123");
+    }
+
+    #[test]
+    fn test_parse_logcat_line_padded_tag_and_empty_message() {
+        let line = parse_logcat_line("10-15 12:34:56.789   987   987 W ALVR    : ").unwrap();
+        assert_eq!(line.tag, "ALVR");
+        assert_eq!(line.message, "");
+
+        let line = parse_logcat_line("10-15 12:34:56.789   987   987 E ALVR:").unwrap();
+        assert_eq!(line.priority, LogcatPriority::Error);
+        assert_eq!(line.tag, "ALVR");
+        assert_eq!(line.message, "");
+    }
+
+    #[test]
+    fn test_parse_logcat_multiline_message() {
+        let output = "\
+10-15 12:34:56.789  2000  2001 E AndroidRuntime: FATAL EXCEPTION: main
+10-15 12:34:56.789  2000  2001 E AndroidRuntime: Process: alvr.client, PID: 2000
+10-15 12:34:56.789  2000  2001 E AndroidRuntime: \tat alvr.client.MainActivity.onCreate(Unknown Source:0)";
+
+        let lines = output
+            .lines()
+            .filter_map(parse_logcat_line)
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines
+                .iter()
+                .all(|l| l.tag == "AndroidRuntime" && l.pid == 2000)
+        );
+        assert_eq!(lines[0].message, "FATAL EXCEPTION: main");
+        assert_eq!(lines[1].message, "Process: alvr.client, PID: 2000");
+        assert_eq!(
+            lines[2].message,
+            "\tat alvr.client.MainActivity.onCreate(Unknown Source:0)"
+        );
+    }
+
+    #[test]
+    fn test_parse_logcat_malformed_lines() {
+        assert!(parse_logcat_line("").is_none());
+        assert!(parse_logcat_line("--------- beginning of main").is_none());
+        assert!(parse_logcat_line("10-15 12:34:56.789  abc  5678 I Tag: message").is_none());
+        assert!(parse_logcat_line("10-15 12:34:56.789  1234  5678 X Tag: message").is_none());
+        assert!(parse_logcat_line("10-15 12:34:56.789  1234  5678 I no separator").is_none());
+        assert!(parse_logcat_line("10-15 12:34:56.789  1234").is_none());
+    }
+
+    const DUMPSYS_PROCESSES_NOT_RESPONDING: &str = "\
+ACTIVITY MANAGER RUNNING PROCESSES (dumpsys activity processes)
+  All known processes:
+  *APP* UID 10123 ProcessRecord{8a1b2c3 4321:alvr.client/u0a123}
+    user #0 uid=10123 gids={50123, 20123, 9997}
+    packageList={alvr.client}
+    pid=4321 starting=false
+    oom: max=1001 curRaw=0 setRaw=0 cur=0 set=0
+    curProcState=2 mRepProcState=2 pssProcState=2 setProcState=2 lastStateTime=-1m0s
+    crashing=false notResponding=true
+    notRespondingReport: {\"processName\":\"alvr.client\"}
+  *APP* UID 10050 ProcessRecord{1d2e3f4 1200:com.oculus.vrshell/u0a50}
+    pid=1200 starting=false
+    crashing=false notResponding=false
+  PID mappings:
+    PID #1200: ProcessRecord{1d2e3f4 1200:com.oculus.vrshell/u0a50}
+    PID #4321: ProcessRecord{8a1b2c3 4321:alvr.client/u0a123}
+";
+
+    #[test]
+    fn test_parse_process_not_responding() {
+        assert!(parse_process_not_responding(
+            DUMPSYS_PROCESSES_NOT_RESPONDING,
+            "alvr.client"
+        ));
+        assert!(!parse_process_not_responding(
+            DUMPSYS_PROCESSES_NOT_RESPONDING,
+            "com.oculus.vrshell"
+        ));
+        assert!(!parse_process_not_responding(
+            DUMPSYS_PROCESSES_NOT_RESPONDING,
+            "alvr.client.dev"
+        ));
+    }
+
+    #[test]
+    fn test_parse_process_responding() {
+        let text = DUMPSYS_PROCESSES_NOT_RESPONDING.replace(
+            "crashing=false notResponding=true",
+            "crashing=false notResponding=false",
+        );
+        assert!(!parse_process_not_responding(&text, "alvr.client"));
+    }
+
+    #[test]
+    fn test_parse_devices_long_form() {
+        let output = "\
+List of devices attached
+1WMHH000000000         device usb:1-1 product:hollywood model:Quest_2 device:hollywood transport_id:3
+192.168.1.20:5555      device product:eureka model:Quest_3 device:eureka transport_id:4
+adb-2G0YC1ZF8B07WD-AbCdEf._adb-tls-connect._tcp device product:eureka model:Quest_3 device:eureka transport_id:5
+
+";
+        let devices = parse_devices(output);
+        assert_eq!(devices.len(), 3);
+
+        let usb = &devices[0];
+        assert_eq!(usb.serial.as_deref(), Some("1WMHH000000000"));
+        assert_eq!(usb.state, Some(ConnectionState::Device));
+        assert_eq!(usb.attributes.get("usb").map(String::as_str), Some("1-1"));
+        assert_eq!(usb.product(), Some("hollywood"));
+        assert_eq!(usb.model(), Some("Quest_2"));
+        assert_eq!(usb.device(), Some("hollywood"));
+        assert_eq!(usb.transport_id(), Some(3));
+
+        let tcp = &devices[1];
+        assert_eq!(tcp.serial.as_deref(), Some("192.168.1.20:5555"));
+        assert!(!tcp.attributes.contains_key("usb"));
+        assert_eq!(tcp.model(), Some("Quest_3"));
+
+        let mdns = &devices[2];
+        assert_eq!(
+            mdns.serial.as_deref(),
+            Some("adb-2G0YC1ZF8B07WD-AbCdEf._adb-tls-connect._tcp")
+        );
+        assert_eq!(mdns.state, Some(ConnectionState::Device));
+        assert_eq!(mdns.transport_id(), Some(5));
+    }
+
+    #[test]
+    fn test_parse_devices_short_form() {
+        let output = "List of devices attached\n1WMHH000000000\tdevice\nemulator-5554\toffline\n\n";
+        let devices = parse_devices(output);
+
+        assert_eq!(
+            devices,
+            vec![
+                Device {
+                    serial: Some("1WMHH000000000".to_owned()),
+                    state: Some(ConnectionState::Device),
+                    attributes: HashMap::new(),
+                },
+                Device {
+                    serial: Some("emulator-5554".to_owned()),
+                    state: Some(ConnectionState::Offline),
+                    attributes: HashMap::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_devices_unauthorized_and_offline() {
+        let output = "\
+List of devices attached
+1WMHH000000000         unauthorized usb:1-1 transport_id:1
+192.168.1.20:5555      offline product:eureka model:Quest_3 device:eureka transport_id:2
+";
+        let devices = parse_devices(output);
+
+        assert_eq!(devices[0].state, Some(ConnectionState::Unauthorized));
+        assert_eq!(devices[0].model(), None);
+        assert_eq!(devices[0].transport_id(), Some(1));
+        assert_eq!(devices[1].state, Some(ConnectionState::Offline));
+        assert_eq!(devices[1].model(), Some("Quest_3"));
+    }
+
+    #[test]
+    fn test_parse_devices_no_permissions() {
+        let output = "\
+List of devices attached
+1WMHH000000000         no permissions (missing udev rules? user is in the plugdev group); see [http://developer.android.com/tools/device.html] usb:1-1 transport_id:1
+";
+        let devices = parse_devices(output);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].serial.as_deref(), Some("1WMHH000000000"));
+        assert_eq!(devices[0].state, Some(ConnectionState::NoPermissions));
+        assert_eq!(
+            devices[0].attributes.get("usb").map(String::as_str),
+            Some("1-1")
+        );
+
+        let output = "????????????\tno permissions (user in plugdev group; are your udev rules wrong?); see [http://developer.android.com/tools/device.html]\n";
+        let devices = parse_devices(output);
+        assert_eq!(devices[0].serial.as_deref(), Some("????????????"));
+        assert_eq!(devices[0].state, Some(ConnectionState::NoPermissions));
+        assert!(devices[0].attributes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_devices_daemon_banner() {
+        let output = "\
+* daemon not running; starting now at tcp:5037
+* daemon started successfully
+List of devices attached
+1WMHH000000000         device usb:1-1 transport_id:1
+";
+        let devices = parse_devices(output);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].serial.as_deref(), Some("1WMHH000000000"));
+
+        assert!(parse_devices("List of devices attached\n\n").is_empty());
+        assert!(parse_devices("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_device_edge_cases() {
+        let device = parse_device("(no serial number)     device usb:1-1 transport_id:7").unwrap();
+        assert_eq!(device.serial, None);
+        assert_eq!(device.state, Some(ConnectionState::Device));
+        assert_eq!(device.transport_id(), Some(7));
+
+        let device = parse_device("1WMHH000000000         sideload").unwrap();
+        assert_eq!(device.state, Some(ConnectionState::Sideload));
+
+        let device = parse_device("1WMHH000000000         weird_state usb:1-1").unwrap();
+        assert_eq!(device.state, None);
+
+        assert!(parse_device("1WMHH000000000").is_none());
+        assert!(parse_device("   ").is_none());
+    }
+
+    #[test]
+    fn test_parse_screen_on() {
+        let text = "POWER MANAGER (dumpsys power)\n\nPower Manager State:\n  mDirty=0x0\n  mWakefulness=Asleep\n  mWakefulnessChanging=false\n";
+        assert_eq!(parse_screen_on(text), Some(false));
+        assert_eq!(
+            parse_screen_on(&text.replace("=Asleep", "=Awake")),
+            Some(true)
+        );
+        assert_eq!(parse_screen_on("Display Power: state=ON\n"), Some(true));
+        assert_eq!(parse_screen_on("  mScreenOn=false\n"), Some(false));
+        assert_eq!(parse_screen_on("Permission Denial: can't dump"), None);
+    }
+
+    #[test]
+    fn test_parse_keyguard_state() {
+        let text = "\
+WINDOW MANAGER POLICY STATE (dumpsys window policy)
+    mKeyguardDrawComplete=true mWindowManagerDrawComplete=true
+    KeyguardServiceDelegate
+      showing=true
+      showingAndNotOccluded=true
+      inputRestricted=true
+      occluded=false
+      secure=true
+      dreaming=false
+    mSystemReady=true mSystemBooted=true
+";
+        assert_eq!(
+            parse_keyguard_state(text),
+            Some(KeyguardState {
+                showing: true,
+                secure: true
+            })
+        );
+        assert_eq!(
+            parse_keyguard_state(&text.replace("      showing=true", "      showing=false")),
+            Some(KeyguardState {
+                showing: false,
+                secure: true
+            })
+        );
+        assert_eq!(
+            parse_keyguard_state("    mShowingLockscreen=true mShowingDream=false\n"),
+            Some(KeyguardState {
+                showing: true,
+                secure: false
+            })
+        );
+        assert_eq!(parse_keyguard_state("mSystemReady=true\n"), None);
+    }
+
+    const DUMPSYS_ACTIVITIES_ANDROID_10: &str = "\
+ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+Display #0 (activities from top to bottom):
+
+  Stack #34: type=standard mode=fullscreen
+  isSleeping=false
+  mBounds=Rect(0, 0 - 0, 0)
+    Task id #34
+    mBounds=Rect(0, 0 - 0, 0)
+    mMinWidth=-1
+    * TaskRecord{8c1d2e3 #34 A=alvr.client U=0 StackId=34 sz=1}
+      affinity=alvr.client
+      Activities=[ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}]
+      * Hist #0: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+          packageName=alvr.client processName=alvr.client
+    mResumedActivity: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+
+  Stack #1: type=home mode=fullscreen
+    mLastPausedActivity: ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+
+ ResumedActivity: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+  mFocusedStack=ActivityStack{3a4b5c6 stackId=34 type=standard mode=fullscreen visible=true}
+";
+
+    const DUMPSYS_ACTIVITIES_ANDROID_12: &str = "\
+ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+Display #0 (activities from top to bottom):
+  * Task{4d1e2f3 #2 type=home I=com.oculus.vrshell/.MainActivity U=0 visible=true mode=fullscreen}
+    mResumedActivity: ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+    * Hist #0: ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+  * Task{5e6f7a8 #34 type=standard A=10123:alvr.client U=0 visible=false mode=fullscreen}
+    mLastPausedActivity: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+    * Hist #0: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+
+  Resumed activities in task display areas (from top to bottom):
+    ResumedActivity:ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+
+  ResumedActivity:ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+";
+
+    const DUMPSYS_ACTIVITIES_ANDROID_14: &str = "\
+ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+Display #0 (activities from top to bottom):
+  * Task{4d1e2f3 #2 type=home I=com.oculus.vrshell/.MainActivity U=0 visible=true mode=fullscreen}
+    topResumedActivity=ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+    * Hist #0: ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+
+Display #3 (activities from top to bottom):
+  * Task{5e6f7a8 #34 type=standard A=10123:alvr.client U=0 visible=true mode=multi-window}
+    mResumedActivity: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+    * Hist #0: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+
+  Resumed activities in task display areas (from top to bottom):
+    ResumedActivity:ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+    ResumedActivity:ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+";
+
+    #[test]
+    fn test_parse_activity_state_android_10() {
+        assert_eq!(
+            parse_activity_state(DUMPSYS_ACTIVITIES_ANDROID_10, "alvr.client"),
+            ActivityState::Resumed
+        );
+        assert_eq!(
+            parse_activity_state(DUMPSYS_ACTIVITIES_ANDROID_10, "com.oculus.vrshell"),
+            ActivityState::Paused
+        );
+    }
+
+    #[test]
+    fn test_parse_activity_state_android_12() {
+        assert_eq!(
+            parse_activity_state(DUMPSYS_ACTIVITIES_ANDROID_12, "alvr.client"),
+            ActivityState::Paused
+        );
+        assert_eq!(
+            parse_activity_state(DUMPSYS_ACTIVITIES_ANDROID_12, "com.oculus.vrshell"),
+            ActivityState::Resumed
+        );
+    }
+
+    #[test]
+    fn test_parse_activity_state_android_14_secondary_display() {
+        assert_eq!(
+            parse_activity_state(DUMPSYS_ACTIVITIES_ANDROID_14, "alvr.client"),
+            ActivityState::Resumed
+        );
+        assert_eq!(
+            parse_activity_state(DUMPSYS_ACTIVITIES_ANDROID_14, "alvr.client.dev"),
+            ActivityState::Paused
+        );
+    }
+
+    #[test]
+    fn test_parse_activity_state_legacy_and_unknown() {
+        let text = "  mFocusedActivity: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}\n";
+        assert_eq!(
+            parse_activity_state(text, "alvr.client"),
+            ActivityState::Resumed
+        );
+        assert_eq!(
+            parse_activity_state("  mResumedActivity: null\n", "alvr.client"),
+            ActivityState::Paused
+        );
+        assert_eq!(
+            parse_activity_state(
+                "Permission Denial: can\'t dump ActivityManager",
+                "alvr.client"
+            ),
+            ActivityState::Unknown
+        );
+    }
+
+    #[test]
+    fn test_parse_activity_resumed() {
+        let text =
+            "  Local Activity e4c1a2b State:\n    mResumed=true mStopped=false mFinished=false\n";
+        assert_eq!(parse_activity_resumed(text), Some(true));
+        assert_eq!(
+            parse_activity_resumed(&text.replace("mResumed=true", "mResumed=false")),
+            Some(false)
+        );
+        assert_eq!(parse_activity_resumed("No activities found"), None);
+    }
+
+    #[test]
+    fn test_parse_battery_state() {
+        let text = "\
+Current Battery Service state:
+  AC powered: false
+  USB powered: true
+  Wireless powered: false
+  Max charging current: 500000
+  Max charging voltage: 5000000
+  status: 2
+  health: 2
+  present: true
+  level: 85
+  scale: 100
+  voltage: 4200
+  temperature: 300
+  technology: Li-ion
+";
+        let state = parse_battery_state(text).unwrap();
+        assert_eq!(state.level_percent, Some(85));
+        assert_eq!(state.status, BatteryStatus::Charging);
+        assert_eq!(state.max_charging_current_ua, Some(500000));
+        assert!(state.usb_powered && state.is_plugged() && state.is_charging());
+        assert!(!state.is_underpowered());
+
+        let state = parse_battery_state(&text.replace("status: 2", "status: 3")).unwrap();
+        assert!(!state.is_charging());
+        assert!(state.is_underpowered());
+
+        assert_eq!(parse_battery_state("Can\'t find service: battery"), None);
+    }
+
+    #[test]
+    fn test_parse_activity_state_visible_in_panel() {
+        let text = "\
+ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+Display #0 (activities from top to bottom):
+  * Task{4d1e2f3 #40 type=standard A=10090:com.oculus.browser U=0 visible=true mode=multi-window}
+    mResumedActivity: ActivityRecord{9f8e7d6 u0 com.oculus.browser/.PanelActivity t40}
+  * Task{5e6f7a8 #34 type=standard A=10123:alvr.client U=0 visible=true mode=multi-window}
+    mLastPausedActivity: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+
+  ResumedActivity:ActivityRecord{9f8e7d6 u0 com.oculus.browser/.PanelActivity t40}
+";
+        assert_eq!(
+            parse_activity_state(text, "alvr.client"),
+            ActivityState::Visible
+        );
+        assert_eq!(
+            parse_activity_state(
+                &text.replace(
+                    "U=0 visible=true mode=multi-window}\n    mLast",
+                    "U=0 visible=false mode=multi-window}\n    mLast"
+                ),
+                "alvr.client"
+            ),
+            ActivityState::Paused
+        );
+
+        let text = "\
+  Stack #34: type=standard mode=fullscreen
+      * Hist #0: ActivityRecord{e4c1a2b u0 alvr.client/.MainActivity t34}
+          state=PAUSED stopped=false delayedResume=false finishing=false
+          keysPaused=false inHistory=true visible=true sleeping=false idle=true
+      * Hist #1: ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+          keysPaused=false inHistory=true visible=false sleeping=false idle=true
+ ResumedActivity: ActivityRecord{1a2b3c4 u0 com.oculus.vrshell/.MainActivity t2}
+";
+        assert_eq!(
+            parse_activity_state(text, "alvr.client"),
+            ActivityState::Visible
+        );
+        assert_eq!(
+            parse_activity_state(text, "com.oculus.vrshell"),
+            ActivityState::Resumed
+        );
+    }
+
+    #[test]
+    fn test_parse_stay_on_while_plugged_in() {
+        assert_eq!(parse_stay_on_while_plugged_in("7\n"), Some(7));
+        assert_eq!(parse_stay_on_while_plugged_in("null\n"), Some(0));
+        assert_eq!(parse_stay_on_while_plugged_in(""), None);
+    }
+
+    #[test]
+    fn test_parse_transport_type() {
+        let output = "\
+List of devices attached
+1WMHH000000000         device usb:1-1 product:hollywood model:Quest_2 device:hollywood transport_id:3
+192.168.1.20:5555      device product:eureka model:Quest_3 device:eureka transport_id:4
+adb-2G0YC1ZF8B07WD-AbCdEf._adb-tls-connect._tcp device product:eureka model:Quest_3 device:eureka transport_id:5
+emulator-5554          device product:sdk_gphone64_x86_64 model:sdk_gphone64_x86_64 transport_id:6
+(no serial number)     no permissions (missing udev rules?); see [http://developer.android.com/tools/device.html] usb:1-2 transport_id:7
+";
+        let transport_types = parse_devices(output)
+            .iter()
+            .map(parse_transport_type)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            transport_types,
+            vec![
+                TransportType::Usb,
+                TransportType::Tcp,
+                TransportType::Tcp,
+                TransportType::Emulator,
+                TransportType::Usb,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_process_start_ticks() {
+        let stat = "4321 (alvr client) S 712 712 0 0 -1 1077952832 41223 0 2 0 1034 221 0 0 10 -10 \
+58 0 123456 16201719808 41201 18446744073709551615";
+        assert_eq!(parse_process_start_ticks(stat), Some(123456));
+        assert_eq!(parse_process_start_ticks("4321 (alvr.client) S 712"), None);
+    }
+
+    #[test]
+    fn test_parse_package_update_time() {
+        let text = "\
+Packages:
+  Package [alvr.client] (b1c2d3e):
+    versionCode=42 minSdk=29 targetSdk=32
+    firstInstallTime=2024-04-30 08:00:00
+    lastUpdateTime=2024-05-01 12:34:56
+";
+        assert_eq!(parse_package_update_time(text), Some(1714566896));
+        assert_eq!(
+            parse_package_update_time("lastUpdateTime=1970-01-01 00:00:00"),
+            Some(0)
+        );
+        assert_eq!(
+            parse_package_update_time("lastUpdateTime=2024-13-01 00:00:00"),
+            None
+        );
+        assert_eq!(parse_package_update_time("versionCode=42"), None);
+    }
+
+    #[test]
+    fn test_parse_device_clock() {
+        let clock = parse_device_clock("1714567890 +0200\n").unwrap();
+        assert_eq!(
+            clock,
+            DeviceClock {
+                unix_secs: 1714567890,
+                utc_offset_secs: 7200,
+            }
+        );
+        assert_eq!(clock.local_secs(), 1714575090);
+        assert_eq!(
+            parse_device_clock("1714567890 -0930").map(|c| c.utc_offset_secs),
+            Some(-34200)
+        );
+        assert_eq!(parse_device_clock("1714567890"), None);
+    }
+
+    #[test]
+    fn test_parse_process_importance_android_8() {
+        let text = "\
+ACTIVITY MANAGER RUNNING PROCESSES (dumpsys activity processes)
+  Process LRU list (sorted by oom_adj, 24 total, non-act at 2, non-svc at 2):
+    PERS #23: sys   F/ /P   trm: 0 1234:system/1000 (fixed)
+    Proc # 0: fore  T/A/TOP  trm: 0 4321:alvr.client/u0a123 (top-activity)
+    Proc # 9: cch+2  B/ /CE   trm: 0 5678:alvr.client.dev/u0a124 (cch-empty)
+";
+        assert_eq!(
+            parse_process_importance(text, "alvr.client"),
+            Some(ProcessImportance {
+                level: ImportanceLevel::Foreground,
+                reason: Some("top-activity".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse_process_importance(text, "alvr.client.dev").map(|i| i.level),
+            Some(ImportanceLevel::Cached)
+        );
+        assert_eq!(
+            parse_process_importance(text, "system").map(|i| i.level),
+            Some(ImportanceLevel::Persistent)
+        );
+    }
+
+    #[test]
+    fn test_parse_process_importance_android_10() {
+        let text = "\
+ACTIVITY MANAGER RUNNING PROCESSES (dumpsys activity processes)
+  Process LRU list (sorted by oom_adj, 76 total, non-act at 2, non-svc at 2):
+    Proc #20: prcp   F/S/FGS  ---  t: 0 4321:alvr.client/u0a123 (fg-service)
+    Proc # 5: cch+ 5 B/ /CEM  ---- t: 0 5678:alvr.client.dev/u0a124 (cch-empty)
+    Proc # 4: vis    F/ /BFGS ---  t: 0 6789:com.oculus.vrshell/u0a55 (service)
+";
+        assert_eq!(
+            parse_process_importance(text, "alvr.client"),
+            Some(ProcessImportance {
+                level: ImportanceLevel::Perceptible,
+                reason: Some("fg-service".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse_process_importance(text, "alvr.client.dev"),
+            Some(ProcessImportance {
+                level: ImportanceLevel::Cached,
+                reason: Some("cch-empty".to_owned()),
+            })
+        );
+        assert_eq!(parse_process_importance(text, "alvr.client.stable"), None);
+    }
+
+    #[test]
+    fn test_parse_process_importance_android_14() {
+        let text = "\
+ACTIVITY MANAGER RUNNING PROCESSES (dumpsys activity processes)
+  Process LRU list (sorted by oom_adj, 81 total, non-act at 3, non-svc at 3):
+    Proc #21: fg     T/A/TOP  LCM  t: 0 4321:alvr.client/u0a123 (top-activity)
+    Proc #12: prev   B/ /LAST ---- t: 0 5678:com.oculus.browser/u0a90 (previous)
+";
+        assert_eq!(
+            parse_process_importance(text, "alvr.client"),
+            Some(ProcessImportance {
+                level: ImportanceLevel::Foreground,
+                reason: Some("top-activity".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse_process_importance(text, "com.oculus.browser").map(|i| i.level),
+            Some(ImportanceLevel::Previous)
+        );
+    }
+
+    #[test]
+    fn test_parse_last_crash() {
+        let text = "\
+Drop box contents: 3 entries
+Max entries: 1000
+Searching for: data_app_crash
+
+========================================
+2024-05-01 12:00:00 data_app_crash (text, 1234 bytes)
+Process: alvr.client
+PID: 4321
+Flags: 0x38c83e44
+Package: alvr.client v42 (20.12.0)
+
+java.lang.IllegalStateException: old crash
+\tat alvr.client.MainActivity.onCreate(MainActivity.java:10)
+
+========================================
+2024-05-01 12:34:56 data_app_crash (text, 1234 bytes)
+Process: alvr.client
+PID: 5432
+Package: alvr.client v42 (20.12.0)
+
+java.lang.UnsatisfiedLinkError: dlopen failed: library \"libalvr_client_openxr.so\" not found
+\tat java.lang.Runtime.loadLibrary0(Runtime.java:1082)
+
+========================================
+2024-05-01 13:00:00 data_app_crash (text, 1234 bytes)
+Process: com.oculus.browser
+PID: 6543
+
+java.lang.NullPointerException
+";
+        assert_eq!(
+            parse_last_crash(text, "alvr.client"),
+            Some(CrashReport {
+                kind: CrashKind::Crash,
+                time: 1714566896,
+                summary: "java.lang.UnsatisfiedLinkError: dlopen failed: library \"libalvr_client_openxr.so\" not found".to_owned(),
+            })
+        );
+        assert_eq!(parse_last_crash(text, "alvr.client.dev"), None);
+
+        let text = "\
+========================================
+2024-05-01 12:34:56 data_app_native_crash (text, 4567 bytes)
+Process: alvr.client
+PID: 4321
+
+*** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***
+pid: 4321, tid: 4350, name: main  >>> alvr.client <<<
+signal 6 (SIGABRT), code -1 (SI_QUEUE), fault addr --------
+Abort message: 'Failed to create OpenXR instance'
+";
+        assert_eq!(
+            parse_last_crash(text, "alvr.client").map(|c| (c.kind, c.summary)),
+            Some((
+                CrashKind::NativeCrash,
+                "'Failed to create OpenXR instance'".to_owned()
+            ))
+        );
+
+        let text = "\
+========================================
+2024-05-01 12:34:56 data_app_anr (text, 4567 bytes)
+Process: alvr.client
+PID: 4321
+Subject: Input dispatching timed out (Waiting to send non-key event)
+
+CPU usage from 0ms to 6231ms later:
+";
+        assert_eq!(
+            parse_last_crash(text, "alvr.client").map(|c| (c.kind, c.summary)),
+            Some((
+                CrashKind::Anr,
+                "Input dispatching timed out (Waiting to send non-key event)".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_adb_version() {
+        let text = "\
+Android Debug Bridge version 1.0.41
+Version 35.0.0-11411520
+Installed as /home/user/.local/share/ALVR/platform-tools/adb
+Running on Linux 6.8.0 (x86_64)
+";
+        assert_eq!(parse_adb_version(text), Some("35.0.0".to_owned()));
+        assert_eq!(
+            parse_adb_version("Android Debug Bridge version 1.0.39"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_memory_info() {
+        let text = "\
+Applications Memory Usage (in Kilobytes):
+Uptime: 1234567 Realtime: 1234567
+
+** MEMINFO in pid 4321 [alvr.client] **
+                   Pss  Private  Private  SwapPss      Rss     Heap     Heap     Heap
+                 Total    Dirty    Clean    Dirty    Total     Size    Alloc     Free
+                ------   ------   ------   ------   ------   ------   ------   ------
+  Native Heap    52340    52300        0        0    53120    65536    60000     5536
+        GL mtrack   145678   145678        0        0   145678
+       Unknown     8812     8800        0        0     9000
+         TOTAL   301234   290000     4000        0   320000    70000    62000     8000
+
+ App Summary
+                       Pss(KB)                        Rss(KB)
+                        ------                         ------
+           Java Heap:     2048                           4096
+              Graphics:   145678                         145678
+
+           TOTAL PSS:   301234            TOTAL RSS:   320000       TOTAL SWAP PSS:        0
+";
+        assert_eq!(
+            parse_memory_info(text),
+            Some(MemoryInfo {
+                total_pss_kb: 301234,
+                gl_mtrack_kb: Some(145678),
+            })
+        );
+
+        // Android 9 and drivers without GL memory tracking
+        let text = "\
+** MEMINFO in pid 4321 [alvr.client] **
+                   Pss  Private  Private  SwapPss     Heap     Heap     Heap
+                 Total    Dirty    Clean    Dirty     Size    Alloc     Free
+                ------   ------   ------   ------   ------   ------   ------
+  Native Heap    52340    52300        0        0    65536    60000     5536
+         TOTAL   155556   150000     4000        0    70000    62000     8000
+";
+        assert_eq!(
+            parse_memory_info(text),
+            Some(MemoryInfo {
+                total_pss_kb: 155556,
+                gl_mtrack_kb: None,
+            })
+        );
+
+        assert_eq!(parse_memory_info("No process found for: alvr.client"), None);
+    }
+
+    #[test]
+    fn test_parse_graphics_info() {
+        let text = "\
+Applications Graphics Acceleration Info:
+Uptime: 1234567 Realtime: 1234567
+
+** Graphics info for pid 4321 [alvr.client] **
+
+Stats since: 1234567890ns
+Total frames rendered: 1200
+Janky frames: 54 (4.50%)
+50th percentile: 8ms
+90th percentile: 13ms
+95th percentile: 17ms
+99th percentile: 34ms
+Number Missed Vsync: 3
+HISTOGRAM: 5ms=100 6ms=400 7ms=500 8ms=200 150ms=0
+50th gpu percentile: 4ms
+GPU HISTOGRAM: 1ms=0 2ms=1200
+";
+        let info = parse_graphics_info(text).unwrap();
+        assert_eq!(info.total_frames, 1200);
+        assert_eq!(info.janky_frames, 54);
+        assert_eq!(info.percentile_50_ms, Some(8));
+        assert_eq!(info.percentile_99_ms, Some(34));
+        assert_eq!(
+            info.histogram,
+            vec![(5, 100), (6, 400), (7, 500), (8, 200), (150, 0)]
+        );
+
+        // Native renderers never draw through the UI toolkit
+        let text = "\
+** Graphics info for pid 4321 [alvr.client] **
+
+Stats since: 1234567890ns
+Total frames rendered: 0
+Janky frames: 0 (0.00%)
+";
+        assert_eq!(parse_graphics_info(text), None);
+        assert_eq!(parse_graphics_info(""), None);
+    }
+
+    #[test]
+    fn test_parse_launch_result() {
+        let text = "\
+Starting: Intent { cmp=alvr.client/android.app.NativeActivity }
+Status: ok
+LaunchState: COLD
+Activity: alvr.client/android.app.NativeActivity
+TotalTime: 1843
+WaitTime: 1851
+Complete
+";
+        assert_eq!(
+            parse_launch_result(text),
+            LaunchResult::Started {
+                total_time_ms: Some(1843)
+            }
+        );
+
+        let text = "\
+Starting: Intent { cmp=alvr.client/android.app.NativeActivity }
+Warning: Activity not started, its current task has been brought to the front
+Status: ok
+LaunchState: UNKNOWN (0)
+Activity: alvr.client/android.app.NativeActivity
+WaitTime: 12
+Complete
+";
+        assert_eq!(
+            parse_launch_result(text),
+            LaunchResult::Started {
+                total_time_ms: None
+            }
+        );
+
+        let text = "\
+Starting: Intent { cmp=alvr.client/android.app.NativeActivity }
+Status: timeout
+LaunchState: UNKNOWN (0)
+Activity: alvr.client/android.app.NativeActivity
+WaitTime: 10003
+Complete
+";
+        assert_eq!(parse_launch_result(text), LaunchResult::TimedOut);
+    }
+
+    #[test]
+    fn test_parse_launch_result_errors() {
+        let text = "\
+Starting: Intent { cmp=alvr.client/android.app.NativeActivity }
+Error: Activity not started, voice control not allowed for: Intent { cmp=alvr.client/android.app.NativeActivity }
+";
+        assert_eq!(
+            parse_launch_result(text),
+            LaunchResult::Failed(LaunchError::Unknown(
+                "Activity not started, voice control not allowed for: Intent { \
+cmp=alvr.client/android.app.NativeActivity }"
+                    .to_owned()
+            ))
+        );
+
+        let text = "\
+Starting: Intent { cmp=alvr.client/android.app.NativeActivity }
+Status: abort
+";
+        assert_eq!(
+            parse_launch_result(text),
+            LaunchResult::Failed(LaunchError::Unknown("Launch status is abort".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_parse_launch_error() {
+        // Quest 2, monkey with a package that is not installed
+        let text = "\
+  bash arg: -p
+  bash arg: alvr.client
+  bash arg: 1
+args: [-p, alvr.client, 1]
+ arg: \"-p\"
+ arg: \"alvr.client\"
+ arg: \"1\"
+data=\"alvr.client\"
+** No activities found to run, monkey aborted.
+";
+        assert_eq!(parse_launch_error(text), Some(LaunchError::PackageNotFound));
+
+        // Quest 2, monkey launch that succeeded
+        let text = "\
+Events injected: 1
+## Network stats: elapsed time=27ms (0ms mobile, 0ms wifi, 27ms not connected)
+";
+        assert_eq!(parse_launch_error(text), None);
+
+        // Quest 3, activity of a custom client that is not exported
+        let text = "\
+Starting: Intent { cmp=my.client/.VrActivity }
+Exception occurred while executing 'start':
+java.lang.SecurityException: Permission Denial: starting Intent { flg=0x10000000 cmp=my.client/.VrActivity } from null (pid=4321, uid=2000) not exported from uid 10123
+\tat com.android.server.wm.ActivityTaskSupervisor.checkStartAnyActivityPermission(ActivityTaskSupervisor.java:1152)
+";
+        assert_eq!(
+            parse_launch_error(text),
+            Some(LaunchError::PermissionDenied)
+        );
+
+        // Quest 3, signed in with a secondary account
+        let text = "\
+Starting: Intent { cmp=alvr.client/android.app.NativeActivity }
+Exception occurred while executing 'start':
+java.lang.SecurityException: Shell does not have permission to access user 10
+ com.android.server.am.ActivityManagerService.handleIncomingUser:14523 com.android.server.am.ActivityManagerShellCommand.runStartActivity:549
+\tat com.android.server.am.UserController.handleIncomingUser(UserController.java:2675)
+";
+        assert_eq!(parse_launch_error(text), Some(LaunchError::UserRestricted));
+
+        // Pico 4, activity renamed by a newer client build
+        let text = "\
+Starting: Intent { cmp=alvr.client/.MainActivity }
+Error type 3
+Error: Activity class {alvr.client/alvr.client.MainActivity} does not exist.
+";
+        assert_eq!(
+            parse_launch_error(text),
+            Some(LaunchError::ActivityNotFound)
+        );
+
+        // Pico Neo 3, package that is not installed
+        let text = "\
+Starting: Intent { cmp=alvr.client/android.app.NativeActivity }
+Error: Activity not started, unable to resolve Intent { flg=0x10000000 cmp=alvr.client/android.app.NativeActivity }
+";
+        assert_eq!(parse_launch_error(text), Some(LaunchError::PackageNotFound));
+    }
+
+    #[test]
+    fn test_parse_install_session_id() {
+        assert_eq!(
+            parse_install_session_id("Success: created install session [1502768458]\n"),
+            Some(1502768458)
+        );
+        assert_eq!(
+            parse_install_session_id("Error: java.lang.SecurityException: Permission Denial"),
+            None
+        );
+        assert_eq!(
+            parse_install_session_id("Unknown command: install-create"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_install_progress() {
+        let text = "Performing Streamed Install\n\
+                    [  0%] /data/local/tmp/alvr_client.apk\r\
+                    [ 45%] /data/local/tmp/alvr_client.apk\r\
+                    [100%] /data/local/tmp/alvr_client.apk\n\
+                    Success\n";
+        let progress = text
+            .split(['\r', '\n'])
+            .filter_map(parse_install_progress)
+            .collect::<Vec<_>>();
+        assert_eq!(progress, [0, 0, 45, 100]);
+
+        // Older versions only print the result
+        assert_eq!(parse_install_progress("Success"), None);
+        assert_eq!(
+            parse_install_progress("adb: failed to install app.apk: Failure [INSTALL_FAILED]"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_setting_value() {
+        assert_eq!(parse_setting_value("72\n"), Some("72".to_owned()));
+        assert_eq!(parse_setting_value("null\n"), None);
+        assert_eq!(parse_setting_value("\n"), Some(String::new()));
+    }
+
+    #[test]
+    fn test_parse_content_query_value() {
+        let text = "Row: 0 package_name=org.example.openxr, native_lib_dir=/data/app/lib/arm64, so_filename=libruntime.so\n";
+        assert_eq!(
+            parse_content_query_value(text, "package_name"),
+            Some("org.example.openxr".to_owned())
+        );
+        assert_eq!(
+            parse_content_query_value(text, "so_filename"),
+            Some("libruntime.so".to_owned())
+        );
+        assert_eq!(parse_content_query_value(text, "package"), None);
+        assert_eq!(
+            parse_content_query_value("Row: 0 package_name=NULL\n", "package_name"),
+            None
+        );
+        assert_eq!(
+            parse_content_query_value("No result found.\n", "package_name"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_displays() {
+        let text = r#"DISPLAY MANAGER (dumpsys display)
+Logical Displays: size=2
+  Display 0:
+    mDisplayId=0
+    mBaseDisplayInfo=DisplayInfo{"Built-in Screen", displayId 0, displayGroupId 0, FLAG_SECURE, real 3664 x 1920, largest app 3664 x 3664, 90.0 fps}
+    mOverrideDisplayInfo=DisplayInfo{"Built-in Screen", displayId 0, displayGroupId 0, FLAG_SECURE, real 3664 x 1920, largest app 3664 x 3664, 90.0 fps}
+  Display 2:
+    mDisplayId=2
+    mBaseDisplayInfo=DisplayInfo{"HDMI Screen", displayId 2, displayGroupId 0, real 1920 x 1080, largest app 1920 x 1920, 60.0 fps}
+"#;
+        assert_eq!(
+            parse_displays(text),
+            vec![
+                Display {
+                    id: 0,
+                    name: "Built-in Screen".to_owned(),
+                    width: Some(3664),
+                    height: Some(1920),
+                },
+                Display {
+                    id: 2,
+                    name: "HDMI Screen".to_owned(),
+                    width: Some(1920),
+                    height: Some(1080),
+                },
+            ]
+        );
+
+        // Android 9 does not print the id
+        assert_eq!(
+            parse_displays(
+                r#"mBaseDisplayInfo=DisplayInfo{"Built-in Screen", uniqueId "local:0", app 1440 x 2560, real 1440 x 2560}"#
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_is_server_port_in_use() {
+        assert!(is_server_port_in_use(
+            "error: could not install *smartsocket* listener: cannot bind to 127.0.0.1:5037: Address already in use (98)\nADB server didn't ACK\n* failed to start daemon"
+        ));
+        assert!(!is_server_port_in_use(
+            "adb server version (39) doesn't match this client (41); killing..."
+        ));
+    }
+
+    #[test]
+    fn test_parse_netstat_listening_pids() {
+        let text = "
+Active Connections
+
+  Proto  Local Address          Foreign Address        State           PID
+  TCP    0.0.0.0:135            0.0.0.0:0              LISTENING       1052
+  TCP    127.0.0.1:5037         0.0.0.0:0              LISTENING       7712
+  TCP    127.0.0.1:5037         127.0.0.1:50123        ESTABLISHED     7712
+  TCP    [::1]:5037             [::]:0                 LISTENING       7712
+  TCP    127.0.0.1:50370        0.0.0.0:0              LISTENING       999
+";
+        assert_eq!(parse_netstat_listening_pids(text, 5037), vec![7712]);
+        assert!(parse_netstat_listening_pids(text, 9943).is_empty());
+    }
+
+    #[test]
+    fn test_parse_tasklist_image_name() {
+        assert_eq!(
+            parse_tasklist_image_name("\"adb.exe\",\"7712\",\"Console\",\"1\",\"8,180 K\"\r\n"),
+            Some("adb.exe".to_owned())
+        );
+        assert_eq!(
+            parse_tasklist_image_name(
+                "INFO: No tasks are running which match the specified criteria.\r\n"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_dumpsys_restricted() {
+        assert!(is_dumpsys_restricted(
+            "Permission Denial: can't dump ActivityManager from from pid=8123, uid=2000 due to missing android.permission.DUMP permission\n"
+        ));
+        assert!(is_dumpsys_restricted("Can't find service: package\n"));
+        assert!(!is_dumpsys_restricted(
+            "Packages:\n  Package [alvr.client] (5f2e1a0):\n    lastUpdateTime=2024-05-01 10:00:00\n"
+        ));
+    }
+
+    #[test]
+    fn test_parse_package_version() {
+        let text = "\
+Packages:
+  Package [alvr.client] (b1c2d3e):
+    versionCode=42 minSdk=29 targetSdk=32
+    versionName=20.11.0
+    lastUpdateTime=2024-05-01 12:34:56
+Hidden system packages:
+  Package [alvr.client] (f4e5d6c):
+    versionName=20.0.0
+";
+        assert_eq!(parse_package_version(text), Some("20.11.0".into()));
+        assert_eq!(parse_package_version("versionName="), None);
+        assert_eq!(parse_package_version("versionCode=42"), None);
+    }
+
+    #[test]
+    fn test_parse_granted_permissions() {
+        let text = "\
+Packages:
+  Package [alvr.client] (b1c2d3e):
+    requested permissions:
+      android.permission.INTERNET
+      android.permission.RECORD_AUDIO
+    install permissions:
+      android.permission.INTERNET: granted=true
+    User 0: ceDataInode=12345 installed=true hidden=false
+      runtime permissions:
+        android.permission.RECORD_AUDIO: granted=false, flags=[ USER_SENSITIVE_WHEN_GRANTED ]
+        android.permission.POST_NOTIFICATIONS: granted=true
+";
+        assert_eq!(
+            parse_granted_permissions(text),
+            HashSet::from([
+                "android.permission.INTERNET".to_owned(),
+                "android.permission.POST_NOTIFICATIONS".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_adb_error() {
+        assert!(matches!(
+            parse_adb_error("error: device 'abc' not found\n"),
+            Some(AdbError::DeviceNotFound(message)) if message == "error: device 'abc' not found"
+        ));
+        assert!(matches!(
+            parse_adb_error("error: no devices/emulators found"),
+            Some(AdbError::DeviceNotFound(_))
+        ));
+        assert!(matches!(
+            parse_adb_error("error: closed"),
+            Some(AdbError::DeviceNotFound(_))
+        ));
+        assert!(matches!(
+            parse_adb_error(
+                "error: device unauthorized.\nThis adb server's $ADB_VENDOR_KEYS is not set\n"
+            ),
+            Some(AdbError::DeviceUnauthorized(_))
+        ));
+        assert!(matches!(
+            parse_adb_error(
+                "error: insufficient permissions for device: user in plugdev group; are your udev \
+                 rules wrong?"
+            ),
+            Some(AdbError::DeviceUnauthorized(_))
+        ));
+        assert!(matches!(
+            parse_adb_error(
+                "* daemon not running; starting now at tcp:5037\n\
+                 could not install *smartsocket* listener: cannot bind to 127.0.0.1:5037: \
+                 Address already in use\n\
+                 * failed to start daemon\n\
+                 error: cannot connect to daemon\n"
+            ),
+            Some(AdbError::ServerUnreachable(message)) if is_server_port_in_use(&message)
+        ));
+        assert!(parse_adb_error("* daemon started successfully\n").is_none());
+        assert!(
+            parse_adb_error("Error: Activity class {alvr.client/Main} does not exist.").is_none()
+        );
+        assert!(parse_adb_error("").is_none());
+    }
+
+    #[test]
+    fn test_parse_prop_bool() {
+        assert_eq!(parse_prop_bool("1\n"), Some(true));
+        assert_eq!(parse_prop_bool("true"), Some(true));
+        assert_eq!(parse_prop_bool("0"), Some(false));
+        assert_eq!(parse_prop_bool("off"), Some(false));
+        assert_eq!(parse_prop_bool(""), None);
+        assert_eq!(parse_prop_bool("2"), None);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::parse::ConnectionState;
+use crate::{WiredConnection, commands};
+use alvr_common::anyhow::{Result, bail};
+use std::fmt::{self, Display, Formatter};
+use std::{env, fs, process};
+
+// Nothing needs to listen on the device, adb only connects to it when the local port is used
+const SELF_TEST_REMOTE_PORT: u16 = 9940;
+const SELF_TEST_REMOTE_PATH: &str = "/data/local/tmp/alvr_self_test";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelfTestResult {
+    Pass(String),
+    Fail(String),
+    // A check it depends on failed
+    Skip,
+}
+
+impl Display for SelfTestResult {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SelfTestResult::Pass(details) => write!(f, "pass ({details})"),
+            SelfTestResult::Fail(error) => write!(f, "FAIL ({error})"),
+            SelfTestResult::Skip => write!(f, "skipped"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfTestItem {
+    pub name: &'static str,
+    pub result: SelfTestResult,
+}
+
+/// Result of `WiredConnection::self_test`. Its `Display` output is meant to be pasted in bug
+/// reports.
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub items: Vec<SelfTestItem>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.items
+            .iter()
+            .all(|item| !matches!(item.result, SelfTestResult::Fail(_)))
+    }
+
+    // Returns the value if the check passed, so that the checks depending on it can run
+    fn check<T>(
+        &mut self,
+        name: &'static str,
+        enabled: bool,
+        check: impl FnOnce() -> Result<(T, String)>,
+    ) -> Option<T> {
+        let (value, result) = if enabled {
+            match check() {
+                Ok((value, details)) => (Some(value), SelfTestResult::Pass(details)),
+                Err(e) => (None, SelfTestResult::Fail(format!("{e:#}"))),
+            }
+        } else {
+            (None, SelfTestResult::Skip)
+        };
+        self.items.push(SelfTestItem { name, result });
+
+        value
+    }
+}
+
+impl Display for SelfTestReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for item in &self.items {
+            writeln!(f, "{}: {}", item.name, item.result)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl WiredConnection {
+    /// Checks every part of the toolchain the wired connection relies on: the adb executable, the
+    /// server, an authorized device, port forwarding and file transfers.
+    pub fn self_test(&self) -> SelfTestReport {
+        let mut report = SelfTestReport::default();
+
+        let adb_found = report
+            .check("ADB executable", true, || {
+                let version = commands::get_adb_version(&self.adb_path)?;
+
+                Ok(((), format!("version {version} at {}", self.adb_path)))
+            })
+            .is_some();
+
+        let server_running = report
+            .check("ADB server", adb_found, || {
+                commands::start_server(&self.adb_path)?;
+
+                Ok(((), "running".to_owned()))
+            })
+            .is_some();
+
+        let device_serial = report.check("Authorized device", server_running, || {
+            let devices = commands::list_devices(&self.adb_path)?;
+            let mut devices = devices
+                .iter()
+                .filter(|device| self.device_watcher.matches_filter(device));
+            let Some(device) = devices
+                .clone()
+                .find(|device| device.state == Some(ConnectionState::Device))
+            else {
+                match devices.next() {
+                    Some(device) => bail!("Device is in state {:?}", device.state),
+                    None => bail!("No device found"),
+                }
+            };
+            let serial = device.serial.clone().unwrap_or_default();
+
+            Ok((serial.clone(), serial))
+        });
+
+        report.check("Displays", device_serial.is_some(), || {
+            let device_serial = device_serial.as_deref().unwrap_or_default();
+            let displays = commands::list_displays(&self.adb_path, device_serial)?
+                .into_iter()
+                .map(|display| match (display.width, display.height) {
+                    (Some(width), Some(height)) => {
+                        format!("{} \"{}\" {width}x{height}", display.id, display.name)
+                    }
+                    _ => format!("{} \"{}\"", display.id, display.name),
+                })
+                .collect::<Vec<_>>();
+
+            Ok(((), displays.join(", ")))
+        });
+
+        report.check("Port forwarding", device_serial.is_some(), || {
+            let device_serial = device_serial.as_deref().unwrap_or_default();
+            let port =
+                commands::forward_free_port(&self.adb_path, device_serial, SELF_TEST_REMOTE_PORT)?;
+            commands::remove_forward(&self.adb_path, device_serial, port)?;
+
+            Ok(((), format!("forwarded local port {port}")))
+        });
+
+        report.check("File transfer", device_serial.is_some(), || {
+            let device_serial = device_serial.as_deref().unwrap_or_default();
+
+            let data = format!("ALVR self test {}", process::id()).into_bytes();
+            let local_path = env::temp_dir().join("alvr_self_test");
+            fs::write(&local_path, &data)?;
+
+            let res = commands::push_file(
+                &self.adb_path,
+                device_serial,
+                &local_path.to_string_lossy(),
+                SELF_TEST_REMOTE_PATH,
+                &self.cancel_handle,
+                |_, _| (),
+            )
+            .and_then(|_| {
+                commands::pull_file(&self.adb_path, device_serial, SELF_TEST_REMOTE_PATH)
+            });
+            fs::remove_file(&local_path).ok();
+            commands::remove_file(&self.adb_path, device_serial, SELF_TEST_REMOTE_PATH).ok();
+
+            if res? != data {
+                bail!("Pulled file differs from the pushed one");
+            }
+
+            Ok(((), format!("{} bytes round-tripped", data.len())))
+        });
+
+        report
+    }
+}
@@ -0,0 +1,149 @@
+use crate::commands;
+use alvr_common::{dbg_connection, error};
+use std::sync::Mutex;
+
+// Connections and fleets of this process which use the ADB server, e.g. one held by the dashboard
+// to list devices and one by the connection thread
+static SERVER_USERS: Mutex<ServerUsers> = Mutex::new(ServerUsers::new());
+
+pub(crate) struct ServerUsers {
+    count: usize,
+    // Whether the server was not running yet when the first user was created
+    started_by_alvr: bool,
+}
+
+impl ServerUsers {
+    pub(crate) const fn new() -> Self {
+        Self {
+            count: 0,
+            started_by_alvr: false,
+        }
+    }
+
+    fn acquire(&mut self, server_running: impl FnOnce() -> bool) {
+        if self.count == 0 {
+            self.started_by_alvr = !server_running();
+        }
+        self.count += 1;
+    }
+
+    // Returns whether the server must be killed
+    fn release(&mut self) -> bool {
+        self.count = self.count.saturating_sub(1);
+
+        self.count == 0 && self.started_by_alvr
+    }
+}
+
+/// Share of the ADB server held by the connections that own it. The server is killed when the last
+/// lease is released, unless it was already running when the first one was acquired. Dropping the
+/// lease releases it.
+pub(crate) struct ServerLease {
+    adb_path: String,
+    users: &'static Mutex<ServerUsers>,
+    kill_server: fn(&str),
+    released: bool,
+}
+
+impl ServerLease {
+    pub(crate) fn acquire(adb_path: String) -> Self {
+        Self::acquire_from(
+            &SERVER_USERS,
+            adb_path,
+            commands::is_server_running,
+            |adb_path| {
+                dbg_connection!("server_lease: Killing ADB server");
+                if let Err(e) = commands::kill_server(adb_path) {
+                    error!("{e:?}");
+                }
+            },
+        )
+    }
+
+    fn acquire_from(
+        users: &'static Mutex<ServerUsers>,
+        adb_path: String,
+        server_running: impl FnOnce() -> bool,
+        kill_server: fn(&str),
+    ) -> Self {
+        users.lock().unwrap().acquire(server_running);
+
+        Self {
+            adb_path,
+            users,
+            kill_server,
+            released: false,
+        }
+    }
+
+    /// Returns whether the server must be killed, which is then left to the caller, e.g. to report
+    /// the failure
+    pub(crate) fn release(mut self) -> bool {
+        self.released = true;
+
+        self.users.lock().unwrap().release()
+    }
+}
+
+impl Drop for ServerLease {
+    fn drop(&mut self) {
+        if !self.released && self.users.lock().unwrap().release() {
+            (self.kill_server)(&self.adb_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_server_lease_kills_once() {
+        static USERS: Mutex<ServerUsers> = Mutex::new(ServerUsers::new());
+        static KILLS: AtomicUsize = AtomicUsize::new(0);
+        fn kill(_: &str) {
+            KILLS.fetch_add(1, Ordering::SeqCst);
+        }
+        let acquire = || ServerLease::acquire_from(&USERS, "adb".to_owned(), || false, kill);
+
+        // Dropped in the order of creation
+        let first = acquire();
+        let second = acquire();
+        drop(first);
+        assert_eq!(KILLS.load(Ordering::SeqCst), 0);
+        drop(second);
+        assert_eq!(KILLS.load(Ordering::SeqCst), 1);
+
+        // Dropped in the reverse order
+        let first = acquire();
+        let second = acquire();
+        drop(second);
+        assert_eq!(KILLS.load(Ordering::SeqCst), 1);
+        drop(first);
+        assert_eq!(KILLS.load(Ordering::SeqCst), 2);
+
+        // Released explicitly, the last release is told to kill the server instead
+        let first = acquire();
+        let second = acquire();
+        assert!(!first.release());
+        assert!(second.release());
+        assert_eq!(KILLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_server_lease_keeps_external_server() {
+        static USERS: Mutex<ServerUsers> = Mutex::new(ServerUsers::new());
+        static KILLS: AtomicUsize = AtomicUsize::new(0);
+        fn kill(_: &str) {
+            KILLS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let first = ServerLease::acquire_from(&USERS, "adb".to_owned(), || true, kill);
+        // Only the first lease checks the server, which is running by then anyway
+        let second = ServerLease::acquire_from(&USERS, "adb".to_owned(), || false, kill);
+        drop(second);
+        drop(first);
+        assert_eq!(KILLS.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    AdbError, AdbResult, DiagnosisReport, TeardownReport, WiredCancelHandle, WiredConnection,
+    WiredConnectionStatus, WiredDiagnostics, WiredMessage, WiredSetupParams, WiredStatusKind,
+    commands, fail,
+};
+use alvr_common::anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+
+/// `WiredConnection` shared by several consumers, e.g. the connection loop and the dashboard, so
+/// that they use the same adb server, download and forwarded ports. Clones share the connection.
+///
+/// The operations that run adb run one at a time. `tick` and `setup` don't wait for another
+/// operation, they return a `WiredStatusKind::Busy` status telling what it is, e.g. "Busy:
+/// installing the client". The other operations wait for their turn. `last_status` and
+/// `diagnostics` return the state of the last operation without waiting.
+#[derive(Clone)]
+pub struct SharedWiredConnection {
+    connection: Arc<Mutex<WiredConnection>>,
+    state: Arc<Mutex<SharedState>>,
+    cancel_handle: WiredCancelHandle,
+}
+
+/// Operation holding a `SharedWiredConnection`, told by the busy statuses
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WiredOperation {
+    SettingUp,
+    TearingDown,
+    InstallingClient,
+    Diagnosing,
+    RunningCommand,
+}
+
+impl Display for WiredOperation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let operation = match self {
+            WiredOperation::SettingUp => "setting up",
+            WiredOperation::TearingDown => "tearing down",
+            WiredOperation::InstallingClient => "installing the client",
+            WiredOperation::Diagnosing => "diagnosing",
+            WiredOperation::RunningCommand => "running a command",
+        };
+
+        write!(f, "{operation}")
+    }
+}
+
+struct SharedState {
+    // Operation holding the connection
+    operation: Option<WiredOperation>,
+    last_status: Option<WiredConnectionStatus>,
+    diagnostics: WiredDiagnostics,
+}
+
+impl SharedWiredConnection {
+    pub fn new(connection: WiredConnection) -> Self {
+        let state = SharedState {
+            operation: None,
+            last_status: connection.last_status.clone(),
+            diagnostics: connection.diagnostics(),
+        };
+
+        Self {
+            cancel_handle: connection.cancel_handle(),
+            connection: Arc::new(Mutex::new(connection)),
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// See `WiredConnection::tick`
+    pub fn tick(&self) -> AdbResult<WiredConnectionStatus> {
+        match self.try_lock() {
+            Some(mut connection) => self.run(
+                &mut connection,
+                WiredOperation::SettingUp,
+                WiredConnection::tick,
+            ),
+            None => Ok(self.busy_status()),
+        }
+    }
+
+    /// Like `tick`, with other parameters than the ones of the connection
+    pub fn setup(&self, params: &WiredSetupParams) -> AdbResult<WiredConnectionStatus> {
+        match self.try_lock() {
+            Some(mut connection) => {
+                self.run(&mut connection, WiredOperation::SettingUp, |connection| {
+                    connection.setup_with_params(params)
+                })
+            }
+            None => Ok(self.busy_status()),
+        }
+    }
+
+    /// See `WiredConnection::set_setup_params`. Waits for the current operation.
+    pub fn set_setup_params(&self, params: WiredSetupParams) {
+        self.lock().set_setup_params(params);
+    }
+
+    /// See `WiredConnection::teardown`. Waits for the current operation.
+    pub fn teardown(&self) -> AdbResult<TeardownReport> {
+        self.run(
+            &mut self.lock(),
+            WiredOperation::TearingDown,
+            WiredConnection::teardown,
+        )
+    }
+
+    /// Installs an APK, APKS or APKM file on the device of the connection, like
+    /// `commands::install_package_file`. Waits for the current operation, and the setups called
+    /// meanwhile return a busy status. The cancel handle of the connection aborts it.
+    pub fn install_client(
+        &self,
+        path: &Path,
+        progress_callback: impl Fn(usize, Option<usize>),
+    ) -> AdbResult<()> {
+        self.run(
+            &mut self.lock(),
+            WiredOperation::InstallingClient,
+            |connection| {
+                let Some(device_serial) = connection.device_serial.clone() else {
+                    return fail(AdbError::Other(anyhow!(
+                        "No wired device to install the client on"
+                    )));
+                };
+                let res = commands::install_package_file(
+                    &connection.adb_path,
+                    &device_serial,
+                    path,
+                    &connection.cancel_handle,
+                    progress_callback,
+                );
+                // The client is looked up again, even if the install failed halfway
+                connection.force_refresh();
+
+                res
+            },
+        )
+    }
+
+    /// See `WiredConnection::diagnose`. Waits for the current operation, and the setups called
+    /// meanwhile return a busy status.
+    pub fn diagnose(&self) -> DiagnosisReport {
+        self.run(&mut self.lock(), WiredOperation::Diagnosing, |connection| {
+            connection.diagnose()
+        })
+    }
+
+    /// Runs `f` once the current operation finished, for the methods of `WiredConnection` that
+    /// have no shared version
+    pub fn with_connection<T>(&self, f: impl FnOnce(&mut WiredConnection) -> T) -> T {
+        self.run(&mut self.lock(), WiredOperation::RunningCommand, f)
+    }
+
+    /// Status of the last setup, None before the first one. Doesn't wait for the current
+    /// operation.
+    pub fn last_status(&self) -> Option<WiredConnectionStatus> {
+        self.state().last_status.clone()
+    }
+
+    /// See `WiredConnection::diagnostics`. Taken after the last operation, doesn't wait for the
+    /// current one.
+    pub fn diagnostics(&self) -> WiredDiagnostics {
+        self.state().diagnostics.clone()
+    }
+
+    /// Aborts the current operation, see `WiredConnection::cancel_handle`
+    pub fn cancel_handle(&self) -> WiredCancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    fn run<T>(
+        &self,
+        connection: &mut WiredConnection,
+        operation: WiredOperation,
+        f: impl FnOnce(&mut WiredConnection) -> T,
+    ) -> T {
+        self.state().operation = Some(operation);
+        let value = f(connection);
+
+        let mut state = self.state();
+        state.operation = None;
+        state.last_status = connection.last_status.clone();
+        state.diagnostics = connection.diagnostics();
+
+        value
+    }
+
+    fn busy_status(&self) -> WiredConnectionStatus {
+        WiredConnectionStatus::not_ready(
+            WiredStatusKind::Busy,
+            WiredMessage::Busy {
+                operation: self.state().operation,
+            },
+        )
+    }
+
+    // A panic in a previous operation leaves the connection usable, like the sync version
+    fn lock(&self) -> MutexGuard<'_, WiredConnection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn try_lock(&self) -> Option<MutexGuard<'_, WiredConnection>> {
+        match self.connection.try_lock() {
+            Ok(connection) => Some(connection),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SharedState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::AdbSource;
+    use crate::{AdbTransport, MockTransport};
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn test_setup_while_installing() {
+        let mock = Arc::new(MockTransport::new());
+        mock.respond(&["devices"], "List of devices attached\n");
+        let mut connection = WiredConnection::with_adb_path(
+            "adb".to_owned(),
+            AdbSource::UserProvided,
+            Some("1WMHH000000000".to_owned()),
+        );
+        connection.set_transport(Arc::clone(&mock) as Arc<dyn AdbTransport>);
+        let shared = SharedWiredConnection::new(connection);
+
+        // Stands for a long install started from another thread
+        let (started_sender, started_receiver) = mpsc::channel();
+        let (finish_sender, finish_receiver) = mpsc::channel::<()>();
+        let installer = thread::spawn({
+            let shared = shared.clone();
+            move || {
+                let mut connection = shared.lock();
+                shared.run(&mut connection, WiredOperation::InstallingClient, |_| {
+                    started_sender.send(()).unwrap();
+                    finish_receiver.recv().ok();
+                });
+            }
+        });
+        started_receiver.recv().unwrap();
+
+        let status = shared.tick().unwrap();
+        assert_eq!(
+            status,
+            WiredConnectionStatus::not_ready(
+                WiredStatusKind::Busy,
+                WiredMessage::Busy {
+                    operation: Some(WiredOperation::InstallingClient)
+                }
+            )
+        );
+        assert!(!mock.was_called(&["devices"]));
+        assert!(shared.last_status().is_none());
+
+        finish_sender.send(()).unwrap();
+        installer.join().unwrap();
+
+        let status = shared.tick().unwrap();
+        assert!(matches!(
+            status,
+            WiredConnectionStatus::NotReady {
+                kind: WiredStatusKind::NoDevice,
+                ..
+            }
+        ));
+        assert_eq!(shared.last_status(), Some(status));
+    }
+}
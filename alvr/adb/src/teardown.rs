@@ -0,0 +1,180 @@
+use crate::metrics::record_metrics;
+use crate::parse::ConnectionState;
+use crate::{AdbResult, WiredConnection, commands, restore_device_settings, with_transport};
+use alvr_common::dbg_connection;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct TeardownStep {
+    pub name: &'static str,
+    // None if the step succeeded
+    pub error: Option<String>,
+}
+
+/// Result of `WiredConnection::teardown`. Steps that had nothing to clean up are not listed.
+#[derive(Debug, Clone, Default)]
+pub struct TeardownReport {
+    pub steps: Vec<TeardownStep>,
+    // The device steps are skipped if the device was unplugged
+    pub device_connected: bool,
+    // Reports of the other devices driven by `setup_all`, by serial
+    pub other_devices: Vec<(String, TeardownReport)>,
+}
+
+impl TeardownReport {
+    pub fn succeeded(&self) -> bool {
+        self.steps.iter().all(|step| step.error.is_none())
+            && self
+                .other_devices
+                .iter()
+                .all(|(_, report)| report.succeeded())
+    }
+
+    fn record(&mut self, name: &'static str, res: AdbResult<()>) {
+        self.steps.push(TeardownStep {
+            name,
+            error: res.err().map(|e| format!("{e:#}")),
+        });
+    }
+}
+
+impl Display for TeardownReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if !self.device_connected {
+            writeln!(f, "Device: disconnected")?;
+        }
+        for step in &self.steps {
+            match &step.error {
+                Some(error) => writeln!(f, "{}: FAIL ({error})", step.name)?,
+                None => writeln!(f, "{}: done", step.name)?,
+            }
+        }
+        for (serial, report) in &self.other_devices {
+            writeln!(f, "Device {serial}:")?;
+            for line in report.to_string().lines() {
+                writeln!(f, "  {line}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl WiredConnection {
+    /// Cleans up in order: removes the forwarded ports, restores the display timeout and the
+    /// device settings, stops the client if `stop_client_on_disconnect` applies and kills the ADB
+    /// server if ALVR started it and no other connection uses it. Unlike dropping the connection, which then does
+    /// nothing, it reports the failures and waits for adb even if it hangs on a device going away.
+    /// Fails only if the devices cannot be listed, in which case the server is still killed.
+    pub fn teardown(&mut self) -> AdbResult<TeardownReport> {
+        record_metrics(Arc::clone(&self.metrics), || match self.transport.clone() {
+            Some(transport) => with_transport(transport, || self.run_teardown()),
+            None => self.run_teardown(),
+        })
+    }
+
+    fn run_teardown(&mut self) -> AdbResult<TeardownReport> {
+        self.torn_down = true;
+
+        // Nothing was set up yet
+        if self.adb_download.is_some() {
+            return Ok(TeardownReport::default());
+        }
+
+        let mut report = TeardownReport::default();
+        let devices = commands::list_devices(&self.adb_path);
+        if let Ok(devices) = &devices
+            && let Some(device_serial) = self.device_serial.clone()
+        {
+            report.device_connected = devices.iter().any(|d| {
+                d.serial.as_ref() == Some(&device_serial)
+                    && d.state == Some(ConnectionState::Device)
+            });
+
+            if report.device_connected {
+                self.teardown_device_steps(&device_serial, &mut report);
+            }
+        }
+
+        let mut other_devices = mem::take(&mut self.other_devices)
+            .into_iter()
+            .collect::<Vec<_>>();
+        other_devices.sort_by(|(serial, _), (other_serial, _)| serial.cmp(other_serial));
+        for (serial, mut connection) in other_devices {
+            let other_report = connection.run_teardown().unwrap_or_else(|e| {
+                let mut other_report = TeardownReport::default();
+                other_report.record("List devices", Err(e));
+
+                other_report
+            });
+            report.other_devices.push((serial, other_report));
+        }
+
+        self.device_lock = None;
+
+        // Other connections of this process can still use the server
+        if let Some(lease) = self.server_lease.take()
+            && lease.release()
+        {
+            dbg_connection!("wired_connection: Killing ADB server");
+            report.record("Kill ADB server", commands::kill_server(&self.adb_path));
+        }
+
+        devices?;
+
+        Ok(report)
+    }
+
+    fn teardown_device_steps(&mut self, device_serial: &str, report: &mut TeardownReport) {
+        let stream_serial = self
+            .stream_device_serial
+            .clone()
+            .unwrap_or_else(|| device_serial.to_owned());
+        let mut forwarded_ports = mem::take(&mut self.forwarded_ports)
+            .into_iter()
+            .collect::<Vec<_>>();
+        forwarded_ports.sort_unstable();
+        for port in forwarded_ports {
+            report.record(
+                "Remove port forward",
+                commands::remove_forward(&self.adb_path, &stream_serial, port),
+            );
+        }
+        self.forwards_check = None;
+
+        if let Some(value) = self.stay_on_restore_value.take() {
+            report.record(
+                "Restore display timeout",
+                commands::set_stay_on_while_plugged_in(&self.adb_path, device_serial, value),
+            );
+        }
+
+        let applied_device_settings = mem::take(&mut self.applied_device_settings);
+        if applied_device_settings
+            .iter()
+            .any(|applied| !applied.failed)
+        {
+            let failures =
+                restore_device_settings(&self.adb_path, device_serial, &applied_device_settings);
+            report.steps.push(TeardownStep {
+                name: "Restore device settings",
+                error: (!failures.is_empty()).then(|| failures.join(", ")),
+            });
+        }
+
+        if let Some(process_name) = self
+            .client_process_name
+            .take()
+            .filter(|_| self.stop_client_on_disconnect && self.client_launched)
+        {
+            dbg_connection!("wired_connection: Stopping {process_name}");
+            report.record(
+                "Stop client",
+                commands::force_stop_application(&self.adb_path, device_serial, &process_name),
+            );
+            self.client_launched = false;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::AdbTransport;
+use std::io;
+use std::process::{ExitStatus, Output};
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, Default)]
+pub struct MockResponse {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+/// Transport with scripted responses, to test the setup without adb or a device. A command gets
+/// the response of the last rule whose pattern appears in its arguments, in order and without
+/// gaps. Commands without a rule succeed with no output.
+#[derive(Default)]
+pub struct MockTransport {
+    rules: Mutex<Vec<(Vec<String>, MockResponse)>>,
+    calls: Mutex<Vec<Vec<String>>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Responds with `stdout` to the commands containing `pattern`, e.g. `&["pidof"]`
+    pub fn respond(&self, pattern: &[&str], stdout: &str) {
+        self.respond_with(
+            pattern,
+            MockResponse {
+                stdout: stdout.to_owned(),
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn respond_with(&self, pattern: &[&str], response: MockResponse) {
+        self.rules.lock().unwrap().push((
+            pattern.iter().map(|arg| (*arg).to_owned()).collect(),
+            response,
+        ));
+    }
+
+    /// Arguments of every command run so far
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn was_called(&self, pattern: &[&str]) -> bool {
+        self.calls().iter().any(|args| contains(args, pattern))
+    }
+}
+
+impl AdbTransport for MockTransport {
+    fn run_command(&self, _: &str, args: &[String]) -> io::Result<Output> {
+        self.calls.lock().unwrap().push(args.to_vec());
+
+        let response = self
+            .rules
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(pattern, _)| contains(args, pattern))
+            .map(|(_, response)| response.clone())
+            .unwrap_or_default();
+
+        Ok(Output {
+            status: exit_status(response.exit_code),
+            stdout: response.stdout.into_bytes(),
+            stderr: response.stderr.into_bytes(),
+        })
+    }
+}
+
+fn contains(args: &[String], pattern: &[impl AsRef<str>]) -> bool {
+    pattern.is_empty()
+        || args.windows(pattern.len()).any(|window| {
+            window
+                .iter()
+                .zip(pattern)
+                .all(|(arg, expected)| arg == expected.as_ref())
+        })
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+
+    ExitStatus::from_raw(code as u32)
+}
@@ -0,0 +1,56 @@
+use crate::WiredConnection;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Progress of the launch delays of a device, to carry them over to a new connection, e.g. one
+/// created with other settings. Otherwise the new connection waits for the pre-launch delay again
+/// although the device stayed connected. See `WiredConnection::timing_state`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WiredTimingState {
+    // The state is ignored by the connections that find another device
+    pub device_serial: String,
+    pub since_device_found: Duration,
+    pub since_launch: Option<Duration>,
+    pub launch_attempts: u32,
+}
+
+impl WiredConnection {
+    /// None until a device is found
+    pub fn timing_state(&self) -> Option<WiredTimingState> {
+        Some(WiredTimingState {
+            device_serial: self.device_serial.clone()?,
+            since_device_found: self.device_found_time.elapsed(),
+            since_launch: self.launch_time.map(|time| time.elapsed()),
+            launch_attempts: self.launch_attempts,
+        })
+    }
+
+    /// Continues the launch delays of `state` once the setup finds its device. The time between
+    /// `timing_state` and this call is not counted.
+    pub fn restore_timing_state(&mut self, state: WiredTimingState) {
+        let device_serial = state.device_serial.clone();
+        self.restored_timing_state = Some((state, Instant::now()));
+
+        if self.device_serial.as_ref() == Some(&device_serial) {
+            self.apply_timing_state(&device_serial);
+        }
+    }
+
+    // Called when the setup finds a device, after its state was reset
+    pub(crate) fn apply_timing_state(&mut self, device_serial: &str) {
+        let Some((state, restore_time)) = self.restored_timing_state.take() else {
+            return;
+        };
+        if state.device_serial != device_serial {
+            return;
+        }
+
+        if let Some(time) = restore_time.checked_sub(state.since_device_found) {
+            self.device_found_time = time;
+        }
+        self.launch_time = state
+            .since_launch
+            .and_then(|since_launch| restore_time.checked_sub(since_launch));
+        self.launch_attempts = state.launch_attempts;
+    }
+}
@@ -0,0 +1,154 @@
+use crate::metrics;
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::process::{Command, Output, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+// Default of `set_max_capture_size`. The largest outputs of the setup are the ones of dumpsys,
+// which are under a megabyte on the usual devices.
+const DEFAULT_MAX_CAPTURE_SIZE: usize = 16 * 1024 * 1024;
+
+static MAX_CAPTURE_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_CAPTURE_SIZE);
+
+/// Limits the size of the stdout and of the stderr kept from each adb command, so that a runaway
+/// command can't exhaust the memory. The rest of the output is read and discarded, and replaced by
+/// a marker telling how many bytes were dropped. Applies to the whole process, and only to the
+/// commands run by `ProcessTransport`. The streaming commands are not limited.
+pub fn set_max_capture_size(size: usize) {
+    MAX_CAPTURE_SIZE.store(size, Ordering::Relaxed);
+}
+
+/// Runs the adb commands that return their whole output once they exit, which are all the commands
+/// of the setup. Commands that stream their output (file transfers with progress, logcat, install
+/// sessions) still spawn adb directly.
+pub trait AdbTransport: Send + Sync {
+    /// Runs adb with `args` and waits for it to exit. The exit status is not checked.
+    fn run_command(&self, adb_path: &str, args: &[String]) -> io::Result<Output>;
+}
+
+/// Spawns the adb executable, used unless another transport is set
+pub struct ProcessTransport;
+
+impl AdbTransport for ProcessTransport {
+    fn run_command(&self, adb_path: &str, args: &[String]) -> io::Result<Output> {
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+        output_capped(&mut crate::commands::get_command(adb_path, &args))
+    }
+}
+
+// Like `Command::output`, but keeps at most `MAX_CAPTURE_SIZE` bytes of each output
+fn output_capped(command: &mut Command) -> io::Result<Output> {
+    let max_size = MAX_CAPTURE_SIZE.load(Ordering::Relaxed);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Both pipes are read at the same time, otherwise adb blocks once the other one is full
+    let stderr = child.stderr.take();
+    let stderr_thread = thread::spawn(move || match stderr {
+        Some(stderr) => read_capped(stderr, max_size),
+        None => Ok(vec![]),
+    });
+    let stdout = match child.stdout.take() {
+        Some(stdout) => read_capped(stdout, max_size),
+        None => Ok(vec![]),
+    };
+    let stderr = stderr_thread
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("Failed to read stderr")));
+    let status = child.wait()?;
+
+    Ok(Output {
+        status,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+// Reads `reader` to the end, keeping the first `max_size` bytes followed by a marker if there were
+// more
+fn read_capped(mut reader: impl Read, max_size: usize) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    reader
+        .by_ref()
+        .take(max_size as u64)
+        .read_to_end(&mut data)?;
+
+    let dropped_size = io::copy(&mut reader, &mut io::sink())?;
+    if dropped_size > 0 {
+        data.extend_from_slice(format!("\n[{dropped_size} bytes truncated]\n").as_bytes());
+    }
+
+    Ok(data)
+}
+
+thread_local! {
+    static TRANSPORT: RefCell<Option<Arc<dyn AdbTransport>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with the commands called on this thread going through `transport`, e.g. to call the
+/// free functions of `commands` with a `MockTransport`
+pub fn with_transport<T>(transport: Arc<dyn AdbTransport>, f: impl FnOnce() -> T) -> T {
+    let previous = TRANSPORT.with(|current| current.replace(Some(transport)));
+    // Restored even if `f` panics, so that a failed test doesn't leak its mock
+    let _restore = RestoreTransport(previous);
+
+    f()
+}
+
+struct RestoreTransport(Option<Arc<dyn AdbTransport>>);
+
+impl Drop for RestoreTransport {
+    fn drop(&mut self) {
+        TRANSPORT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+// Transport set on this thread, e.g. to pass it to other threads
+pub(crate) fn current_transport() -> Option<Arc<dyn AdbTransport>> {
+    TRANSPORT.with(|current| current.borrow().clone())
+}
+
+pub(crate) fn run_command(command: &mut Command) -> io::Result<Output> {
+    let args = command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    let start_time = Instant::now();
+    let res = match current_transport() {
+        Some(transport) => transport.run_command(&command.get_program().to_string_lossy(), &args),
+        None => output_capped(command),
+    };
+    metrics::record_call(&args, start_time.elapsed());
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_capped() {
+        assert_eq!(
+            read_capped(Cursor::new("0123456789"), 16).unwrap(),
+            b"0123456789"
+        );
+        assert_eq!(
+            read_capped(Cursor::new("0123456789"), 10).unwrap(),
+            b"0123456789"
+        );
+        assert_eq!(
+            read_capped(Cursor::new("0123456789"), 4).unwrap(),
+            b"0123\n[6 bytes truncated]\n"
+        );
+    }
+}
@@ -162,7 +162,7 @@ fn install_and_launch_apk(
     }
 
     let layout = alvr_filesystem::Layout::new(&root);
-    let adb_path = alvr_adb::commands::require_adb(&layout, |downloaded, total| {
+    let (adb_path, _) = alvr_adb::commands::require_adb(&layout, |downloaded, total| {
         let progress = total.map_or(0.0, |t| downloaded as f32 / t as f32);
         worker_message_sender
             .send(WorkerMessage::ProgressUpdate(Progress {
@@ -202,9 +202,27 @@ fn install_and_launch_apk(
         message: "Installing new APK".into(),
         progress: 0.0,
     }))?;
-    alvr_adb::commands::install_package(&adb_path, &device_serial, &apk_path.to_string_lossy())?;
+    alvr_adb::commands::install_package_file(
+        &adb_path,
+        &device_serial,
+        &apk_path,
+        &alvr_adb::WiredCancelHandle::default(),
+        |written, total| {
+            let progress = total.map_or(0.0, |t| written as f32 / t as f32);
+            worker_message_sender
+                .send(WorkerMessage::ProgressUpdate(Progress {
+                    message: "Installing new APK".into(),
+                    progress,
+                }))
+                .ok();
+        },
+    )?;
 
-    alvr_adb::commands::start_application(&adb_path, &device_serial, application_id)?;
+    if let alvr_adb::parse::LaunchResult::Failed(error) =
+        alvr_adb::commands::start_application(&adb_path, &device_serial, application_id)?
+    {
+        bail!("Failed to start the client: {error:?}");
+    }
 
     Ok(())
 }
@@ -7,7 +7,9 @@ use crate::{
     statistics::StatisticsManager,
     tracking::{self, TrackingManager},
 };
-use alvr_adb::{WiredConnection, WiredConnectionStatus};
+use alvr_adb::{
+    SharedWiredConnection, WiredConnection, WiredConnectionStatus, WiredEvent, WiredSetupParams,
+};
 use alvr_common::{
     AnyhowToCon, BUTTON_INFO, CONTROLLER_PROFILE_INFO, ConResult, ConnectionError, ConnectionState,
     LifecycleState, QUEST_CONTROLLER_PROFILE_PATH, con_bail, dbg_connection, debug, error,
@@ -263,49 +265,52 @@ pub fn handshake_loop(ctx: Arc<ConnectionContext>, lifecycle_state: Arc<RwLock<L
             })
         {
             // Make sure the wired connection is created once and kept alive
-            let wired_connection = if let Some(connection) = &wired_connection {
+            let wired_connection = if let Some(connection) = &mut wired_connection {
                 connection
             } else {
-                let connection = match WiredConnection::new(
-                    FILESYSTEM_LAYOUT.get().unwrap(),
-                    |downloaded, maybe_total| {
-                        if let Some(total) = maybe_total {
-                            alvr_events::send_event(EventType::Adb(AdbEvent {
-                                download_progress: downloaded as f32 / total as f32,
-                            }));
-                        };
-                    },
-                ) {
-                    Ok(connection) => connection,
-                    Err(e) => {
-                        error!("{e:?}");
-                        thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
-                        continue;
-                    }
-                };
+                // ADB is installed in the background, the setup reports the progress meanwhile
+                let connection = WiredConnection::builder()
+                    // Shown as notifications in the dashboard. Errors are already logged below.
+                    .on_event(|event| {
+                        if !matches!(event, WiredEvent::Error { .. }) {
+                            info!("{event}");
+                        }
+                    })
+                    .build_in_background(
+                        FILESYSTEM_LAYOUT.get().unwrap(),
+                        |downloaded, maybe_total| {
+                            if let Some(total) = maybe_total {
+                                alvr_events::send_event(EventType::Adb(AdbEvent {
+                                    download_progress: downloaded as f32 / total as f32,
+                                }));
+                            };
+                        },
+                    );
 
-                wired_connection = Some(connection);
+                // Shared with the dashboard requests
+                let connection = SharedWiredConnection::new(connection);
+                *ctx.wired_connection.lock() = Some(connection.clone());
 
-                wired_connection.as_ref().unwrap()
+                wired_connection.insert(connection)
             };
 
-            let stream_port;
-            let client_type;
-            let client_autolaunch;
-            {
+            // The settings can change between ticks
+            let setup_params = {
                 let session_manager_lock = SESSION_MANAGER.read();
                 let connection = &session_manager_lock.settings().connection;
-                stream_port = connection.stream_port;
-                client_type = connection.wired_client_type.clone();
-                client_autolaunch = connection.wired_client_autolaunch.as_option().cloned();
-            }
 
-            let status = match wired_connection.setup(
-                CONTROL_PORT,
-                stream_port,
-                &client_type,
-                client_autolaunch,
-            ) {
+                WiredSetupParams::new(CONTROL_PORT, connection.stream_port)
+                    .client_type(connection.wired_client_type.clone())
+                    .allow_background_client(connection.wired_allow_background_client)
+                    .keep_display_awake(connection.wired_keep_display_awake)
+                    .autolaunch(connection.wired_client_autolaunch.as_option().cloned())
+                    .device_settings(connection.wired_device_settings.clone())
+                    .pre_launch_shell(connection.wired_pre_launch_shell.clone())
+                    .client_permissions(connection.wired_client_permissions.clone())
+            };
+
+            // Returns a busy status instead of waiting for an install started from the dashboard
+            let status = match wired_connection.setup(&setup_params) {
                 Ok(status) => status,
                 Err(e) => {
                     error!("{e:?}");
@@ -315,8 +320,12 @@ pub fn handshake_loop(ctx: Arc<ConnectionContext>, lifecycle_state: Arc<RwLock<L
             };
 
             #[cfg_attr(not(debug_assertions), expect(unused_variables))]
-            if let WiredConnectionStatus::NotReady(s) = status {
-                dbg_connection!("handshake_loop: Wired connection not ready: {s}");
+            if let WiredConnectionStatus::NotReady { kind, message } = status {
+                dbg_connection!(
+                    "handshake_loop: Wired connection not ready ({kind:?}, pinned device {:?}): \
+                    {message}",
+                    wired_connection.diagnostics().pinned_device_serial
+                );
                 thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
                 continue;
             }
@@ -442,6 +451,15 @@ pub fn handshake_loop(ctx: Arc<ConnectionContext>, lifecycle_state: Arc<RwLock<L
         }
     }
 
+    *ctx.wired_connection.lock() = None;
+    if let Some(connection) = wired_connection.take() {
+        match connection.teardown() {
+            Ok(report) if !report.succeeded() => warn!("Wired connection teardown:\n{report}"),
+            Ok(_) => (),
+            Err(e) => warn!("Failed to tear down the wired connection: {e:?}"),
+        }
+    }
+
     alvr_common::dbg_connection!("handshake_loop: Joining connection threads");
 
     // At this point, LIFECYCLE_STATE == ShuttingDown, so all threads are already terminating
@@ -2,6 +2,7 @@ use crate::{
     ConnectionContext, FILESYSTEM_LAYOUT, SESSION_MANAGER, ServerCoreEvent,
     logging_backend::EVENTS_SENDER,
 };
+use alvr_adb::{DiagnosisReport, SharedWiredConnection, WiredDiagnostics};
 use alvr_common::{ConnectionState, LogEntry, anyhow::Result, error, info, log};
 use alvr_events::{ButtonEvent, EventType};
 use alvr_packets::{ButtonEntry, ClientConnectionsAction, FirewallRulesAction, PathValuePair};
@@ -19,7 +20,7 @@ use axum::{
 };
 use serde_json as json;
 use std::{net::SocketAddr, path::PathBuf, sync::Arc};
-use tokio::{net::TcpListener, sync::broadcast::error::RecvError};
+use tokio::{net::TcpListener, sync::broadcast::error::RecvError, task};
 use tower_http::{
     cors::{self, CorsLayer},
     set_header::SetResponseHeaderLayer,
@@ -78,6 +79,13 @@ pub async fn web_server(connection_context: Arc<ConnectionContext>) -> Result<()
                         ),
                 )
                 .route("/buttons", routing::post(set_buttons))
+                .nest(
+                    "/wired",
+                    Router::new()
+                        .route("/diagnostics", routing::get(get_wired_diagnostics))
+                        .route("/diagnose", routing::post(diagnose_wired_connection))
+                        .route("/select-device", routing::post(select_wired_device)),
+                )
                 .route("/insert-idr", routing::post(insert_idr))
                 .route("/capture-frame", routing::post(capture_frame))
                 .nest(
@@ -265,6 +273,58 @@ async fn shutdown_steamvr(State(ctx): State<Arc<ConnectionContext>>) {
         .ok();
 }
 
+// Null while there is no wired connection
+async fn get_wired_diagnostics(
+    State(ctx): State<Arc<ConnectionContext>>,
+) -> Json<Option<WiredDiagnostics>> {
+    Json(
+        ctx.wired_connection
+            .lock()
+            .as_ref()
+            .map(SharedWiredConnection::diagnostics),
+    )
+}
+
+// Checks the wired setup without changing anything on the headset. Null while there is no wired
+// connection.
+async fn diagnose_wired_connection(
+    State(ctx): State<Arc<ConnectionContext>>,
+) -> Json<Option<DiagnosisReport>> {
+    let Some(connection) = ctx.wired_connection.lock().clone() else {
+        return Json(None);
+    };
+
+    match task::spawn_blocking(move || connection.diagnose()).await {
+        Ok(report) => Json(Some(report)),
+        Err(e) => {
+            error!("Failed to diagnose the wired connection: {e}");
+
+            Json(None)
+        }
+    }
+}
+
+// Sets up the wired headset picked in the dashboard instead of the one the setup kept
+async fn select_wired_device(
+    State(ctx): State<Arc<ConnectionContext>>,
+    Json(serial): Json<String>,
+) {
+    let Some(connection) = ctx.wired_connection.lock().clone() else {
+        error!("Failed to select the wired device: no wired connection");
+        return;
+    };
+
+    let res = task::spawn_blocking(move || {
+        connection.with_connection(|connection| connection.select_device(serial))
+    })
+    .await;
+    match res {
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => error!("Failed to select the wired device: {e:?}"),
+        Err(e) => error!("Failed to select the wired device: {e}"),
+    }
+}
+
 async fn set_buttons(
     State(ctx): State<Arc<ConnectionContext>>,
     Json(button_events): Json<Vec<ButtonEvent>>,
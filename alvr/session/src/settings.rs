@@ -1314,12 +1314,79 @@ pub enum SocketBufferSize {
     Custom(#[schema(suffix = "B")] u32),
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[schema(gui = "button_group")]
+pub enum DeviceSettingNamespace {
+    System,
+    Secure,
+    Global,
+    #[schema(strings(display_name = "System property"))]
+    Property,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct WiredDeviceSetting {
+    pub namespace: DeviceSettingNamespace,
+    pub key: String,
+    pub value: String,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct WiredClientAutoLaunchConfig {
     #[schema(strings(
         help = "Delay in seconds to wait after booting the headset before trying to launch the client."
     ))]
     pub boot_delay: u32,
+
+    #[schema(strings(
+        help = "Delay in seconds to wait after the headset is detected before launching the client."
+    ))]
+    #[schema(gui(slider(min = 0, max = 120)), suffix = "s")]
+    pub pre_launch_delay: u32,
+
+    #[schema(strings(
+        help = "Delay in seconds to wait after launching the client before trying to launch it again."
+    ))]
+    #[schema(gui(slider(min = 0, max = 120)), suffix = "s")]
+    pub post_launch_delay: u32,
+
+    #[schema(strings(
+        help = "Number of launch attempts before giving up when the client does not start. The delay between attempts doubles each time."
+    ))]
+    #[schema(gui(slider(min = 1, max = 20)))]
+    pub max_launch_attempts: u32,
+
+    #[schema(strings(
+        help = "Force-stop and relaunch the client after it has not been responding for this many seconds."
+    ))]
+    #[schema(suffix = "s")]
+    pub unresponsive_restart_delay: Switch<u32>,
+
+    #[schema(strings(
+        help = "Force-stop the client when the streamer closes the wired connection, instead of leaving it on the connection screen. Only a client launched by the streamer is stopped."
+    ))]
+    pub stop_client_on_disconnect: bool,
+
+    #[schema(strings(
+        help = "Force-stop and relaunch the client when it is still running a build older than the installed one."
+    ))]
+    pub restart_outdated_client: bool,
+
+    #[schema(strings(
+        help = "Force-stop these apps before launching the client if they are running. Other streaming apps can hold the VR runtime and prevent the client from starting."
+    ))]
+    pub stop_competing_apps: Switch<Vec<String>>,
+
+    #[schema(strings(
+        help = "Bring the client back to the foreground when it is paused, e.g. after opening the home menu, at most once every this many seconds. This stops if the client is paused again right after."
+    ))]
+    #[schema(suffix = "s")]
+    pub foreground_paused_client: Switch<u32>,
+
+    #[schema(strings(
+        help = "Launch the client on the display with this id, for devices with more than one display. The displays are listed in the wired connection self test. The default display is used if it doesn't exist."
+    ))]
+    pub launch_display: Switch<u32>,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -1333,10 +1400,36 @@ TCP: Slower than UDP, but more stable. Pick this if you experience video or audi
     pub client_discovery: Switch<DiscoveryConfig>,
 
     #[schema(strings(
-        help = r#"Which release type of client should ALVR look for when establishing a wired connection."#
+        help = r#"Which release type of client should ALVR look for when establishing a wired connection.
+Custom: package name of the client. Append "/<activity>" to launch a specific activity, e.g. "alvr.client/android.app.NativeActivity"."#
     ))]
     pub wired_client_type: ClientFlavor,
 
+    #[schema(strings(
+        help = r#"Keep streaming over the wired connection when the client is visible but not focused, for example when a panel is opened next to it. If disabled the client is reported as paused in that case."#
+    ))]
+    pub wired_allow_background_client: bool,
+
+    #[schema(strings(
+        help = r#"Keep the headset display on while it is powered over USB and the wired connection is ready, so it doesn't doze off when the proximity sensor is uncovered. The previous setting is restored when the streamer closes."#
+    ))]
+    pub wired_keep_display_awake: bool,
+
+    #[schema(strings(
+        help = r#"Android settings and system properties to change while the wired connection is ready, e.g. "debug.oculus.refreshRate" or "debug.oculus.foveation.level". Invalid keys are skipped with a warning. The previous values are restored when the streamer closes."#
+    ))]
+    pub wired_device_settings: Vec<WiredDeviceSetting>,
+
+    #[schema(strings(
+        help = r#"Shell commands to run on the headset before ALVR launches the client, e.g. "setprop debug.example 1". Each command is logged. If one fails, the launch is reported as failed and retried."#
+    ))]
+    pub wired_pre_launch_shell: Vec<String>,
+
+    #[schema(strings(
+        help = r#"Runtime permissions to grant to the client when the wired connection finds it, e.g. "android.permission.RECORD_AUDIO". Missing permissions are granted again after the client data is cleared, without reinstalling it."#
+    ))]
+    pub wired_client_permissions: Vec<String>,
+
     #[schema(strings(
         help = r#"Wether ALVR should try to automatically launch the client when establishing a wired connection."#
     ))]
@@ -2090,9 +2183,63 @@ pub fn session_settings_default() -> SettingsDefault {
                     ClientFlavorDefaultVariant::Github
                 },
             },
+            wired_allow_background_client: false,
+            wired_keep_display_awake: false,
+            wired_device_settings: VectorDefault {
+                gui_collapsed: true,
+                element: WiredDeviceSettingDefault {
+                    namespace: DeviceSettingNamespaceDefault {
+                        variant: DeviceSettingNamespaceDefaultVariant::Property,
+                    },
+                    key: "".into(),
+                    value: "".into(),
+                },
+                content: vec![],
+            },
+            wired_pre_launch_shell: VectorDefault {
+                gui_collapsed: true,
+                element: String::new(),
+                content: vec![],
+            },
+            wired_client_permissions: VectorDefault {
+                gui_collapsed: true,
+                element: String::new(),
+                content: vec![],
+            },
             wired_client_autolaunch: SwitchDefault {
                 enabled: true,
-                content: WiredClientAutoLaunchConfigDefault { boot_delay: 0 },
+                content: WiredClientAutoLaunchConfigDefault {
+                    boot_delay: 0,
+                    pre_launch_delay: 0,
+                    post_launch_delay: 0,
+                    max_launch_attempts: 5,
+                    unresponsive_restart_delay: SwitchDefault {
+                        enabled: false,
+                        content: 10,
+                    },
+                    stop_client_on_disconnect: false,
+                    restart_outdated_client: false,
+                    stop_competing_apps: SwitchDefault {
+                        enabled: false,
+                        content: VectorDefault {
+                            gui_collapsed: true,
+                            element: String::new(),
+                            content: vec![
+                                "VirtualDesktop.Android".into(),
+                                "com.valvesoftware.steamlinkvr".into(),
+                                "com.oculus.xrstreamingclient".into(),
+                            ],
+                        },
+                    },
+                    foreground_paused_client: SwitchDefault {
+                        enabled: false,
+                        content: 30,
+                    },
+                    launch_display: SwitchDefault {
+                        enabled: false,
+                        content: 0,
+                    },
+                },
             },
             web_server_port: 8082,
             stream_port: 9944,
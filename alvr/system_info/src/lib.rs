@@ -12,6 +12,11 @@ pub const PACKAGE_NAME_STORE: &str = "alvr.client";
 pub const PACKAGE_NAME_GITHUB_DEV: &str = "alvr.client.dev";
 pub const PACKAGE_NAME_GITHUB_STABLE: &str = "alvr.client.stable";
 
+// cargo-apk declares the VR activity of the clients with the default NativeActivity class
+pub const ACTIVITY_NAME_STORE: &str = "android.app.NativeActivity";
+pub const ACTIVITY_NAME_GITHUB_DEV: &str = "android.app.NativeActivity";
+pub const ACTIVITY_NAME_GITHUB_STABLE: &str = "android.app.NativeActivity";
+
 // Platform of the device. It is used to match the VR runtime and enable features conditionally.
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum Platform {